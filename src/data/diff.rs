@@ -0,0 +1,206 @@
+use crate::{GroundTruth, Metric, QuerySet};
+use std::cmp::max;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Differences between two versions of a query set.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct QuerySetDiff {
+    /// Whether the query points differ.
+    pub points_differ: bool,
+    /// Metrics whose ground truth exists only in the second query set. This and the other lists
+    /// of metrics are in the order of `Metric`'s `Ord`.
+    pub added_metrics: Vec<Metric>,
+    /// Metrics whose ground truth exists only in the first query set.
+    pub removed_metrics: Vec<Metric>,
+    /// Metrics whose ground truth exists in both query sets but differs, along with the fraction
+    /// of rows (i.e., queries) whose neighbors differ.
+    pub changed_ground_truth: Vec<(Metric, f32)>,
+}
+
+impl QuerySetDiff {
+    pub(crate) fn new<DataType: Clone + PartialEq>(
+        first: &QuerySet<DataType>,
+        second: &QuerySet<DataType>,
+    ) -> QuerySetDiff {
+        let first_gts = first.get_ground_truths();
+        let second_gts = second.get_ground_truths();
+
+        let mut added_metrics = second_gts
+            .keys()
+            .filter(|metric| !first_gts.contains_key(metric))
+            .cloned()
+            .collect::<Vec<_>>();
+        added_metrics.sort();
+
+        let mut removed_metrics = first_gts
+            .keys()
+            .filter(|metric| !second_gts.contains_key(metric))
+            .cloned()
+            .collect::<Vec<_>>();
+        removed_metrics.sort();
+
+        let mut changed_ground_truth = first_gts
+            .iter()
+            .filter_map(|(metric, gt)| {
+                let other = second_gts.get(metric)?;
+                if gt == other {
+                    return None;
+                }
+                Some((metric.clone(), fraction_of_differing_rows(gt, other)))
+            })
+            .collect::<Vec<_>>();
+        changed_ground_truth.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        QuerySetDiff {
+            points_differ: first.get_points() != second.get_points(),
+            added_metrics,
+            removed_metrics,
+            changed_ground_truth,
+        }
+    }
+
+    /// Returns `true` if the two query sets are identical.
+    pub fn is_identical(&self) -> bool {
+        !self.points_differ
+            && self.added_metrics.is_empty()
+            && self.removed_metrics.is_empty()
+            && self.changed_ground_truth.is_empty()
+    }
+}
+
+/// Returns the fraction of rows whose neighbors differ between two ground-truth sets. Rows that
+/// exist in only one of the two sets count as differing.
+fn fraction_of_differing_rows(first: &GroundTruth, second: &GroundTruth) -> f32 {
+    let first = first.get_neighbors();
+    let second = second.get_neighbors();
+    let num_rows = max(first.nrows(), second.nrows());
+    if num_rows == 0 {
+        return 0_f32;
+    }
+
+    let differing = (0..num_rows)
+        .filter(|&i| i >= first.nrows() || i >= second.nrows() || first.row(i) != second.row(i))
+        .count();
+    differing as f32 / num_rows as f32
+}
+
+/// Differences between two versions of an ANN dataset.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct DatasetDiff {
+    /// Whether the data points differ.
+    pub data_points_differ: bool,
+    /// Labels of query sets that exist only in the second dataset.
+    pub added_query_sets: Vec<String>,
+    /// Labels of query sets that exist only in the first dataset.
+    pub removed_query_sets: Vec<String>,
+    /// Labels of query sets that exist in both datasets but differ, sorted by label.
+    pub changed_query_sets: Vec<(String, QuerySetDiff)>,
+    /// Names of data variants that exist only in the second dataset.
+    pub added_data_variants: Vec<String>,
+    /// Names of data variants that exist only in the first dataset.
+    pub removed_data_variants: Vec<String>,
+    /// Names of data variants that exist in both datasets but differ.
+    pub changed_data_variants: Vec<String>,
+    /// Keys of results that are recorded only in the second dataset.
+    pub added_results: Vec<String>,
+    /// Keys of results that are recorded only in the first dataset.
+    pub removed_results: Vec<String>,
+    /// Keys of results that are recorded in both datasets but differ.
+    pub changed_results: Vec<String>,
+}
+
+impl DatasetDiff {
+    /// Returns `true` if the two datasets are identical.
+    pub fn is_identical(&self) -> bool {
+        !self.data_points_differ
+            && self.added_query_sets.is_empty()
+            && self.removed_query_sets.is_empty()
+            && self.changed_query_sets.is_empty()
+            && self.added_data_variants.is_empty()
+            && self.removed_data_variants.is_empty()
+            && self.changed_data_variants.is_empty()
+            && self.added_results.is_empty()
+            && self.removed_results.is_empty()
+            && self.changed_results.is_empty()
+    }
+}
+
+/// Returns the keys that exist only in `second`, the keys that exist only in `first`, and the
+/// keys whose values differ, each in ascending order.
+pub(crate) fn diff_keys<K: Ord + ToString, V: PartialEq>(
+    first: &BTreeMap<K, V>,
+    second: &BTreeMap<K, V>,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let only_in = |a: &BTreeMap<K, V>, b: &BTreeMap<K, V>| {
+        a.keys()
+            .filter(|key| !b.contains_key(*key))
+            .map(|key| key.to_string())
+            .collect::<Vec<_>>()
+    };
+    let changed = first
+        .iter()
+        .filter(|(key, value)| second.get(*key).is_some_and(|other| other != *value))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    (only_in(second, first), only_in(first, second), changed)
+}
+
+fn join<T: ToString>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Display for QuerySetDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let points = if self.points_differ {
+            "differ"
+        } else {
+            "are identical"
+        };
+        write!(
+            f,
+            "Query points {}; Added ground-truths: [{}]; Removed ground-truths: [{}]; \
+            Changed ground-truths: [{}]",
+            points,
+            join(&self.added_metrics),
+            join(&self.removed_metrics),
+            self.changed_ground_truth
+                .iter()
+                .map(|(metric, fraction)| format!("{}: {:.2}% of rows", metric, fraction * 100.0))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl Display for DatasetDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let data_points = if self.data_points_differ {
+            "differ"
+        } else {
+            "are identical"
+        };
+        write!(
+            f,
+            "Data points {}\nAdded query sets: [{}]\nRemoved query sets: [{}]\n\
+            Added data variants: [{}]\nRemoved data variants: [{}]\nChanged data variants: [{}]\n\
+            Added results: [{}]\nRemoved results: [{}]\nChanged results: [{}]",
+            data_points,
+            join(&self.added_query_sets),
+            join(&self.removed_query_sets),
+            join(&self.added_data_variants),
+            join(&self.removed_data_variants),
+            join(&self.changed_data_variants),
+            join(&self.added_results),
+            join(&self.removed_results),
+            join(&self.changed_results),
+        )?;
+        self.changed_query_sets
+            .iter()
+            .try_for_each(|(label, diff)| write!(f, "\n{}: {}", label, diff))
+    }
+}
@@ -1,20 +1,51 @@
+use crate::data::diff::{diff_keys, DatasetDiff, QuerySetDiff};
 use crate::data::AnnDataset;
+use crate::io::gzip::{decompress_to_temp_file, is_gzip};
+use crate::io::inspect::DatasetInfo;
 use crate::io::Hdf5File;
-use crate::{Hdf5Serialization, PointSet, QuerySet};
+use crate::types::point_set::{inspect_point_set, link_external_point_set};
+use crate::types::query_set::inspect_query_set;
+use crate::types::query_set::QuerySetBuffer;
+use crate::{GroundTruth, Hdf5Serialization, Metric, PointSet, QuerySet};
 use anyhow::{anyhow, Result};
-use hdf5::{File, Group, H5Type};
+use hdf5::types::VarLenUnicode;
+use hdf5::{Group, H5Type};
+use ndarray_rand::rand::rngs::StdRng;
+use ndarray_rand::rand::seq::index::sample;
+use ndarray_rand::rand::SeedableRng;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::min;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::fmt::Formatter;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::iter::zip;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc::Receiver;
 
 const QUERY_SETS: &str = "query_sets";
+const DATA_VARIANTS: &str = "data_variants";
+const RESULTS: &str = "results";
+const RESULT_KEYS: &str = "keys";
+const RESULT_VALUES: &str = "values";
 
 /// An ANN dataset.
-#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+///
+/// Recorded results are compared bit by bit, so that equality is reflexive even if a result is
+/// NaN.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InMemoryAnnDataset<DataType: Clone> {
     data_points: PointSet<DataType>,
-    query_sets: HashMap<String, QuerySet<DataType>>,
+    /// Query sets keyed by label, kept sorted so that output is deterministic.
+    query_sets: BTreeMap<String, QuerySet<DataType>>,
+    /// Alternative versions of the data points, such as their normalized form, keyed by name.
+    data_variants: BTreeMap<String, PointSet<DataType>>,
+    /// Results, such as the recall achieved by a system, recorded for archival and keyed by a
+    /// free-form description.
+    results: BTreeMap<String, f32>,
 }
 
 impl<DataType: Clone> InMemoryAnnDataset<DataType> {
@@ -42,7 +73,333 @@ impl<DataType: Clone> InMemoryAnnDataset<DataType> {
     pub fn create(data_points: PointSet<DataType>) -> InMemoryAnnDataset<DataType> {
         InMemoryAnnDataset {
             data_points,
-            query_sets: HashMap::new(),
+            query_sets: BTreeMap::new(),
+            data_variants: BTreeMap::new(),
+            results: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a variant of the data points with the given `name`, such as their L2-normalized
+    /// form, or replaces one if it already exists. The primary data points remain the default.
+    ///
+    /// Returns an error if `points` does not have as many points as the primary data points, as
+    /// the `i`-th point of every variant must correspond to the `i`-th data point.
+    pub fn add_data_variant(&mut self, name: &str, points: PointSet<DataType>) -> Result<()> {
+        if points.num_points() != self.data_points.num_points() {
+            return Err(anyhow!(
+                "Variant '{}' has {} points but there are {} data points.",
+                name,
+                points.num_points(),
+                self.data_points.num_points()
+            ));
+        }
+        self.data_variants.insert(name.to_string(), points);
+        Ok(())
+    }
+
+    /// Returns the variant of the data points with the given `name`, or an error if it does not
+    /// exist.
+    pub fn get_data_variant(&self, name: &str) -> Result<&PointSet<DataType>> {
+        self.data_variants
+            .get(name)
+            .ok_or_else(|| anyhow!("Data variant '{}' does not exist.", name))
+    }
+
+    /// Records a result achieved on this dataset, such as the recall of a system, under the given
+    /// `key`, or replaces the result if one already exists. Results are stored along with the
+    /// dataset, so that benchmark outcomes can be archived with the data that produced them.
+    ///
+    /// Keys are free-form, but a description such as `"system@metric@k"` makes results easy to
+    /// tell apart.
+    pub fn record_result(&mut self, key: &str, value: f32) {
+        self.results.insert(key.to_string(), value);
+    }
+
+    /// Returns all recorded results, sorted by key.
+    pub fn get_results(&self) -> &BTreeMap<String, f32> {
+        &self.results
+    }
+
+    /// Returns an iterator over all query sets along with their labels, sorted by label.
+    pub fn query_sets(&self) -> impl Iterator<Item = (&str, &QuerySet<DataType>)> {
+        self.query_sets
+            .iter()
+            .map(|(label, query_set)| (label.as_str(), query_set))
+    }
+
+    /// Creates a small version of this dataset, useful for tests and CI, that has at most
+    /// `max_points` data points, at most `max_queries` query points per query set, and at most
+    /// `k` neighbors per query in each ground-truth set. Data and query points are sampled
+    /// uniformly at random using the given `seed`, preserving their relative order.
+    ///
+    /// Note that this operation is lossy. Ground-truth neighbors that are not among the sampled
+    /// data points are dropped and the remaining ids are remapped to the new data point ids, so
+    /// that every row holds the exact nearest neighbors within the sampled points, along with
+    /// their distances if recorded. Each row keeps up to `k` of its remaining neighbors, and the
+    /// ground truth is ragged if rows end up with different numbers of neighbors. If the sample
+    /// is much smaller than the original dataset, some queries may have few or no neighbors.
+    pub fn make_tiny(
+        &self,
+        max_points: usize,
+        max_queries: usize,
+        k: usize,
+        seed: u64,
+    ) -> Result<InMemoryAnnDataset<DataType>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let num_points = self.data_points.num_points();
+
+        let mut point_ids = sample(&mut rng, num_points, min(max_points, num_points)).into_vec();
+        point_ids.sort_unstable();
+        let mut new_ids = vec![None; num_points];
+        point_ids
+            .iter()
+            .enumerate()
+            .for_each(|(new_id, &id)| new_ids[id] = Some(new_id));
+
+        let mut query_sets = BTreeMap::new();
+        for (label, query_set) in &self.query_sets {
+            let num_queries = query_set.get_points().num_points();
+            let mut query_ids =
+                sample(&mut rng, num_queries, min(max_queries, num_queries)).into_vec();
+            query_ids.sort_unstable();
+
+            let mut tiny = QuerySet::new(query_set.get_points().select(&query_ids));
+            for (metric, gt) in query_set.get_ground_truths() {
+                // The new id of every kept neighbor along with its column in the original row.
+                let kept = query_ids
+                    .iter()
+                    .map(|&id| {
+                        gt.neighbors_of(id)
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(column, &neighbor)| {
+                                new_ids
+                                    .get(neighbor)
+                                    .copied()
+                                    .flatten()
+                                    .map(|new_id| (new_id, column))
+                            })
+                            .take(k)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                let rows = kept
+                    .iter()
+                    .map(|row| row.iter().map(|&(new_id, _)| new_id).collect())
+                    .collect();
+                let tiny_gt = match gt.get_distances() {
+                    None => GroundTruth::new_ragged(rows),
+                    Some(distances) => {
+                        let distances = query_ids
+                            .iter()
+                            .zip(&kept)
+                            .map(|(&id, row)| {
+                                row.iter()
+                                    .map(|&(_, column)| distances[[id, column]])
+                                    .collect()
+                            })
+                            .collect();
+                        GroundTruth::new_ragged_with_distances(rows, distances)?
+                    }
+                };
+                tiny.insert_ground_truth(metric.clone(), tiny_gt)?;
+            }
+            query_sets.insert(label.clone(), tiny);
+        }
+
+        Ok(InMemoryAnnDataset {
+            data_points: self.data_points.select(&point_ids),
+            query_sets,
+            data_variants: self
+                .data_variants
+                .iter()
+                .map(|(name, points)| (name.clone(), points.select(&point_ids)))
+                .collect(),
+            // Results achieved on this dataset do not carry over to a subset of it.
+            results: BTreeMap::new(),
+        })
+    }
+
+    /// Appends every query set received from `receiver` to the query set with the given `label`,
+    /// creating the latter from the first received query set if it does not already exist.
+    ///
+    /// This method blocks until all senders are dropped. Returns an error if a received query set
+    /// can not be appended to the existing query set, in which case query sets received so far
+    /// remain appended.
+    pub fn add_query_sets(
+        &mut self,
+        label: &str,
+        receiver: Receiver<QuerySet<DataType>>,
+    ) -> Result<()> {
+        for query_set in receiver {
+            self.check_dimensions(&query_set)?;
+            match self.query_sets.get_mut(label) {
+                None => {
+                    self.query_sets.insert(label.to_string(), query_set);
+                }
+                Some(set) => set.append(&query_set)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the mean recall of `retrieved` against the ground truth for `metric` of the query
+    /// set with the given `label`. See `GroundTruth::mean_recall`.
+    ///
+    /// Returns an error, naming the label and metric, if the query set or its ground truth for
+    /// `metric` does not exist, or if `retrieved` does not have one retrieved set per query.
+    pub fn mean_recall(
+        &self,
+        label: &str,
+        metric: &Metric,
+        retrieved: &[Vec<usize>],
+    ) -> Result<f32> {
+        let gt = self.labeled_ground_truth(label, metric)?;
+        gt.mean_recall(retrieved).map_err(|e| {
+            anyhow!(
+                "Failed to compute recall for query set '{}' with {}: {}",
+                label,
+                metric,
+                e
+            )
+        })
+    }
+
+    /// Returns the ground truth for `metric` of the query set with the given `label`, or an error
+    /// naming the label and metric if either does not exist.
+    fn labeled_ground_truth(&self, label: &str, metric: &Metric) -> Result<&GroundTruth> {
+        let query_set = self
+            .query_sets
+            .get(label)
+            .ok_or_else(|| anyhow!("Query set '{}' does not exist.", label))?;
+        query_set
+            .get_ground_truth(metric)
+            .map_err(|_| anyhow!("Query set '{}' has no ground truth for {}.", label, metric))
+    }
+
+    /// Returns an error if the points in `query_set` do not have the same dense and sparse
+    /// dimensions as the data points.
+    fn check_dimensions(&self, query_set: &QuerySet<DataType>) -> Result<()> {
+        let data_points = &self.data_points;
+        let query_points = query_set.get_points();
+        let components =
+            |points: &PointSet<DataType>| match (points.get_dense(), points.get_sparse()) {
+                (Some(_), Some(_)) => "dense and sparse",
+                (Some(_), None) => "dense",
+                (None, Some(_)) => "sparse",
+                (None, None) => "no",
+            };
+        if components(data_points) != components(query_points) {
+            return Err(anyhow!(
+                "Data points have {} components but query points have {} components.",
+                components(data_points),
+                components(query_points)
+            ));
+        }
+        if data_points.num_dense_dimensions() != query_points.num_dense_dimensions()
+            || data_points.num_sparse_dimensions() != query_points.num_sparse_dimensions()
+        {
+            return Err(anyhow!(
+                "Data points have {} dense and {} sparse dimensions but query points have {} dense \
+                and {} sparse dimensions.",
+                data_points.num_dense_dimensions(),
+                data_points.num_sparse_dimensions(),
+                query_points.num_dense_dimensions(),
+                query_points.num_sparse_dimensions()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Drops the exact nearest neighbors for all metrics that are not in `keep` from every query
+    /// set in the dataset. See `QuerySet::retain_metrics`.
+    pub fn retain_metrics(&mut self, keep: &[Metric]) {
+        self.query_sets
+            .values_mut()
+            .for_each(|query_set| query_set.retain_metrics(keep));
+    }
+
+    /// Same as `add_query_sets` but preallocates buffers for `expected_points` query points in
+    /// total, including any points already in the query set with the given `label`.
+    ///
+    /// This avoids repeated reallocation when receiving many small query sets. The buffers still
+    /// grow if more than `expected_points` points arrive. Returns an error if a received query set
+    /// can not be appended, in which case the existing query set is left unchanged.
+    pub fn add_query_sets_with_hint(
+        &mut self,
+        label: &str,
+        receiver: Receiver<QuerySet<DataType>>,
+        expected_points: usize,
+    ) -> Result<()> {
+        let mut buffer = QuerySetBuffer::with_capacity(expected_points);
+        if let Some(set) = self.query_sets.get(label) {
+            buffer.push(set)?;
+        }
+        for query_set in receiver {
+            self.check_dimensions(&query_set)?;
+            buffer.push(&query_set)?;
+        }
+        if let Some(set) = buffer.finish()? {
+            self.query_sets.insert(label.to_string(), set);
+        }
+        Ok(())
+    }
+}
+
+impl<DataType: Clone + PartialEq> InMemoryAnnDataset<DataType> {
+    /// Compares this dataset against `other` and reports how `other` differs from it.
+    ///
+    /// The report includes whether the data points differ, which query sets were added or
+    /// removed, and, for every query set that exists in both datasets but differs, which
+    /// ground-truth sets were added, removed, or changed. It also lists the data variants and the
+    /// keys of recorded results that were added, removed, or changed.
+    pub fn diff(&self, other: &InMemoryAnnDataset<DataType>) -> DatasetDiff {
+        let mut added_query_sets = other
+            .query_sets
+            .keys()
+            .filter(|label| !self.query_sets.contains_key(*label))
+            .cloned()
+            .collect::<Vec<_>>();
+        added_query_sets.sort();
+
+        let mut removed_query_sets = self
+            .query_sets
+            .keys()
+            .filter(|label| !other.query_sets.contains_key(*label))
+            .cloned()
+            .collect::<Vec<_>>();
+        removed_query_sets.sort();
+
+        let mut changed_query_sets = self
+            .query_sets
+            .iter()
+            .filter_map(|(label, query_set)| {
+                let diff = QuerySetDiff::new(query_set, other.query_sets.get(label)?);
+                if diff.is_identical() {
+                    return None;
+                }
+                Some((label.clone(), diff))
+            })
+            .collect::<Vec<_>>();
+        changed_query_sets.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let (added_data_variants, removed_data_variants, changed_data_variants) =
+            diff_keys(&self.data_variants, &other.data_variants);
+        let (added_results, removed_results, changed_results) =
+            diff_keys(&result_bits(&self.results), &result_bits(&other.results));
+
+        DatasetDiff {
+            data_points_differ: self.data_points != other.data_points,
+            added_query_sets,
+            removed_query_sets,
+            changed_query_sets,
+            added_data_variants,
+            removed_data_variants,
+            changed_data_variants,
+            added_results,
+            removed_results,
+            changed_results,
         }
     }
 }
@@ -76,10 +433,12 @@ impl<DataType: Clone> AnnDataset<DataType> for InMemoryAnnDataset<DataType> {
     /// let mut dataset = InMemoryAnnDataset::create(data_points);
     ///
     /// let query_set = QuerySet::new(query_points);
-    /// dataset.add_query_set("train", query_set);
+    /// dataset.add_query_set("train", query_set).expect("Query points must match data points.");
     /// ```
-    fn add_query_set(&mut self, label: &str, query_set: QuerySet<DataType>) {
+    fn add_query_set(&mut self, label: &str, query_set: QuerySet<DataType>) -> Result<()> {
+        self.check_dimensions(&query_set)?;
         self.query_sets.insert(label.to_string(), query_set);
+        Ok(())
     }
 
     fn get_query_set(&self, label: &str) -> Result<&QuerySet<DataType>> {
@@ -90,37 +449,189 @@ impl<DataType: Clone> AnnDataset<DataType> for InMemoryAnnDataset<DataType> {
     }
 }
 
-impl<DataType: Clone + H5Type> Hdf5Serialization for InMemoryAnnDataset<DataType> {
-    type Object = InMemoryAnnDataset<DataType>;
+impl<DataType: Clone + H5Type> InMemoryAnnDataset<DataType> {
+    /// Stores the dataset as an HDF5 file at `path`, omitting the ground truth of all query sets.
+    ///
+    /// This supports publishing data and query points while keeping the ground truth private.
+    /// Reading the file back yields query sets without ground truth.
+    pub fn write_without_gt(&self, path: &str) -> Result<()> {
+        let file = hdf5::File::create(path)?;
+        let mut root = file.group("/")?;
+        self.add_to_with_gt(&mut root, false)?;
+        file.close()?;
+        Ok(())
+    }
 
-    fn add_to(&self, group: &mut Group) -> Result<()> {
+    /// Stores the query sets and data variants of the dataset as an HDF5 file at `path`, while
+    /// the data points are stored as HDF5 external links to the data points at the root of the
+    /// HDF5 file `corpus_path`, for example one written by `write`. This allows many query files
+    /// to share a single large corpus. Reading the file at `path` with `read` resolves the links
+    /// transparently, provided the corpus can be found.
+    ///
+    /// If `corpus_path` is relative, it is interpreted relative to the directory of `path`, which
+    /// is also where HDF5 first looks for the corpus when resolving the links (unless the
+    /// `HDF5_EXT_PREFIX` environment variable is set). The two files then remain linked as long as
+    /// they are moved together. If `corpus_path` is absolute, the corpus must stay where it is.
+    ///
+    /// Returns an error if the corpus does not contain data points with the same number of points
+    /// and the same dense and sparse dimensions as this dataset. Only metadata is compared; the
+    /// vectors themselves are not read.
+    pub fn write_with_external_data(&self, path: &str, corpus_path: &str) -> Result<()> {
+        let resolved = Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(corpus_path);
+        let info = inspect_point_set(&hdf5::File::open(&resolved)?.group("/")?)?;
+        let data_points = &self.data_points;
+        if info.num_points != data_points.num_points()
+            || info.dense_dimensions != data_points.get_dense().map(|dense| dense.ncols())
+            || info.sparse_dimensions != data_points.get_sparse().map(|sparse| sparse.cols())
+        {
+            return Err(anyhow!(
+                "Data points in {} do not match the data points of the dataset.",
+                resolved.display()
+            ));
+        }
+
+        let file = hdf5::File::create(path)?;
+        let mut root = file.group("/")?;
+        link_external_point_set(&root, corpus_path, &info)?;
+        self.add_query_sets_to(&mut root, true)?;
+        file.close()?;
+        Ok(())
+    }
+
+    /// Adds the dataset to the given HDF5 `group`, including the ground truth of query sets only
+    /// if `include_gt` is `true`.
+    fn add_to_with_gt(&self, group: &mut Group, include_gt: bool) -> Result<()> {
         self.data_points.add_to(group)?;
+        self.add_query_sets_to(group, include_gt)
+    }
 
+    /// Adds everything but the data points to the given HDF5 `group`: the query sets, including
+    /// their ground truth only if `include_gt` is `true`, the data variants, and the results.
+    fn add_query_sets_to(&self, group: &mut Group, include_gt: bool) -> Result<()> {
         let query_group = group.create_group(QUERY_SETS)?;
         self.query_sets.iter().try_for_each(|entry| {
             let mut grp = query_group.create_group(entry.0)?;
-            entry.1.add_to(&mut grp)?;
+            if include_gt {
+                entry.1.add_to(&mut grp)?;
+            } else {
+                entry.1.add_to_without_gt(&mut grp)?;
+            }
             anyhow::Ok(())
         })?;
+
+        if !self.data_variants.is_empty() {
+            let variant_group = group.create_group(DATA_VARIANTS)?;
+            self.data_variants.iter().try_for_each(|(name, points)| {
+                let mut grp = variant_group.create_group(name)?;
+                points.add_to(&mut grp)?;
+                anyhow::Ok(())
+            })?;
+        }
+
+        // Results are stored as datasets rather than attributes, which are limited in size.
+        if !self.results.is_empty() {
+            let result_group = group.create_group(RESULTS)?;
+            let keys = self
+                .results
+                .keys()
+                .map(|key| VarLenUnicode::from_str(key))
+                .collect::<Result<Vec<_>, _>>()?;
+            let values = self.results.values().copied().collect::<Vec<_>>();
+            result_group
+                .new_dataset::<VarLenUnicode>()
+                .shape(keys.len())
+                .create(RESULT_KEYS)?
+                .write(keys.as_slice())?;
+            result_group
+                .new_dataset::<f32>()
+                .shape(values.len())
+                .create(RESULT_VALUES)?
+                .write(values.as_slice())?;
+        }
         Ok(())
     }
+}
+
+/// Summarizes the dataset stored in `group` from HDF5 metadata alone, without reading any
+/// vectors or ground truth.
+pub(crate) fn inspect_dataset(group: &Group) -> Result<DatasetInfo> {
+    let data_points = inspect_point_set(group)?;
+    let mut query_sets = match group.group(QUERY_SETS) {
+        Ok(query_group) => query_group
+            .groups()?
+            .iter()
+            .map(|grp| {
+                let name = grp.name();
+                inspect_query_set(grp, name.split('/').next_back().unwrap())
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Err(_) => vec![],
+    };
+    query_sets.sort_by(|a, b| a.label.cmp(&b.label));
+    Ok(DatasetInfo {
+        data_points,
+        query_sets,
+    })
+}
+
+impl<DataType: Clone + H5Type> Hdf5Serialization for InMemoryAnnDataset<DataType> {
+    type Object = InMemoryAnnDataset<DataType>;
+
+    fn add_to(&self, group: &mut Group) -> Result<()> {
+        self.add_to_with_gt(group, true)
+    }
 
     fn read_from(group: &Group) -> Result<Self::Object> {
         let data_points = PointSet::<DataType>::read_from(group)?;
 
-        let mut query_sets: HashMap<String, QuerySet<DataType>> = HashMap::new();
-        let query_group = group.group(QUERY_SETS)?;
-        query_group.groups()?.iter().try_for_each(|grp| {
-            let name = grp.name();
-            let name = name.split('/').last().unwrap();
-            let query_set = QuerySet::<DataType>::read_from(grp)?;
-            query_sets.insert(name.to_string(), query_set);
-            anyhow::Ok(())
-        })?;
+        // A missing query set group is treated as a dataset without query sets.
+        let mut query_sets: BTreeMap<String, QuerySet<DataType>> = BTreeMap::new();
+        if let Ok(query_group) = group.group(QUERY_SETS) {
+            query_group.groups()?.iter().try_for_each(|grp| {
+                let name = grp.name();
+                let name = name.split('/').next_back().unwrap();
+                let query_set = QuerySet::<DataType>::read_from(grp)?;
+                query_sets.insert(name.to_string(), query_set);
+                anyhow::Ok(())
+            })?;
+        }
+
+        // A missing variant group is treated as a dataset without data variants.
+        let mut data_variants: BTreeMap<String, PointSet<DataType>> = BTreeMap::new();
+        if let Ok(variant_group) = group.group(DATA_VARIANTS) {
+            variant_group.groups()?.iter().try_for_each(|grp| {
+                let name = grp.name();
+                let name = name.split('/').next_back().unwrap();
+                data_variants.insert(name.to_string(), PointSet::<DataType>::read_from(grp)?);
+                anyhow::Ok(())
+            })?;
+        }
+
+        // A missing result group is treated as a dataset without results.
+        let mut results = BTreeMap::new();
+        if let Ok(result_group) = group.group(RESULTS) {
+            let keys = result_group
+                .dataset(RESULT_KEYS)?
+                .read_raw::<VarLenUnicode>()?;
+            let values = result_group.dataset(RESULT_VALUES)?.read_raw::<f32>()?;
+            if keys.len() != values.len() {
+                return Err(anyhow!(
+                    "There are {} result keys but {} result values.",
+                    keys.len(),
+                    values.len()
+                ));
+            }
+            results.extend(zip(keys, values).map(|(key, value)| (key.to_string(), value)));
+        }
 
         Ok(InMemoryAnnDataset {
             data_points,
             query_sets,
+            data_variants,
+            results,
         })
     }
 
@@ -133,7 +644,7 @@ impl<DataType: Clone + H5Type> Hdf5File for InMemoryAnnDataset<DataType> {
     type Object = InMemoryAnnDataset<DataType>;
 
     fn write(&self, path: &str) -> Result<()> {
-        let file = File::create(path)?;
+        let file = hdf5::File::create(path)?;
         let mut root = file.group("/")?;
         Hdf5Serialization::add_to(self, &mut root)?;
         file.close()?;
@@ -141,12 +652,106 @@ impl<DataType: Clone + H5Type> Hdf5File for InMemoryAnnDataset<DataType> {
     }
 
     fn read(path: &str) -> Result<Self::Object> {
-        let hdf5_dataset = File::open(path)?;
+        if is_gzip(path) {
+            let (_dir, decompressed) = decompress_to_temp_file(path)?;
+            let decompressed = decompressed
+                .to_str()
+                .ok_or_else(|| anyhow!("Invalid temporary path for {}", path))?;
+            return Self::read(decompressed);
+        }
+        let hdf5_dataset = hdf5::File::open(path)?;
         let root = hdf5_dataset.group("/")?;
         <InMemoryAnnDataset<DataType> as Hdf5Serialization>::read_from(&root)
     }
 }
 
+impl InMemoryAnnDataset<f32> {
+    /// Evaluates a retrieval method end to end on the query set with the given `label`.
+    ///
+    /// Invokes `retrieve` on the query points of the set, where the `i`-th entry of its result
+    /// must hold the ids of the points retrieved for the `i`-th query, and returns the mean
+    /// recall at `k` against the ground truth for `metric`. Only the first `k` points of every
+    /// retrieved set are considered, and a query for which fewer points are retrieved is
+    /// penalized accordingly. See `GroundTruth::mean_recall_at_k`.
+    ///
+    /// Returns an error if the query set or its ground truth for `metric` does not exist, or if
+    /// `retrieve` does not return one retrieved set per query.
+    pub fn evaluate<F>(&self, label: &str, metric: &Metric, k: usize, retrieve: F) -> Result<f32>
+    where
+        F: Fn(&PointSet<f32>) -> Vec<Vec<usize>>,
+    {
+        let gt = self.labeled_ground_truth(label, metric)?;
+        gt.mean_recall_at_k(&retrieve(self.query_sets[label].get_points()), k)
+    }
+
+    /// Evaluates a retrieval method on every query set and every metric the set has ground truth
+    /// for, returning the mean recall at `k` of each pair keyed by the label of the query set and
+    /// the metric. See `evaluate` for how recall is computed.
+    ///
+    /// `retrieve` receives the label, the metric and the query points, so that callers may route
+    /// queries accordingly. It is invoked exactly once per pair, sequentially: query sets are
+    /// visited in order of their labels and, within a query set, metrics in their natural order.
+    /// The returned map itself carries no order.
+    ///
+    /// Returns an error as soon as `retrieve` does not return one retrieved set per query.
+    pub fn evaluate_all<F>(&self, retrieve: F, k: usize) -> Result<HashMap<(String, Metric), f32>>
+    where
+        F: Fn(&str, &Metric, &PointSet<f32>) -> Vec<Vec<usize>>,
+    {
+        let mut report = HashMap::new();
+        for (label, query_set) in &self.query_sets {
+            for (metric, _) in query_set.sorted_ground_truths() {
+                let recall = self.evaluate(label, metric, k, |queries: &PointSet<f32>| {
+                    retrieve(label, metric, queries)
+                })?;
+                report.insert((label.clone(), metric.clone()), recall);
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl<DataType: Clone + Serialize + DeserializeOwned> InMemoryAnnDataset<DataType> {
+    /// Stores the dataset in bincode format at `path`.
+    ///
+    /// Bincode is a compact binary encoding that is much faster to write and read than HDF5,
+    /// making it suitable for caching intermediate datasets within a Rust pipeline. Unlike HDF5,
+    /// however, bincode files are not portable: they may not be readable on a different
+    /// architecture or by a different version of this library, so they should not be used to
+    /// distribute datasets.
+    pub fn write_bincode(&self, path: &str) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a dataset stored in bincode format at `path` by `write_bincode`.
+    pub fn read_bincode(path: &str) -> Result<InMemoryAnnDataset<DataType>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(bincode::deserialize_from(reader)?)
+    }
+}
+
+impl<DataType: Clone + PartialEq> PartialEq for InMemoryAnnDataset<DataType> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data_points == other.data_points
+            && self.query_sets == other.query_sets
+            && self.data_variants == other.data_variants
+            && result_bits(&self.results) == result_bits(&other.results)
+    }
+}
+
+impl<DataType: Clone + Eq> Eq for InMemoryAnnDataset<DataType> {}
+
+/// Returns the bits of every result, so that results can be compared exactly.
+fn result_bits(results: &BTreeMap<String, f32>) -> BTreeMap<&str, u32> {
+    results
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.to_bits()))
+        .collect()
+}
+
 impl<DataType: Clone> fmt::Display for InMemoryAnnDataset<DataType> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
@@ -166,11 +771,14 @@ impl<DataType: Clone> fmt::Display for InMemoryAnnDataset<DataType> {
 mod tests {
     use crate::data::in_memory_dataset::InMemoryAnnDataset;
     use crate::data::AnnDataset;
-    use crate::{Hdf5File, PointSet, QuerySet};
-    use ndarray::Array2;
+    use crate::{GroundTruth, Hdf5File, Hdf5Serialization, Metric, PointSet, QuerySet};
+    use hdf5::File;
+    use ndarray::{arr2, Array2};
     use ndarray_rand::rand_distr::Uniform;
     use ndarray_rand::RandomExt;
     use sprs::{CsMat, TriMat};
+    use std::cell::RefCell;
+    use std::sync::mpsc::channel;
     use tempdir::TempDir;
 
     fn sample_data_points() -> PointSet<f32> {
@@ -203,25 +811,56 @@ mod tests {
         assert!(dataset.get_test_query_set().is_err());
 
         let query_points = sample_data_points();
-        dataset.add_train_query_set(QuerySet::new(query_points.clone()));
+        dataset
+            .add_train_query_set(QuerySet::new(query_points.clone()))
+            .unwrap();
         assert!(dataset.get_train_query_set().is_ok());
         let copy = dataset.get_train_query_set().unwrap();
         assert_eq!(&query_points, copy.get_points());
 
         // Replace an existing query set.
         let query_points = sample_data_points();
-        dataset.add_train_query_set(QuerySet::new(query_points.clone()));
+        dataset
+            .add_train_query_set(QuerySet::new(query_points.clone()))
+            .unwrap();
         assert!(dataset.get_train_query_set().is_ok());
         let copy = dataset.get_train_query_set().unwrap();
         assert_eq!(&query_points, copy.get_points());
     }
 
+    #[test]
+    fn test_query_sets() {
+        let mut dataset = InMemoryAnnDataset::<f32>::create(sample_data_points());
+        assert_eq!(dataset.query_sets().count(), 0);
+
+        dataset
+            .add_train_query_set(QuerySet::new(sample_data_points()))
+            .unwrap();
+        dataset
+            .add_test_query_set(QuerySet::new(sample_data_points()))
+            .unwrap();
+        dataset
+            .add_query_set("custom", QuerySet::new(sample_data_points()))
+            .unwrap();
+
+        let labels = dataset
+            .query_sets()
+            .map(|(label, query_set)| {
+                assert_eq!(dataset.get_query_set(label).unwrap(), query_set);
+                label
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["custom", "test_query_set", "train_query_set"]);
+    }
+
     #[test]
     fn test_write() {
         let data_points = sample_data_points();
         let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
         let query_points = sample_data_points();
-        dataset.add_train_query_set(QuerySet::new(query_points.clone()));
+        dataset
+            .add_train_query_set(QuerySet::new(query_points.clone()))
+            .unwrap();
 
         let dir = TempDir::new("test_write").unwrap();
         let path = dir.path().join("ann-dataset.hdf5");
@@ -241,4 +880,826 @@ mod tests {
             dataset.get_train_query_set().unwrap().get_points()
         );
     }
+
+    #[test]
+    fn test_data_variants() {
+        let data_points = sample_data_points();
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        assert!(dataset.get_data_variant("normalized").is_err());
+
+        let mut normalized = data_points.clone();
+        normalized.l2_normalize_inplace();
+        dataset
+            .add_data_variant("normalized", normalized.clone())
+            .unwrap();
+        assert_eq!(dataset.get_data_variant("normalized").unwrap(), &normalized);
+        assert_eq!(dataset.get_data_points(), &data_points);
+
+        assert!(dataset
+            .add_data_variant("subset", data_points.select(&[0, 1]))
+            .is_err());
+        assert!(dataset.get_data_variant("subset").is_err());
+
+        let tiny = dataset.make_tiny(2, 2, 1, 0).unwrap();
+        assert_eq!(tiny.get_data_variant("normalized").unwrap().num_points(), 2);
+    }
+
+    #[test]
+    fn test_hdf5_data_variants() {
+        let data_points = sample_data_points();
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        let mut normalized = data_points.clone();
+        normalized.l2_normalize_inplace();
+        dataset.add_data_variant("normalized", normalized).unwrap();
+
+        let dir = TempDir::new("test_hdf5_data_variants").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        dataset.write(path).unwrap();
+        assert_eq!(InMemoryAnnDataset::<f32>::read(path).unwrap(), dataset);
+    }
+
+    #[test]
+    fn test_results() {
+        let mut dataset = InMemoryAnnDataset::<f32>::create(sample_data_points());
+        assert!(dataset.get_results().is_empty());
+
+        dataset.record_result("hnsw@euclidean@10", 0.5);
+        dataset.record_result("ivf@inner-product@10", 0.9);
+        dataset.record_result("hnsw@euclidean@10", 0.95);
+        assert_eq!(
+            dataset.get_results().iter().collect::<Vec<_>>(),
+            vec![
+                (&"hnsw@euclidean@10".to_string(), &0.95_f32),
+                (&"ivf@inner-product@10".to_string(), &0.9_f32),
+            ]
+        );
+        assert!(dataset
+            .make_tiny(2, 2, 1, 0)
+            .unwrap()
+            .get_results()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_hdf5_results() {
+        let mut dataset = InMemoryAnnDataset::<f32>::create(sample_data_points());
+        let dir = TempDir::new("test_hdf5_results").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        dataset.write(path).unwrap();
+        assert!(InMemoryAnnDataset::<f32>::read(path)
+            .unwrap()
+            .get_results()
+            .is_empty());
+
+        dataset.record_result("hnsw@euclidean@10", 0.95);
+        dataset.record_result("ivf@inner-product@100", 0.875);
+        dataset.write(path).unwrap();
+        let copy = InMemoryAnnDataset::<f32>::read(path).unwrap();
+        assert_eq!(copy.get_results(), dataset.get_results());
+        assert_eq!(copy, dataset);
+
+        // Far more results than fit in the 64 KiB of attributes of an HDF5 object header.
+        (0..10_000).for_each(|run| {
+            dataset.record_result(&format!("system/run-{:05}@euclidean@10", run), run as f32)
+        });
+        dataset.write(path).unwrap();
+        let copy = InMemoryAnnDataset::<f32>::read(path).unwrap();
+        assert_eq!(copy.get_results().len(), 10_002);
+        assert_eq!(copy, dataset);
+    }
+
+    #[test]
+    fn test_display_order() {
+        let data_points = sample_data_points();
+        let mut query_set = QuerySet::new(sample_data_points());
+        query_set
+            .add_ground_truth(Metric::Euclidean, Array2::<usize>::zeros((4, 1)))
+            .unwrap();
+        query_set
+            .add_ground_truth(Metric::InnerProduct, Array2::<usize>::ones((4, 1)))
+            .unwrap();
+
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        let mut other = InMemoryAnnDataset::<f32>::create(data_points);
+        for label in ["b", "c", "a"] {
+            dataset.add_query_set(label, query_set.clone()).unwrap();
+        }
+        for label in ["a", "c", "b"] {
+            other.add_query_set(label, query_set.clone()).unwrap();
+        }
+
+        let display = dataset.to_string();
+        assert_eq!(display, other.to_string());
+        let positions = ["\na: ", "\nb: ", "\nc: "]
+            .iter()
+            .map(|label| display.find(label).unwrap())
+            .collect::<Vec<_>>();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_hdf5_deterministic_order() {
+        let points = sample_data_points();
+        let create = |labels: [&str; 3], metrics: [Metric; 3]| {
+            let mut dataset = InMemoryAnnDataset::<f32>::create(points.clone());
+            for label in labels {
+                let mut query_set = QuerySet::new(points.clone());
+                for metric in metrics.clone() {
+                    query_set
+                        .add_ground_truth(metric, Array2::<usize>::zeros((4, 1)))
+                        .unwrap();
+                }
+                dataset.add_query_set(label, query_set).unwrap();
+            }
+            dataset
+        };
+        let first = create(
+            ["label-c", "label-a", "label-b"],
+            [Metric::InnerProduct, Metric::Cosine, Metric::Euclidean],
+        );
+        let second = create(
+            ["label-b", "label-c", "label-a"],
+            [Metric::Euclidean, Metric::InnerProduct, Metric::Cosine],
+        );
+
+        // `member_names` is always sorted by name, so the order in which groups were created is
+        // recovered from the file itself: link names are appended to the heap of their parent
+        // group as they are created.
+        let dir = TempDir::new("test_hdf5_deterministic_order").unwrap();
+        let offsets = |dataset: &InMemoryAnnDataset<f32>, name: &str| {
+            let path = dir.path().join(name);
+            let path = path.to_str().unwrap();
+            dataset.write(path).unwrap();
+            let bytes = std::fs::read(path).unwrap();
+            [
+                "label-a",
+                "label-b",
+                "label-c",
+                "euclidean",
+                "cosine",
+                "inner-product",
+            ]
+            .iter()
+            .map(|needle| {
+                bytes
+                    .windows(needle.len())
+                    .position(|window| window == needle.as_bytes())
+                    .unwrap()
+            })
+            .collect::<Vec<_>>()
+        };
+        let first = offsets(&first, "first.hdf5");
+        assert!(first[0] < first[1] && first[1] < first[2]);
+        assert!(first[3] < first[4] && first[4] < first[5]);
+        assert_eq!(first, offsets(&second, "second.hdf5"));
+    }
+
+    #[test]
+    fn test_hdf5_external_data() {
+        let data_points = sample_data_points();
+        let corpus = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points);
+        let mut query_set = QuerySet::new(sample_data_points());
+        query_set
+            .add_ground_truth(Metric::InnerProduct, Array2::<usize>::zeros((4, 2)))
+            .unwrap();
+        dataset.add_test_query_set(query_set).unwrap();
+
+        let dir = TempDir::new("test_hdf5_external_data").unwrap();
+        let corpus_path = dir.path().join("corpus.hdf5");
+        let path = dir.path().join("queries.hdf5");
+        corpus.write(corpus_path.to_str().unwrap()).unwrap();
+        dataset
+            .write_with_external_data(path.to_str().unwrap(), "corpus.hdf5")
+            .unwrap();
+        assert!(
+            std::fs::metadata(&path).unwrap().len()
+                < std::fs::metadata(&corpus_path).unwrap().len()
+        );
+        assert_eq!(
+            InMemoryAnnDataset::<f32>::read(path.to_str().unwrap()).unwrap(),
+            dataset
+        );
+
+        // Moving both files together keeps the link intact.
+        let moved = dir.path().join("moved");
+        std::fs::create_dir(&moved).unwrap();
+        std::fs::rename(&corpus_path, moved.join("corpus.hdf5")).unwrap();
+        std::fs::rename(&path, moved.join("queries.hdf5")).unwrap();
+        let path = moved.join("queries.hdf5");
+        assert_eq!(
+            InMemoryAnnDataset::<f32>::read(path.to_str().unwrap()).unwrap(),
+            dataset
+        );
+
+        // A corpus with different data points is rejected.
+        let other = InMemoryAnnDataset::<f32>::create(dataset.select(&[0, 1]));
+        let other_path = moved.join("other.hdf5");
+        other.write(other_path.to_str().unwrap()).unwrap();
+        assert!(dataset
+            .write_with_external_data(path.to_str().unwrap(), "other.hdf5")
+            .is_err());
+    }
+
+    #[test]
+    fn test_write_without_gt() {
+        let data_points = sample_data_points();
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        let query_points = sample_data_points();
+        let mut query_set = QuerySet::new(query_points.clone());
+        query_set
+            .add_ground_truth(Metric::InnerProduct, Array2::<usize>::zeros((4, 2)))
+            .unwrap();
+        dataset.add_test_query_set(query_set).unwrap();
+
+        let dir = TempDir::new("test_write_without_gt").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        assert!(dataset.write_without_gt(path).is_ok());
+
+        let dataset = InMemoryAnnDataset::<f32>::read(path).unwrap();
+        assert_eq!(&data_points, dataset.get_data_points());
+        let query_set = dataset.get_test_query_set().unwrap();
+        assert_eq!(&query_points, query_set.get_points());
+        assert!(query_set.get_ground_truth(&Metric::InnerProduct).is_err());
+    }
+
+    #[test]
+    fn test_bincode() {
+        let mut dataset = InMemoryAnnDataset::<f32>::create(sample_data_points());
+        let mut query_set = QuerySet::new(sample_data_points());
+        query_set
+            .add_ground_truth(
+                Metric::InnerProduct,
+                arr2(&[[0_usize, 1], [1, 2], [2, 3], [3, 0]]),
+            )
+            .unwrap();
+        dataset.add_train_query_set(query_set).unwrap();
+        dataset
+            .add_test_query_set(QuerySet::new(sample_data_points()))
+            .unwrap();
+
+        let dir = TempDir::new("test_bincode").unwrap();
+        let path = dir.path().join("ann-dataset.bin");
+        let path = path.to_str().unwrap();
+
+        dataset.write_bincode(path).unwrap();
+        let copy = InMemoryAnnDataset::<f32>::read_bincode(path).unwrap();
+        assert_eq!(dataset, copy);
+
+        assert!(InMemoryAnnDataset::<f32>::read_bincode(
+            dir.path().join("missing.bin").to_str().unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let data_points = sample_data_points();
+        let mut query_set = QuerySet::new(sample_data_points());
+        query_set
+            .add_ground_truth(
+                Metric::Euclidean,
+                arr2(&[[0_usize, 1], [1, 2], [2, 3], [3, 0]]),
+            )
+            .unwrap();
+        query_set
+            .add_ground_truth(Metric::Cosine, Array2::zeros((4, 2)))
+            .unwrap();
+
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        dataset.add_train_query_set(query_set.clone()).unwrap();
+        dataset.add_test_query_set(query_set.clone()).unwrap();
+        assert!(dataset.diff(&dataset.clone()).is_identical());
+
+        let mut other = InMemoryAnnDataset::<f32>::create(data_points);
+        query_set
+            .add_ground_truth(
+                Metric::Euclidean,
+                arr2(&[[0_usize, 1], [1, 3], [2, 3], [3, 0]]),
+            )
+            .unwrap();
+        for metric in [Metric::InnerProduct, Metric::Chebyshev, Metric::Hamming] {
+            query_set
+                .add_ground_truth(metric, Array2::zeros((4, 2)))
+                .unwrap();
+        }
+        other.add_train_query_set(query_set.clone()).unwrap();
+        other.add_validation_query_set(query_set).unwrap();
+
+        let diff = dataset.diff(&other);
+        assert!(!diff.is_identical());
+        assert!(!diff.data_points_differ);
+        assert_eq!(
+            diff.added_query_sets,
+            vec!["validation_query_set".to_string()]
+        );
+        assert_eq!(diff.removed_query_sets, vec!["test_query_set".to_string()]);
+        assert_eq!(diff.changed_query_sets.len(), 1);
+
+        let (label, query_set_diff) = &diff.changed_query_sets[0];
+        assert_eq!(label, "train_query_set");
+        assert!(!query_set_diff.points_differ);
+        // Metrics are in the order of `Metric`'s `Ord`, not of their names.
+        assert_eq!(
+            query_set_diff.added_metrics,
+            vec![Metric::Hamming, Metric::InnerProduct, Metric::Chebyshev]
+        );
+        assert!(query_set_diff.removed_metrics.is_empty());
+        assert_eq!(
+            query_set_diff.changed_ground_truth,
+            vec![(Metric::Euclidean, 0.25)]
+        );
+        assert!(diff.to_string().contains("Euclidean: 25.00% of rows"));
+        assert!(diff.added_data_variants.is_empty());
+    }
+
+    #[test]
+    fn test_diff_data_variants() {
+        let data_points = sample_data_points();
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        dataset
+            .add_data_variant("normalized", data_points.clone())
+            .unwrap();
+        dataset
+            .add_data_variant("copy", data_points.clone())
+            .unwrap();
+
+        let mut other = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        other
+            .add_data_variant("normalized", data_points.select(&[1, 0, 2, 3]))
+            .unwrap();
+        other.add_data_variant("centered", data_points).unwrap();
+        assert_ne!(dataset, other);
+
+        let diff = dataset.diff(&other);
+        assert!(!diff.is_identical());
+        assert!(!diff.data_points_differ);
+        assert_eq!(diff.added_data_variants, vec!["centered".to_string()]);
+        assert_eq!(diff.removed_data_variants, vec!["copy".to_string()]);
+        assert_eq!(diff.changed_data_variants, vec!["normalized".to_string()]);
+        assert!(diff
+            .to_string()
+            .contains("Added data variants: [centered]\nRemoved data variants: [copy]"));
+    }
+
+    #[test]
+    fn test_diff_results() {
+        let mut dataset = InMemoryAnnDataset::<f32>::create(sample_data_points());
+        dataset.record_result("hnsw@euclidean@10", 0.9);
+        dataset.record_result("ivf@euclidean@10", 0.8);
+        dataset.record_result("broken", f32::NAN);
+        assert_eq!(dataset, dataset.clone());
+        assert!(dataset.diff(&dataset.clone()).is_identical());
+        fn assert_eq_impl<T: Eq>(_: &T) {}
+        assert_eq_impl(&InMemoryAnnDataset::create(
+            dataset.data_points.cast_with(|&x| x as u8),
+        ));
+
+        let mut other = InMemoryAnnDataset::<f32>::create(sample_data_points());
+        other.data_points = dataset.data_points.clone();
+        other.record_result("hnsw@euclidean@10", 0.95);
+        other.record_result("broken", f32::NAN);
+        other.record_result("flat@euclidean@10", 1.0);
+        assert_ne!(dataset, other);
+
+        let diff = dataset.diff(&other);
+        assert!(!diff.is_identical());
+        assert_eq!(diff.added_results, vec!["flat@euclidean@10".to_string()]);
+        assert_eq!(diff.removed_results, vec!["ivf@euclidean@10".to_string()]);
+        assert_eq!(diff.changed_results, vec!["hnsw@euclidean@10".to_string()]);
+        assert!(diff
+            .to_string()
+            .contains("Changed results: [hnsw@euclidean@10]"));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let data_points = PointSet::new(
+            Some(Array2::random((20, 4), Uniform::new(0_f32, 1_f32))),
+            None,
+        )
+        .unwrap();
+        let mut query_set = QuerySet::new(data_points.select(&[3, 8, 11]));
+        query_set
+            .compute_ground_truth(&data_points, &[Metric::Euclidean], 5)
+            .unwrap();
+        let mut dataset = InMemoryAnnDataset::create(data_points.clone());
+        dataset.add_test_query_set(query_set).unwrap();
+
+        let exact = |queries: &PointSet<f32>| {
+            data_points
+                .search(queries, 10, &Metric::Euclidean)
+                .unwrap()
+                .outer_iter()
+                .map(|row| row.to_vec())
+                .collect::<Vec<_>>()
+        };
+        let recall = dataset.evaluate("test_query_set", &Metric::Euclidean, 5, exact);
+        assert_eq!(recall.unwrap(), 1_f32);
+
+        let nothing = |queries: &PointSet<f32>| vec![vec![]; queries.num_points()];
+        let recall = dataset.evaluate("test_query_set", &Metric::Euclidean, 5, nothing);
+        assert_eq!(recall.unwrap(), 0_f32);
+
+        assert!(dataset
+            .evaluate("train", &Metric::Euclidean, 5, exact)
+            .is_err());
+        assert!(dataset
+            .evaluate("test_query_set", &Metric::Cosine, 5, exact)
+            .is_err());
+    }
+
+    #[test]
+    fn test_evaluate_all() {
+        let data_points = PointSet::random_dense(20, 4, 7);
+        let metrics = [Metric::Euclidean, Metric::InnerProduct];
+        let mut dataset = InMemoryAnnDataset::create(data_points.clone());
+        for (label, ids) in [("a", [3, 8, 11]), ("b", [0, 1, 2])] {
+            let mut query_set = QuerySet::new(data_points.select(&ids));
+            query_set
+                .compute_ground_truth(&data_points, &metrics, 5)
+                .unwrap();
+            dataset.add_query_set(label, query_set).unwrap();
+        }
+
+        let calls = RefCell::new(vec![]);
+        let report = dataset
+            .evaluate_all(
+                |label, metric, queries| {
+                    calls.borrow_mut().push((label.to_string(), metric.clone()));
+                    if label == "a" && metric == &Metric::Euclidean {
+                        return vec![vec![]; queries.num_points()];
+                    }
+                    data_points
+                        .search(queries, 5, metric)
+                        .unwrap()
+                        .outer_iter()
+                        .map(|row| row.to_vec())
+                        .collect()
+                },
+                5,
+            )
+            .unwrap();
+
+        assert_eq!(
+            calls.into_inner(),
+            vec![
+                ("a".to_string(), Metric::Euclidean),
+                ("a".to_string(), Metric::InnerProduct),
+                ("b".to_string(), Metric::Euclidean),
+                ("b".to_string(), Metric::InnerProduct),
+            ]
+        );
+        assert_eq!(report.len(), 4);
+        assert_eq!(report[&("a".to_string(), Metric::Euclidean)], 0_f32);
+        assert_eq!(report[&("a".to_string(), Metric::InnerProduct)], 1_f32);
+        assert_eq!(report[&("b".to_string(), Metric::Euclidean)], 1_f32);
+        assert_eq!(report[&("b".to_string(), Metric::InnerProduct)], 1_f32);
+
+        assert!(dataset.evaluate_all(|_, _, _| vec![], 5).is_err());
+    }
+
+    #[test]
+    fn test_mean_recall() {
+        let mut dataset = InMemoryAnnDataset::<f32>::create(sample_data_points());
+        let mut query_set = QuerySet::new(sample_data_points());
+        query_set
+            .add_ground_truth(
+                Metric::InnerProduct,
+                arr2(&[[0_usize, 1], [1, 2], [2, 3], [3, 0]]),
+            )
+            .unwrap();
+        dataset.add_query_set("test", query_set).unwrap();
+
+        let retrieved = vec![vec![0_usize, 1], vec![1, 3], vec![0, 1], vec![3, 0]];
+        let recall = dataset.mean_recall("test", &Metric::InnerProduct, &retrieved);
+        assert_eq!(recall.unwrap(), 0.625);
+
+        let error = dataset
+            .mean_recall("train", &Metric::InnerProduct, &retrieved)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'train'"));
+
+        let error = dataset
+            .mean_recall("test", &Metric::Euclidean, &retrieved)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'test'") && error.contains("Euclidean"));
+
+        let error = dataset
+            .mean_recall("test", &Metric::Minkowski { p: 3 }, &retrieved)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(&Metric::Minkowski { p: 3 }.to_string()));
+
+        let error = dataset
+            .mean_recall("test", &Metric::InnerProduct, &retrieved[..2])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'test'") && error.contains("InnerProduct"));
+    }
+
+    #[test]
+    fn test_add_query_set_mismatched_dimensions() {
+        let mut dataset = InMemoryAnnDataset::<f32>::create(sample_data_points());
+
+        let dense_only = PointSet::new(Some(Array2::<f32>::zeros((4, 10))), None).unwrap();
+        let result = dataset.add_test_query_set(QuerySet::new(dense_only));
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("components"));
+
+        let mut sparse = TriMat::new((4, 5));
+        sparse.add_triplet(0, 4, 1.0_f32);
+        let narrow =
+            PointSet::new(Some(Array2::<f32>::zeros((4, 10))), Some(sparse.to_csr())).unwrap();
+        let result = dataset.add_test_query_set(QuerySet::new(narrow.clone()));
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("dimensions"));
+
+        let (sender, receiver) = channel();
+        sender.send(QuerySet::new(narrow)).unwrap();
+        drop(sender);
+        assert!(dataset.add_query_sets("test", receiver).is_err());
+        assert!(dataset.get_test_query_set().is_err());
+
+        assert!(dataset
+            .add_test_query_set(QuerySet::new(sample_data_points()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_add_query_sets() {
+        let data_points = sample_data_points();
+        let chunks = (0..3)
+            .map(|i| {
+                let mut query_set = QuerySet::new(sample_data_points());
+                query_set
+                    .add_ground_truth(Metric::InnerProduct, Array2::from_elem((4, 2), i))
+                    .unwrap();
+                query_set
+            })
+            .collect::<Vec<_>>();
+
+        let mut expected = chunks[0].clone();
+        expected.append(&chunks[1]).unwrap();
+        expected.append(&chunks[2]).unwrap();
+        assert_eq!(expected.get_points().num_points(), 12);
+
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        let (sender, receiver) = channel();
+        chunks
+            .iter()
+            .for_each(|chunk| sender.send(chunk.clone()).unwrap());
+        drop(sender);
+        assert!(dataset.add_query_sets("train", receiver).is_ok());
+        assert_eq!(dataset.get_query_set("train").unwrap(), &expected);
+
+        let mut dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+        let (sender, receiver) = channel();
+        chunks
+            .iter()
+            .for_each(|chunk| sender.send(chunk.clone()).unwrap());
+        drop(sender);
+        assert!(dataset
+            .add_query_sets_with_hint("train", receiver, 12)
+            .is_ok());
+        assert_eq!(dataset.get_query_set("train").unwrap(), &expected);
+
+        // Mismatched ground-truth widths can not be appended.
+        let mut mismatched = QuerySet::new(sample_data_points());
+        mismatched
+            .add_ground_truth(Metric::InnerProduct, Array2::zeros((4, 3)))
+            .unwrap();
+        let (sender, receiver) = channel();
+        sender.send(mismatched).unwrap();
+        drop(sender);
+        assert!(dataset
+            .add_query_sets_with_hint("train", receiver, 16)
+            .is_err());
+        assert_eq!(dataset.get_query_set("train").unwrap(), &expected);
+    }
+
+    #[test]
+    fn test_add_query_sets_with_distances() {
+        let chunk = |i: usize, with_distances: bool| {
+            let neighbors = Array2::from_elem((4, 2), i);
+            let gt = if with_distances {
+                GroundTruth::with_distances(neighbors, Array2::from_elem((4, 2), i as f32)).unwrap()
+            } else {
+                GroundTruth::new(neighbors)
+            };
+            let mut query_set = QuerySet::new(sample_data_points());
+            query_set
+                .insert_ground_truth(Metric::Euclidean, gt)
+                .unwrap();
+            query_set
+        };
+        let add_both_ways = |chunks: &[QuerySet<f32>]| {
+            let mut appended = InMemoryAnnDataset::<f32>::create(sample_data_points());
+            let (sender, receiver) = channel();
+            chunks
+                .iter()
+                .for_each(|chunk| sender.send(chunk.clone()).unwrap());
+            drop(sender);
+            appended.add_query_sets("train", receiver).unwrap();
+
+            let mut buffered = InMemoryAnnDataset::<f32>::create(sample_data_points());
+            let (sender, receiver) = channel();
+            chunks
+                .iter()
+                .for_each(|chunk| sender.send(chunk.clone()).unwrap());
+            drop(sender);
+            buffered
+                .add_query_sets_with_hint("train", receiver, 12)
+                .unwrap();
+            (
+                appended.get_query_set("train").unwrap().clone(),
+                buffered.get_query_set("train").unwrap().clone(),
+            )
+        };
+
+        let (appended, buffered) = add_both_ways(&[chunk(0, true), chunk(1, true), chunk(2, true)]);
+        assert_eq!(appended, buffered);
+        let distances = buffered
+            .get_ground_truth(&Metric::Euclidean)
+            .unwrap()
+            .get_distances()
+            .unwrap();
+        assert_eq!(distances.shape(), &[12, 2]);
+        assert_eq!(distances[[5, 1]], 1_f32);
+
+        let (appended, buffered) = add_both_ways(&[chunk(0, true), chunk(1, false)]);
+        assert_eq!(appended, buffered);
+        assert!(buffered
+            .get_ground_truth(&Metric::Euclidean)
+            .unwrap()
+            .get_distances()
+            .is_none());
+    }
+
+    #[test]
+    fn test_make_tiny() {
+        let points = PointSet::new(Some(Array2::<f32>::eye(10)), None).unwrap();
+        let mut query_set = QuerySet::new(points.clone());
+        let neighbors = Array2::from_shape_fn((10, 5), |(i, j)| (i + j) % 10);
+        query_set
+            .add_ground_truth(Metric::InnerProduct, neighbors.clone())
+            .unwrap();
+        // Distances equal the ids of the neighbors, so that they can be traced after sampling.
+        let distances = neighbors.mapv(|id| id as f32);
+        query_set
+            .insert_ground_truth(
+                Metric::Euclidean,
+                GroundTruth::with_distances(neighbors.clone(), distances).unwrap(),
+            )
+            .unwrap();
+
+        let mut dataset = InMemoryAnnDataset::<f32>::create(points);
+        dataset.add_test_query_set(query_set).unwrap();
+
+        let tiny = dataset.make_tiny(5, 3, 2, 42).unwrap();
+        assert_eq!(tiny, dataset.make_tiny(5, 3, 2, 42).unwrap());
+        assert_eq!(tiny.get_data_points().num_points(), 5);
+
+        // Recovers the original id of a one-hot point.
+        let original_id = |point: ndarray::ArrayView1<f32>| point.iter().position(|&x| x == 1.0);
+
+        let tiny_data = tiny.get_data_points().get_dense().unwrap();
+        let tiny_queries = tiny.get_test_query_set().unwrap();
+        let tiny_gt = tiny_queries
+            .get_ground_truth(&Metric::InnerProduct)
+            .unwrap();
+        assert_eq!(tiny_queries.get_points().num_points(), 3);
+        assert!(tiny_gt.get_neighbors().ncols() <= 2);
+
+        let query_points = tiny_queries.get_points().get_dense().unwrap();
+        for i in 0..query_points.nrows() {
+            let query_id = original_id(query_points.row(i)).unwrap();
+            let expected = neighbors
+                .row(query_id)
+                .iter()
+                .copied()
+                .filter(|&id| {
+                    tiny_data
+                        .rows()
+                        .into_iter()
+                        .any(|p| original_id(p) == Some(id))
+                })
+                .take(2)
+                .collect::<Vec<_>>();
+            let kept = tiny_gt
+                .neighbors_of(i)
+                .iter()
+                .map(|&neighbor| original_id(tiny_data.row(neighbor)).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(kept, expected);
+        }
+
+        let tiny_gt = tiny_queries.get_ground_truth(&Metric::Euclidean).unwrap();
+        assert!(tiny_gt.get_neighbors().ncols() > 0);
+        let tiny_distances = tiny_gt.get_distances().unwrap();
+        assert_eq!(tiny_distances.dim(), tiny_gt.get_neighbors().dim());
+        for i in 0..tiny_gt.get_neighbors().nrows() {
+            tiny_gt
+                .neighbors_of(i)
+                .iter()
+                .enumerate()
+                .for_each(|(j, &neighbor)| {
+                    let id = original_id(tiny_data.row(neighbor)).unwrap();
+                    assert_eq!(tiny_distances[[i, j]], id as f32);
+                });
+        }
+    }
+
+    #[test]
+    fn test_make_tiny_ragged() {
+        let points = PointSet::new(Some(Array2::<f32>::eye(4)), None).unwrap();
+        let mut query_set = QuerySet::new(points.clone());
+        // With every data point kept but the last, the last query loses its only neighbor while
+        // the other queries keep theirs.
+        let neighbors = arr2(&[[0_usize, 1], [1, 2], [2, 0], [3, 3]]);
+        let distances = arr2(&[[0.5_f32, 1.5], [2.5, 3.5], [4.5, 5.5], [6.5, 7.5]]);
+        query_set
+            .insert_ground_truth(
+                Metric::Euclidean,
+                GroundTruth::with_distances(neighbors, distances).unwrap(),
+            )
+            .unwrap();
+        let mut dataset = InMemoryAnnDataset::<f32>::create(points);
+        dataset.add_test_query_set(query_set).unwrap();
+
+        // Sampling is uniform, so look for a seed that drops exactly the last data point.
+        let tiny = (0..1000)
+            .map(|seed| dataset.make_tiny(3, 4, 2, seed).unwrap())
+            .find(|tiny| {
+                tiny.get_data_points().get_dense().unwrap()
+                    == Array2::<f32>::eye(4).slice(ndarray::s![..3, ..])
+            })
+            .unwrap();
+
+        let gt = tiny
+            .get_test_query_set()
+            .unwrap()
+            .get_ground_truth(&Metric::Euclidean)
+            .unwrap();
+        assert!(gt.is_ragged());
+        assert_eq!(gt.neighbors_of(0).to_vec(), vec![0, 1]);
+        assert_eq!(gt.neighbors_of(1).to_vec(), vec![1, 2]);
+        assert_eq!(gt.neighbors_of(2).to_vec(), vec![2, 0]);
+        assert_eq!(gt.neighbors_of(3).to_vec(), Vec::<usize>::new());
+        assert_eq!(
+            gt,
+            &GroundTruth::new_ragged_with_distances(
+                vec![vec![0, 1], vec![1, 2], vec![2, 0], vec![]],
+                vec![vec![0.5, 1.5], vec![2.5, 3.5], vec![4.5, 5.5], vec![]],
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_without_query_sets() {
+        let data_points = sample_data_points();
+        let dataset = InMemoryAnnDataset::<f32>::create(data_points.clone());
+
+        let dir = TempDir::new("test_read_without_query_sets").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+
+        // A dataset without query sets.
+        assert!(dataset.write(path).is_ok());
+        assert_eq!(InMemoryAnnDataset::<f32>::read(path).unwrap(), dataset);
+
+        // A file that only has data points and no query set group at all.
+        {
+            let hdf5 = File::create(path).unwrap();
+            let mut root = hdf5.group("/").unwrap();
+            assert!(data_points.add_to(&mut root).is_ok());
+        }
+        assert_eq!(InMemoryAnnDataset::<f32>::read(path).unwrap(), dataset);
+    }
+
+    #[test]
+    fn test_retain_metrics() {
+        let mut dataset = InMemoryAnnDataset::<f32>::create(sample_data_points());
+        for label in ["train", "test"] {
+            let mut query_set = QuerySet::new(sample_data_points());
+            for metric in [Metric::InnerProduct, Metric::Euclidean] {
+                query_set
+                    .add_ground_truth(metric, Array2::<usize>::zeros((4, 2)))
+                    .unwrap();
+            }
+            dataset.add_query_set(label, query_set).unwrap();
+        }
+
+        dataset.retain_metrics(&[Metric::InnerProduct]);
+        for (_, query_set) in dataset.query_sets() {
+            assert!(query_set.get_ground_truth(&Metric::InnerProduct).is_ok());
+            assert!(query_set.get_ground_truth(&Metric::Euclidean).is_err());
+        }
+    }
 }
@@ -1,3 +1,4 @@
+pub mod diff;
 pub mod in_memory_dataset;
 
 use crate::{PointSet, QuerySet};
@@ -18,21 +19,24 @@ pub trait AnnDataset<DataType: Clone> {
 
     /// Adds a new query set to the dataset with the given `label` or replaces one if it already
     /// exists.
-    fn add_query_set(&mut self, label: &str, query_set: QuerySet<DataType>);
+    ///
+    /// Returns an error if the query points do not have the same dense and sparse dimensions as
+    /// the data points.
+    fn add_query_set(&mut self, label: &str, query_set: QuerySet<DataType>) -> anyhow::Result<()>;
 
     /// Convenience method to add a "train" query set.
-    fn add_train_query_set(&mut self, query_set: QuerySet<DataType>) {
-        self.add_query_set(TRAIN_QUERY_SET, query_set);
+    fn add_train_query_set(&mut self, query_set: QuerySet<DataType>) -> anyhow::Result<()> {
+        self.add_query_set(TRAIN_QUERY_SET, query_set)
     }
 
     /// Convenience method to add a "validation" query set.
-    fn add_validation_query_set(&mut self, query_set: QuerySet<DataType>) {
-        self.add_query_set(VALIDATION_QUERY_SET, query_set);
+    fn add_validation_query_set(&mut self, query_set: QuerySet<DataType>) -> anyhow::Result<()> {
+        self.add_query_set(VALIDATION_QUERY_SET, query_set)
     }
 
     /// Convenience method to add a "test" query set.
-    fn add_test_query_set(&mut self, query_set: QuerySet<DataType>) {
-        self.add_query_set(TEST_QUERY_SET, query_set);
+    fn add_test_query_set(&mut self, query_set: QuerySet<DataType>) -> anyhow::Result<()> {
+        self.add_query_set(TEST_QUERY_SET, query_set)
     }
 
     fn get_query_set(&self, label: &str) -> anyhow::Result<&QuerySet<DataType>>;
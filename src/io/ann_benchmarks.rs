@@ -0,0 +1,276 @@
+use crate::data::AnnDataset;
+use crate::{GroundTruth, InMemoryAnnDataset, Metric, PointSet, QuerySet};
+use anyhow::{anyhow, Result};
+use hdf5::types::VarLenUnicode;
+use hdf5::H5Type;
+use ndarray::Array2;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+const TRAIN: &str = "train";
+const TEST: &str = "test";
+const NEIGHBORS: &str = "neighbors";
+const DISTANCES: &str = "distances";
+const DISTANCE: &str = "distance";
+const DIMENSION: &str = "dimension";
+const TYPE: &str = "type";
+
+impl<DataType: Clone + H5Type> InMemoryAnnDataset<DataType> {
+    /// Reads a dataset stored at `path` in the HDF5 layout of the `ann-benchmarks` project, where
+    /// all vectors are dense and stored in top-level datasets as follows:
+    ///
+    ///  * `train` holds the data points;
+    ///  * `test` holds the query points, which become the test query set; and,
+    ///  * `neighbors` holds the ids of the exact nearest neighbors of every query, which become
+    ///    the ground truth of the test query set for the given `metric`; and,
+    ///  * `distances`, if present, holds the distance from every query to each of its neighbors,
+    ///    which is recorded in the ground truth.
+    ///
+    /// The attributes of the file, such as the name of the distance function, are ignored.
+    ///
+    /// Returns an error if any of the above datasets is missing, if a neighbor id is negative, or
+    /// if the test query set is inconsistent with the data points.
+    pub fn read_ann_benchmarks(path: &str, metric: Metric) -> Result<InMemoryAnnDataset<DataType>> {
+        let file = hdf5::File::open(path)?;
+        let train = file.dataset(TRAIN)?.read_2d::<DataType>()?;
+        let test = file.dataset(TEST)?.read_2d::<DataType>()?;
+        let neighbors = file.dataset(NEIGHBORS)?.read_2d::<i64>()?;
+
+        let ids = neighbors
+            .iter()
+            .map(|&id| usize::try_from(id).map_err(|_| anyhow!("Invalid neighbor id {}.", id)))
+            .collect::<Result<Vec<_>>>()?;
+        let neighbors = Array2::from_shape_vec(neighbors.raw_dim(), ids)?;
+        let gt = match file.dataset(DISTANCES) {
+            Ok(dataset) => GroundTruth::with_distances(neighbors, dataset.read_2d::<f32>()?)?,
+            Err(_) => GroundTruth::new(neighbors),
+        };
+
+        let mut query_set = QuerySet::new(PointSet::new(Some(test), None)?);
+        query_set.insert_ground_truth(metric, gt)?;
+        let mut dataset = InMemoryAnnDataset::create(PointSet::new(Some(train), None)?);
+        dataset.add_test_query_set(query_set)?;
+        Ok(dataset)
+    }
+
+    /// Stores the query set with the given `label` along with the data points as an HDF5 file at
+    /// `path` in the layout of the `ann-benchmarks` project, so that the dataset can be used with
+    /// its evaluation harness. See `read_ann_benchmarks` for the layout.
+    ///
+    /// The ground truth of the query set for `metric` is written as `neighbors`, and the
+    /// distances it records, if any, as `distances`. The `distance` attribute of the file names
+    /// the metric as `ann-benchmarks` does where it has an equivalent; `Cosine` and `Angular`
+    /// both become `angular` because they rank points identically.
+    ///
+    /// Returns an error if the query set or its ground truth for `metric` does not exist, if the
+    /// data points or query points have a sparse component, which the layout does not support,
+    /// or if the ground truth is ragged.
+    pub fn write_ann_benchmarks(&self, path: &str, label: &str, metric: &Metric) -> Result<()> {
+        let query_set = self.get_query_set(label)?;
+        let gt = query_set.get_ground_truth(metric)?;
+        let (Some(train), Some(test)) = (
+            self.get_data_points().get_dense(),
+            query_set.get_points().get_dense(),
+        ) else {
+            return Err(anyhow!("The ann-benchmarks layout requires dense vectors."));
+        };
+        if self.get_data_points().get_sparse().is_some()
+            || query_set.get_points().get_sparse().is_some()
+        {
+            return Err(anyhow!(
+                "The ann-benchmarks layout does not support sparse vectors."
+            ));
+        }
+        if gt.is_ragged() {
+            return Err(anyhow!(
+                "The ann-benchmarks layout does not support ragged ground truth."
+            ));
+        }
+        let neighbors = gt
+            .get_neighbors()
+            .iter()
+            .map(|&id| i32::try_from(id).map_err(|_| anyhow!("Neighbor id {} exceeds i32.", id)))
+            .collect::<Result<Vec<_>>>()?;
+        let neighbors = Array2::from_shape_vec(gt.get_neighbors().raw_dim(), neighbors)?;
+
+        let file = hdf5::File::create(path)?;
+        file.new_dataset_builder().with_data(train).create(TRAIN)?;
+        file.new_dataset_builder().with_data(test).create(TEST)?;
+        file.new_dataset_builder()
+            .with_data(&neighbors)
+            .create(NEIGHBORS)?;
+        if let Some(distances) = gt.get_distances() {
+            file.new_dataset_builder()
+                .with_data(&distances)
+                .create(DISTANCES)?;
+        }
+        file.new_attr::<VarLenUnicode>()
+            .create(DISTANCE)?
+            .write_scalar(&VarLenUnicode::from_str(&ann_benchmarks_distance(metric))?)?;
+        file.new_attr::<VarLenUnicode>()
+            .create(TYPE)?
+            .write_scalar(&VarLenUnicode::from_str("dense")?)?;
+        file.new_attr::<usize>()
+            .create(DIMENSION)?
+            .write_scalar(&train.ncols())?;
+        file.close()?;
+        Ok(())
+    }
+}
+
+/// Returns the name of the distance function in `ann-benchmarks` that ranks points like
+/// `metric`, or the canonical name of `metric` if there is none.
+fn ann_benchmarks_distance(metric: &Metric) -> Cow<'static, str> {
+    match metric {
+        Metric::Cosine | Metric::Angular => Cow::Borrowed("angular"),
+        metric => metric.canonical_name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnnDataset, InMemoryAnnDataset, Metric, PointSet, QuerySet};
+    use hdf5::File;
+    use ndarray::{arr2, Array2};
+    use tempdir::TempDir;
+
+    fn sample_dataset() -> InMemoryAnnDataset<f32> {
+        let data_points = PointSet::random_dense(20, 4, 0);
+        let mut query_set = QuerySet::new(data_points.select(&[1, 5, 9]));
+        query_set
+            .compute_ground_truth(&data_points, &[Metric::Euclidean], 3)
+            .unwrap();
+        let mut dataset = InMemoryAnnDataset::create(data_points);
+        dataset.add_test_query_set(query_set).unwrap();
+        dataset
+    }
+
+    #[test]
+    fn test_hdf5_read_ann_benchmarks() {
+        let train = Array2::from_shape_fn((5, 3), |(i, j)| (i * 3 + j) as f32);
+        let test = arr2(&[[0_f32, 1_f32, 2_f32], [12_f32, 13_f32, 14_f32]]);
+        let neighbors = arr2(&[[0_i32, 1], [4, 3]]);
+        let distances = arr2(&[[0_f32, 5.196], [0_f32, 5.196]]);
+
+        let dir = TempDir::new("test_hdf5_read_ann_benchmarks").unwrap();
+        let path = dir.path().join("ann-benchmarks.hdf5");
+        let path = path.to_str().unwrap();
+        {
+            let file = File::create(path).unwrap();
+            file.new_dataset_builder()
+                .with_data(&train)
+                .create("train")
+                .unwrap();
+            file.new_dataset_builder()
+                .with_data(&test)
+                .create("test")
+                .unwrap();
+            file.new_dataset_builder()
+                .with_data(&neighbors)
+                .create("neighbors")
+                .unwrap();
+            file.new_dataset_builder()
+                .with_data(&distances)
+                .create("distances")
+                .unwrap();
+        }
+
+        let dataset =
+            InMemoryAnnDataset::<f32>::read_ann_benchmarks(path, Metric::Euclidean).unwrap();
+        assert_eq!(dataset.get_data_points().get_dense().unwrap(), &train);
+        let query_set = dataset.get_test_query_set().unwrap();
+        assert_eq!(query_set.get_points().get_dense().unwrap(), &test);
+        assert_eq!(
+            query_set
+                .get_ground_truth(&Metric::Euclidean)
+                .unwrap()
+                .get_neighbors(),
+            arr2(&[[0_usize, 1], [4, 3]])
+        );
+        assert_eq!(
+            query_set
+                .get_ground_truth(&Metric::Euclidean)
+                .unwrap()
+                .get_distances()
+                .unwrap(),
+            distances
+        );
+        assert!(query_set.get_ground_truth(&Metric::Cosine).is_err());
+
+        let missing = dir.path().join("missing.hdf5");
+        assert!(InMemoryAnnDataset::<f32>::read_ann_benchmarks(
+            missing.to_str().unwrap(),
+            Metric::Euclidean
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_hdf5_write_ann_benchmarks() {
+        let dataset = sample_dataset();
+        let dir = TempDir::new("test_hdf5_write_ann_benchmarks").unwrap();
+        let path = dir.path().join("ann-benchmarks.hdf5");
+        let path = path.to_str().unwrap();
+        dataset
+            .write_ann_benchmarks(path, "test_query_set", &Metric::Euclidean)
+            .unwrap();
+
+        let file = File::open(path).unwrap();
+        assert_eq!(
+            file.attr("distance")
+                .unwrap()
+                .read_scalar::<hdf5::types::VarLenUnicode>()
+                .unwrap()
+                .as_str(),
+            "euclidean"
+        );
+        assert_eq!(
+            file.attr("dimension")
+                .unwrap()
+                .read_scalar::<usize>()
+                .unwrap(),
+            4
+        );
+
+        let copy = InMemoryAnnDataset::<f32>::read_ann_benchmarks(path, Metric::Euclidean).unwrap();
+        assert_eq!(copy, dataset);
+    }
+
+    #[test]
+    fn test_write_ann_benchmarks_unsupported() {
+        let dir = TempDir::new("test_write_ann_benchmarks_unsupported").unwrap();
+        let path = dir.path().join("ann-benchmarks.hdf5");
+        let path = path.to_str().unwrap();
+
+        let dataset = sample_dataset();
+        assert!(dataset
+            .write_ann_benchmarks(path, "train_query_set", &Metric::Euclidean)
+            .is_err());
+        assert!(dataset
+            .write_ann_benchmarks(path, "test_query_set", &Metric::Cosine)
+            .is_err());
+
+        let data_points = PointSet::random_sparse(10, 20, 3, 0);
+        let mut query_set = QuerySet::new(data_points.select(&[0, 1]));
+        query_set
+            .add_ground_truth(Metric::InnerProduct, Array2::zeros((2, 1)))
+            .unwrap();
+        let mut sparse = InMemoryAnnDataset::create(data_points);
+        sparse.add_test_query_set(query_set).unwrap();
+        assert!(sparse
+            .write_ann_benchmarks(path, "test_query_set", &Metric::InnerProduct)
+            .is_err());
+
+        let mut ragged = sample_dataset();
+        let mut query_set = ragged.get_test_query_set().unwrap().clone();
+        query_set
+            .add_ragged_ground_truth(Metric::InnerProduct, vec![vec![0], vec![], vec![1, 2]])
+            .unwrap();
+        ragged.add_test_query_set(query_set).unwrap();
+        assert!(ragged
+            .write_ann_benchmarks(path, "test_query_set", &Metric::InnerProduct)
+            .is_err());
+
+        assert!(!std::path::Path::new(path).exists());
+    }
+}
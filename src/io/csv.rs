@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Writes per-query recall, as returned by `GroundTruth::recall`, to a CSV file at `path`.
+///
+/// The file has a header row followed by one `query_id,recall` row per query, where `query_id`
+/// is the index of the query. If `retrieved_sizes` is given, a third `retrieved` column records
+/// the number of points retrieved for each query.
+///
+/// Returns an error if `retrieved_sizes` does not have one entry per query, or if the file can
+/// not be written.
+pub fn write_per_query_recall_csv(
+    path: &str,
+    recall: &[f32],
+    retrieved_sizes: Option<&[usize]>,
+) -> Result<()> {
+    if let Some(sizes) = retrieved_sizes {
+        if sizes.len() != recall.len() {
+            return Err(anyhow!(
+                "Expected {} retrieved-set sizes, one per query, but got {}.",
+                recall.len(),
+                sizes.len()
+            ));
+        }
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    match retrieved_sizes {
+        None => {
+            writeln!(writer, "query_id,recall")?;
+            for (id, recall) in recall.iter().enumerate() {
+                writeln!(writer, "{},{}", id, recall)?;
+            }
+        }
+        Some(sizes) => {
+            writeln!(writer, "query_id,recall,retrieved")?;
+            for (id, (recall, size)) in recall.iter().zip(sizes).enumerate() {
+                writeln!(writer, "{},{},{}", id, recall, size)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::csv::write_per_query_recall_csv;
+    use std::fs::read_to_string;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_write_per_query_recall_csv() {
+        let dir = TempDir::new("test_recall_csv").unwrap();
+        let path = dir.path().join("recall.csv");
+        let path = path.to_str().unwrap();
+
+        write_per_query_recall_csv(path, &[1.0, 0.5], None).unwrap();
+        assert_eq!(
+            read_to_string(path).unwrap(),
+            "query_id,recall\n0,1\n1,0.5\n"
+        );
+
+        write_per_query_recall_csv(path, &[0.25, 0.0], Some(&[10, 3])).unwrap();
+        assert_eq!(
+            read_to_string(path).unwrap(),
+            "query_id,recall,retrieved\n0,0.25,10\n1,0,3\n"
+        );
+
+        assert!(write_per_query_recall_csv(path, &[0.25, 0.0], Some(&[10])).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempdir::TempDir;
+
+/// Returns whether the file at `path` is expected to be gzip-compressed, judging by a `.gz`
+/// extension.
+pub(crate) fn is_gzip(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension == "gz")
+}
+
+/// Decompresses the gzip file at `path` into a new temporary directory.
+///
+/// Returns the directory along with the path to the decompressed file within it. The directory
+/// and the decompressed file are removed when the returned `TempDir` is dropped, so callers must
+/// keep it alive for as long as they use the file.
+pub(crate) fn decompress_to_temp_file(path: &str) -> Result<(TempDir, PathBuf)> {
+    let mut decoder = GzDecoder::new(BufReader::new(File::open(path)?));
+
+    let dir = TempDir::new("ann-dataset-gz")?;
+    let name = Path::new(path)
+        .file_stem()
+        .map_or_else(|| "decompressed".into(), |stem| stem.to_os_string());
+    let decompressed = dir.path().join(name);
+
+    let mut writer = BufWriter::new(File::create(&decompressed)?);
+    std::io::copy(&mut decoder, &mut writer)?;
+    writer.flush()?;
+    Ok((dir, decompressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::gzip::{decompress_to_temp_file, is_gzip};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_decompress_to_temp_file() {
+        assert!(is_gzip("data/glove.hdf5.gz"));
+        assert!(!is_gzip("data/glove.hdf5"));
+        assert!(!is_gzip("data/gz"));
+
+        let dir = TempDir::new("test_decompress").unwrap();
+        let path = dir.path().join("contents.bin.gz");
+        let contents = (0..=255_u8).cycle().take(10_000).collect::<Vec<_>>();
+        {
+            let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+            encoder.write_all(&contents).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        let (temp_dir, decompressed) = decompress_to_temp_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(decompressed.file_name().unwrap(), "contents.bin");
+        assert_eq!(std::fs::read(&decompressed).unwrap(), contents);
+
+        drop(temp_dir);
+        assert!(!decompressed.exists());
+    }
+}
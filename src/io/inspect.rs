@@ -0,0 +1,157 @@
+use crate::data::in_memory_dataset::inspect_dataset;
+use crate::Metric;
+use anyhow::Result;
+use hdf5::File;
+use std::fmt::{Display, Formatter};
+
+/// Summary of a point set stored in an HDF5 file.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct PointSetInfo {
+    /// Number of points in the set.
+    pub num_points: usize,
+    /// Dimensionality of the dense component, if any.
+    pub dense_dimensions: Option<usize>,
+    /// Dimensionality of the sparse component, if any.
+    pub sparse_dimensions: Option<usize>,
+    /// Type of the stored values as described by HDF5 (e.g., `float32`).
+    pub dtype: String,
+}
+
+/// Summary of a query set stored in an HDF5 file.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct QuerySetInfo {
+    /// Label of the query set.
+    pub label: String,
+    /// Summary of the query points.
+    pub points: PointSetInfo,
+    /// Metrics for which the query set has ground truth, in the order of `Metric`'s `Ord`.
+    pub metrics: Vec<Metric>,
+}
+
+/// Summary of an ANN dataset stored in an HDF5 file.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct DatasetInfo {
+    /// Summary of the data points.
+    pub data_points: PointSetInfo,
+    /// Summaries of the query sets, sorted by label.
+    pub query_sets: Vec<QuerySetInfo>,
+}
+
+/// Summarizes the ANN dataset stored as an HDF5 file at `path`.
+///
+/// Only HDF5 metadata and attributes are read, not the vectors or ground truth, so this is fast
+/// even on very large files and can be used to decide how to load a dataset.
+pub fn inspect(path: &str) -> Result<DatasetInfo> {
+    let file = File::open(path)?;
+    inspect_dataset(&file.group("/")?)
+}
+
+impl Display for PointSetInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let dimensions = |dimensions: Option<usize>| match dimensions {
+            None => "-".to_string(),
+            Some(dimensions) => dimensions.to_string(),
+        };
+        write!(
+            f,
+            "{} points of type {}, dense dimensions: {}, sparse dimensions: {}",
+            self.num_points,
+            self.dtype,
+            dimensions(self.dense_dimensions),
+            dimensions(self.sparse_dimensions)
+        )
+    }
+}
+
+impl Display for DatasetInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Data points: {}", self.data_points)?;
+        self.query_sets.iter().try_for_each(|query_set| {
+            write!(
+                f,
+                "\n{}: {}; ground truth: [{}]",
+                query_set.label,
+                query_set.points,
+                query_set
+                    .metrics
+                    .iter()
+                    .map(|metric| metric.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::inspect::{inspect, DatasetInfo, PointSetInfo, QuerySetInfo};
+    use crate::{AnnDataset, Hdf5File, InMemoryAnnDataset, Metric, PointSet, QuerySet};
+    use ndarray::Array2;
+    use sprs::{CsMat, TriMat};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_display() {
+        let info = DatasetInfo {
+            data_points: PointSetInfo {
+                num_points: 10,
+                dense_dimensions: Some(4),
+                sparse_dimensions: None,
+                dtype: "float32".to_string(),
+            },
+            query_sets: vec![QuerySetInfo {
+                label: "test".to_string(),
+                points: PointSetInfo {
+                    num_points: 2,
+                    dense_dimensions: Some(4),
+                    sparse_dimensions: None,
+                    dtype: "float32".to_string(),
+                },
+                metrics: vec![Metric::Cosine, Metric::InnerProduct],
+            }],
+        };
+        assert_eq!(
+            info.to_string(),
+            "Data points: 10 points of type float32, dense dimensions: 4, sparse dimensions: -\n\
+            test: 2 points of type float32, dense dimensions: 4, sparse dimensions: -; \
+            ground truth: [Cosine, InnerProduct]"
+        );
+    }
+
+    #[test]
+    fn test_inspect() {
+        let mut sparse = TriMat::new((10, 7));
+        sparse.add_triplet(0, 0, 3.0_f64);
+        sparse.add_triplet(9, 6, 2.0);
+        let sparse: CsMat<_> = sparse.to_csr();
+        let data_points = PointSet::new(Some(Array2::<f64>::eye(10)), Some(sparse)).unwrap();
+        let mut dataset = InMemoryAnnDataset::create(data_points.clone());
+
+        let mut query_set = QuerySet::new(data_points);
+        query_set
+            .add_ground_truth(Metric::InnerProduct, Array2::zeros((10, 1)))
+            .unwrap();
+        dataset.add_query_set("queries", query_set).unwrap();
+
+        let dir = TempDir::new("test_inspect").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        dataset.write(path).unwrap();
+
+        let info = inspect(path).unwrap();
+        assert_eq!(
+            info.data_points,
+            PointSetInfo {
+                num_points: 10,
+                dense_dimensions: Some(10),
+                sparse_dimensions: Some(7),
+                dtype: "float64".to_string(),
+            }
+        );
+        assert_eq!(info.query_sets.len(), 1);
+        assert_eq!(info.query_sets[0].label, "queries");
+        assert_eq!(info.query_sets[0].points.num_points, 10);
+        assert_eq!(info.query_sets[0].metrics, vec![Metric::InnerProduct]);
+    }
+}
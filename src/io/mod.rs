@@ -1,5 +1,11 @@
 use hdf5::Group;
 
+mod ann_benchmarks;
+pub mod csv;
+pub(crate) mod gzip;
+pub mod inspect;
+pub mod vecs;
+
 pub trait Hdf5Serialization {
     type Object;
 
@@ -20,5 +26,9 @@ pub trait Hdf5File {
     fn write(&self, path: &str) -> anyhow::Result<()>;
 
     /// Reads `Object` from HDF5 file at `path`.
+    ///
+    /// If `path` has a `.gz` extension, the file is first decompressed into a temporary file,
+    /// because HDF5 can not read from a compressed stream. The temporary file is removed once
+    /// reading completes, but requires as much free disk space as the decompressed dataset.
     fn read(path: &str) -> anyhow::Result<Self::Object>;
 }
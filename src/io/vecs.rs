@@ -0,0 +1,248 @@
+//! Readers and writers for the `.fvecs`, `.ivecs` and `.bvecs` formats of benchmark datasets such
+//! as SIFT, GIST and Deep1B.
+//!
+//! A file is a sequence of records, one per vector, where each record is a little-endian `i32`
+//! holding the number of dimensions followed by that many values: little-endian `f32` values in
+//! `.fvecs`, little-endian `i32` values in `.ivecs` (typically ground-truth neighbor ids), and
+//! `u8` values in `.bvecs`. All vectors in a file must have the same number of dimensions.
+//!
+//! Files whose path ends in `.gz`, such as `sift.fvecs.gz`, are read and written gzip-compressed.
+use crate::io::gzip::is_gzip;
+use crate::PointSet;
+use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ndarray::Array2;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// Reads an `.fvecs` file into a matrix with one row per vector.
+///
+/// Returns an error if the file can not be read, if a record is truncated, or if vectors have
+/// different numbers of dimensions. An empty file yields a `0 x 0` matrix.
+pub fn read_fvecs(path: &str) -> Result<Array2<f32>> {
+    read_vecs(path, f32::from_le_bytes)
+}
+
+/// Writes the rows of `vectors` to an `.fvecs` file at `path`.
+pub fn write_fvecs(path: &str, vectors: &Array2<f32>) -> Result<()> {
+    write_vecs(path, vectors, |x| x.to_le_bytes())
+}
+
+/// Reads an `.ivecs` file into a matrix with one row per vector. See `read_fvecs`.
+pub fn read_ivecs(path: &str) -> Result<Array2<i32>> {
+    read_vecs(path, i32::from_le_bytes)
+}
+
+/// Writes the rows of `vectors` to an `.ivecs` file at `path`.
+pub fn write_ivecs(path: &str, vectors: &Array2<i32>) -> Result<()> {
+    write_vecs(path, vectors, |x| x.to_le_bytes())
+}
+
+/// Reads a `.bvecs` file into a matrix with one row per vector. See `read_fvecs`.
+pub fn read_bvecs(path: &str) -> Result<Array2<u8>> {
+    read_vecs(path, |[x]: [u8; 1]| x)
+}
+
+/// Writes the rows of `vectors` to a `.bvecs` file at `path`.
+pub fn write_bvecs(path: &str, vectors: &Array2<u8>) -> Result<()> {
+    write_vecs(path, vectors, |&x| [x])
+}
+
+impl PointSet<f32> {
+    /// Reads a dense point set from an `.fvecs` file. See `read_fvecs`.
+    pub fn from_fvecs(path: &str) -> Result<PointSet<f32>> {
+        PointSet::new(Some(read_fvecs(path)?), None)
+    }
+}
+
+/// Reads records whose values are `SIZE` bytes wide, converting each value with `decode`.
+fn read_vecs<T, const SIZE: usize>(
+    path: &str,
+    decode: impl Fn([u8; SIZE]) -> T,
+) -> Result<Array2<T>> {
+    let file = File::open(path)?;
+    let mut reader: BufReader<Box<dyn Read>> = if is_gzip(path) {
+        BufReader::new(Box::new(GzDecoder::new(file)))
+    } else {
+        BufReader::new(Box::new(file))
+    };
+    let mut values = vec![];
+    let mut num_dimensions: Option<usize> = None;
+    let mut num_vectors = 0_usize;
+    let mut header = [0_u8; 4];
+    let mut record = vec![];
+
+    while !reader.fill_buf()?.is_empty() {
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| anyhow!("Record {} of '{}' is truncated.", num_vectors, path))?;
+        let dimensions = i32::from_le_bytes(header);
+        let dimensions = usize::try_from(dimensions).map_err(|_| {
+            anyhow!(
+                "Record {} of '{}' has a negative number of dimensions: {}.",
+                num_vectors,
+                path,
+                dimensions
+            )
+        })?;
+        match num_dimensions {
+            Some(expected) if expected != dimensions => {
+                return Err(anyhow!(
+                    "Record {} of '{}' has {} dimensions but previous records have {}.",
+                    num_vectors,
+                    path,
+                    dimensions,
+                    expected
+                ));
+            }
+            _ => num_dimensions = Some(dimensions),
+        }
+
+        record.resize(dimensions * SIZE, 0);
+        reader
+            .read_exact(&mut record)
+            .map_err(|_| anyhow!("Record {} of '{}' is truncated.", num_vectors, path))?;
+        values.extend(
+            record
+                .chunks_exact(SIZE)
+                .map(|bytes| decode(bytes.try_into().unwrap())),
+        );
+        num_vectors += 1;
+    }
+
+    Ok(Array2::from_shape_vec(
+        (num_vectors, num_dimensions.unwrap_or(0)),
+        values,
+    )?)
+}
+
+/// Writes one record per row of `vectors`, converting each value to `SIZE` bytes with `encode`.
+fn write_vecs<T, const SIZE: usize>(
+    path: &str,
+    vectors: &Array2<T>,
+    encode: impl Fn(&T) -> [u8; SIZE],
+) -> Result<()> {
+    let dimensions = i32::try_from(vectors.ncols()).map_err(|_| {
+        anyhow!(
+            "Vectors with {} dimensions can not be stored; at most {} are supported.",
+            vectors.ncols(),
+            i32::MAX
+        )
+    })?;
+
+    let file = File::create(path)?;
+    if is_gzip(path) {
+        let mut writer = BufWriter::new(GzEncoder::new(file, Compression::default()));
+        write_records(&mut writer, vectors, dimensions, encode)?;
+        writer.into_inner()?.finish()?;
+    } else {
+        let mut writer = BufWriter::new(file);
+        write_records(&mut writer, vectors, dimensions, encode)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Writes one record per row of `vectors`, each with `dimensions` values, to `writer`.
+fn write_records<T, const SIZE: usize>(
+    writer: &mut impl Write,
+    vectors: &Array2<T>,
+    dimensions: i32,
+    encode: impl Fn(&T) -> [u8; SIZE],
+) -> Result<()> {
+    for row in vectors.rows() {
+        writer.write_all(&dimensions.to_le_bytes())?;
+        for x in row.iter() {
+            writer.write_all(&encode(x))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::vecs::{
+        read_bvecs, read_fvecs, read_ivecs, write_bvecs, write_fvecs, write_ivecs,
+    };
+    use crate::PointSet;
+    use ndarray::{arr2, Array2};
+    use std::fs::{read, write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = TempDir::new("test_vecs").unwrap();
+        let path = dir.path().join("vectors");
+        let path = path.to_str().unwrap();
+
+        let vectors = arr2(&[[0.5_f32, -1.0, 3.25], [1e-7, 0.0, f32::MAX]]);
+        write_fvecs(path, &vectors).unwrap();
+        assert_eq!(read(path).unwrap().len(), 2 * (4 + 3 * 4));
+        assert_eq!(read(path).unwrap()[..8], [3, 0, 0, 0, 0, 0, 0, 0x3f]);
+        assert_eq!(read_fvecs(path).unwrap(), vectors);
+        assert_eq!(
+            PointSet::from_fvecs(path).unwrap(),
+            PointSet::new(Some(vectors), None).unwrap()
+        );
+
+        let ids = arr2(&[[7_i32, -1], [0, i32::MAX], [3, 4]]);
+        write_ivecs(path, &ids).unwrap();
+        assert_eq!(read_ivecs(path).unwrap(), ids);
+
+        let bytes = arr2(&[[0_u8, 128, 255, 1]]);
+        write_bvecs(path, &bytes).unwrap();
+        assert_eq!(read(path).unwrap(), [4, 0, 0, 0, 0, 128, 255, 1]);
+        assert_eq!(read_bvecs(path).unwrap(), bytes);
+
+        write_fvecs(path, &Array2::zeros((0, 5))).unwrap();
+        assert_eq!(read_fvecs(path).unwrap().shape(), &[0, 0]);
+    }
+
+    #[test]
+    fn test_gzip() {
+        let dir = TempDir::new("test_vecs").unwrap();
+        let path = dir.path().join("vectors.fvecs.gz");
+        let path = path.to_str().unwrap();
+
+        let vectors = arr2(&[[0.5_f32, -1.0, 3.25], [1e-7, 0.0, f32::MAX]]);
+        write_fvecs(path, &vectors).unwrap();
+        // The gzip magic number.
+        assert_eq!(read(path).unwrap()[..2], [0x1f, 0x8b]);
+        assert_eq!(read_fvecs(path).unwrap(), vectors);
+        assert_eq!(
+            PointSet::from_fvecs(path).unwrap(),
+            PointSet::new(Some(vectors), None).unwrap()
+        );
+
+        let path = dir.path().join("vectors.bvecs.gz");
+        let path = path.to_str().unwrap();
+        let bytes = arr2(&[[0_u8, 128, 255, 1], [2, 3, 4, 5]]);
+        write_bvecs(path, &bytes).unwrap();
+        assert_eq!(read_bvecs(path).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let dir = TempDir::new("test_vecs").unwrap();
+        let path = dir.path().join("vectors.bvecs");
+        let path = path.to_str().unwrap();
+
+        // Vectors with different numbers of dimensions.
+        write(path, [2, 0, 0, 0, 1, 2, 1, 0, 0, 0, 3]).unwrap();
+        assert!(read_bvecs(path).is_err());
+
+        // Truncated values and header.
+        write(path, [2, 0, 0, 0, 1]).unwrap();
+        assert!(read_bvecs(path).is_err());
+        write(path, [1, 0, 0, 0, 1, 1, 0]).unwrap();
+        assert!(read_bvecs(path).is_err());
+
+        // Negative number of dimensions.
+        write(path, (-1_i32).to_le_bytes()).unwrap();
+        assert!(read_bvecs(path).is_err());
+
+        assert!(read_fvecs(dir.path().join("missing.fvecs").to_str().unwrap()).is_err());
+    }
+}
@@ -4,8 +4,12 @@
 //!  * Storage of dense, sparse, and dense-sparse vector sets;
 //!  * Storage of query sets with ground-truth (i.e., exact nearest neighbors) according to
 //!    different metrics;
-//!  * Basic functionality such as computing recall given a retrieved set; and,
-//!  * Serialization into and deserialization from HDF5 file format.
+//!  * Basic functionality such as computing recall given a retrieved set;
+//!  * Serialization into and deserialization from HDF5 file format, and a compact bincode format
+//!    for caching;
+//!  * Conversion from and to the HDF5 layout of the `ann-benchmarks` project; and,
+//!  * Reading and writing the `.fvecs`, `.ivecs` and `.bvecs` formats of benchmarks such as
+//!    SIFT.
 //!
 //! ## Example usage
 //! It is straightforward to read an ANN dataset. The code snippet
@@ -34,17 +38,42 @@
 //! // for the `i`-th query.
 //! let recall = gt.mean_recall(&[]);
 //! ```
+//!
+//! ## Features
+//! The `parallel` feature, enabled by default, parallelizes search and normalization with rayon.
+//! Disable default features to run single-threaded without rayon. Note that the crate still
+//! requires the native HDF5 library either way.
+//!
+//! The `f16` feature adds `F16`, a half-precision data type, so that datasets such as
+//! `InMemoryAnnDataset<F16>` can be serialized to HDF5 at half the size of `f32`.
+#[macro_use]
+mod parallel;
+
 mod data;
 mod io;
+mod search;
 mod types;
 
+pub use crate::data::diff::{DatasetDiff, QuerySetDiff};
 pub use crate::data::in_memory_dataset::InMemoryAnnDataset;
 pub use crate::data::AnnDataset;
 
-pub use crate::types::ground_truth::GroundTruth;
-pub use crate::types::point_set::PointSet;
+#[cfg(feature = "f16")]
+pub use crate::types::f16::F16;
+pub use crate::types::ground_truth::{
+    GroundTruth, GroundTruthBuilder, RecallAccumulator, RecallStats,
+};
+pub use crate::types::point_set::{PointSet, SparsityStats};
+pub use crate::types::point_set_view::{PointSetView, PointView};
 pub use crate::types::query_set::QuerySet;
 pub use crate::types::Metric;
 
+pub use crate::io::csv::write_per_query_recall_csv;
+pub use crate::io::inspect::{inspect, DatasetInfo, PointSetInfo, QuerySetInfo};
+pub use crate::io::vecs::{
+    read_bvecs, read_fvecs, read_ivecs, write_bvecs, write_fvecs, write_ivecs,
+};
 pub use crate::io::Hdf5File;
 pub use crate::io::Hdf5Serialization;
+
+pub use crate::search::{Quantized, SearchResult};
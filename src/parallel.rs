@@ -0,0 +1,27 @@
+//! Helpers that run work in parallel with rayon if the `parallel` feature is enabled, and
+//! sequentially otherwise, for example on `wasm32` targets where rayon is unavailable. Both paths
+//! produce identical results.
+
+/// Calls `f` on every item of the ndarray `Zip` given as `zip`, in parallel if the `parallel`
+/// feature is enabled.
+macro_rules! zip_for_each {
+    ($zip:expr, $f:expr) => {{
+        #[cfg(feature = "parallel")]
+        $zip.par_for_each($f);
+        #[cfg(not(feature = "parallel"))]
+        $zip.for_each($f);
+    }};
+}
+
+/// Turns `iter` into a parallel iterator if the `parallel` feature is enabled, and into a
+/// sequential iterator otherwise. The two share the adapters used in this crate, such as `map`,
+/// `filter_map`, and `collect`, which preserve the order of items in both cases.
+macro_rules! maybe_par_iter {
+    ($iter:expr) => {{
+        #[cfg(feature = "parallel")]
+        let iter = $iter.into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let iter = $iter.into_iter();
+        iter
+    }};
+}
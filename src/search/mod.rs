@@ -0,0 +1,1104 @@
+use crate::{GroundTruth, Metric, PointSet, PointSetView, QuerySet};
+use anyhow::{anyhow, Result};
+#[cfg(feature = "parallel")]
+use ndarray::parallel::prelude::*;
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2, Axis, Zip};
+use ndarray_rand::rand::rngs::StdRng;
+use ndarray_rand::rand::seq::index::sample;
+use ndarray_rand::rand::SeedableRng;
+use sprs::CsMatView;
+use std::cmp::{min, Ordering};
+use std::collections::BinaryHeap;
+
+/// A quantized value type, `i8` or `u8`, whose vectors can be searched by inner product with
+/// `PointSet::search_inner_product`.
+///
+/// This trait is sealed: wider integer types are excluded because their inner products can
+/// overflow even the 64-bit accumulator.
+pub trait Quantized: private::Sealed + Copy + Into<i64> {}
+
+impl Quantized for i8 {}
+impl Quantized for u8 {}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for i8 {}
+    impl Sealed for u8 {}
+}
+
+/// A point returned by a search along with its score.
+///
+/// The score is the distance between the point and the query according to the metric of the
+/// search (see `Metric::distance`), so that smaller scores are closer. For `InnerProduct`, the
+/// score is the negated inner product. Results are ordered by score, with ties broken by the
+/// smaller id.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchResult {
+    pub id: usize,
+    pub score: f32,
+}
+
+impl PartialEq for SearchResult {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SearchResult {}
+
+impl PartialOrd for SearchResult {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SearchResult {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+/// Returns the `k` points with the smallest distance, sorted by ascending distance, where
+/// the `i`-th item of `distances` is the distance of the point with id `i`. Ties are broken by
+/// the smaller id.
+fn smallest_k<I: Iterator<Item = f32>>(distances: I, k: usize) -> Vec<SearchResult> {
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    distances.enumerate().for_each(|(id, distance)| {
+        heap.push(SearchResult {
+            id,
+            score: distance,
+        });
+        if heap.len() > k {
+            heap.pop();
+        }
+    });
+    heap.into_sorted_vec()
+}
+
+/// Returns the `k` points in `data` with the smallest distance to `query`, sorted by ascending
+/// distance. Ties are broken by the smaller id.
+fn top_k<F>(
+    data: ArrayView2<f32>,
+    query: ArrayView1<f32>,
+    k: usize,
+    distance: &F,
+) -> Vec<SearchResult>
+where
+    F: Fn(ArrayView1<f32>, ArrayView1<f32>) -> f32,
+{
+    smallest_k(
+        data.axis_iter(Axis(0)).map(|point| distance(point, query)),
+        k,
+    )
+}
+
+/// Returns the dense components of `data` and `queries`, or an error if either set has a sparse
+/// component or if the dimensionality of the two sets does not match.
+fn dense_components<'a, 'b, DataType: Clone>(
+    data: &PointSetView<'a, DataType>,
+    queries: &PointSetView<'b, DataType>,
+    metric: &Metric,
+) -> Result<(ArrayView2<'a, DataType>, ArrayView2<'b, DataType>)> {
+    let (dense, query_points) = match (data.get_dense(), queries.get_dense()) {
+        (Some(dense), Some(query_points))
+            if data.get_sparse().is_none() && queries.get_sparse().is_none() =>
+        {
+            (dense, query_points)
+        }
+        _ => {
+            return Err(anyhow!(
+                "Search with {} is only supported for dense point sets.",
+                metric
+            ))
+        }
+    };
+    if dense.ncols() != query_points.ncols() {
+        return Err(anyhow!(
+            "Data points have {} dimensions but queries have {} dimensions.",
+            dense.ncols(),
+            query_points.ncols()
+        ));
+    }
+    Ok((dense, query_points))
+}
+
+/// Returns the sparse components of `data` and `queries` if neither set has a dense component.
+fn sparse_components<'a, 'b>(
+    data: &PointSetView<'a, f32>,
+    queries: &'b PointSet<f32>,
+) -> Option<(CsMatView<'a, f32>, CsMatView<'b, f32>)> {
+    match (
+        data.get_dense(),
+        data.get_sparse(),
+        queries.get_dense(),
+        queries.get_sparse(),
+    ) {
+        (None, Some(data), None, Some(query_points)) => Some((*data, query_points.view())),
+        _ => None,
+    }
+}
+
+/// Finds the exact nearest neighbors of every query by computing `distance` between the query
+/// and every data point. Queries are processed in parallel if the `parallel` feature is enabled.
+fn brute_force<F>(
+    data: ArrayView2<f32>,
+    queries: ArrayView2<f32>,
+    k: usize,
+    distance: F,
+) -> Array2<usize>
+where
+    F: Fn(ArrayView1<f32>, ArrayView1<f32>) -> f32 + Sync,
+{
+    let k = min(k, data.nrows());
+    let mut neighbors = Array2::<usize>::zeros((queries.nrows(), k));
+    zip_for_each!(
+        Zip::from(neighbors.axis_iter_mut(Axis(0))).and(queries.axis_iter(Axis(0))),
+        |mut row, query| {
+            let ids = top_k(data, query, k, &distance)
+                .iter()
+                .map(|c| c.id)
+                .collect::<Vec<_>>();
+            row.assign(&ArrayView1::from(&ids));
+        }
+    );
+    neighbors
+}
+
+/// Same as `brute_force` but returns the score of every neighbor along with its id.
+fn brute_force_with_scores<F>(
+    data: ArrayView2<f32>,
+    queries: ArrayView2<f32>,
+    k: usize,
+    distance: F,
+) -> Vec<Vec<SearchResult>>
+where
+    F: Fn(ArrayView1<f32>, ArrayView1<f32>) -> f32 + Sync,
+{
+    let k = min(k, data.nrows());
+    maybe_par_iter!(queries.axis_iter(Axis(0)))
+        .map(|query| top_k(data, query, k, &distance))
+        .collect()
+}
+
+/// Same as `brute_force_with_scores` but for `InnerProduct` and `Cosine` over point sets that may
+/// have a sparse component, where the inner product of two points is the sum of the inner
+/// products of their dense components and of their sparse components.
+///
+/// Returns an error if `metric` is neither `InnerProduct` nor `Cosine`, or if the two sets do not
+/// have the same components with the same dimensionality.
+fn dot_product_brute_force(
+    data: &PointSet<f32>,
+    queries: &PointSet<f32>,
+    k: usize,
+    metric: &Metric,
+) -> Result<Vec<Vec<SearchResult>>> {
+    if !matches!(metric, Metric::InnerProduct | Metric::Cosine) {
+        return Err(anyhow!(
+            "Search with {} is only supported for dense point sets.",
+            metric
+        ));
+    }
+    if data.get_dense().map(|dense| dense.ncols()) != queries.get_dense().map(|dense| dense.ncols())
+        || data.get_sparse().map(|sparse| sparse.cols())
+            != queries.get_sparse().map(|sparse| sparse.cols())
+    {
+        return Err(anyhow!(
+            "Data points and queries must have the same dense and sparse dimensions."
+        ));
+    }
+
+    let k = min(k, data.num_points());
+    // Columns of the sparse data points, so that a sparse query visits only the points that
+    // share a non-zero coordinate with it.
+    let columns = data.get_sparse().map(|sparse| sparse.to_csc());
+    let data_norms = data.l2_norm();
+    let query_norms = queries.l2_norm();
+    Ok(maybe_par_iter!(0..queries.num_points())
+        .map(|i| {
+            let mut dots = match (data.get_dense(), queries.get_dense()) {
+                (Some(dense), Some(query_points)) => dense.dot(&query_points.row(i)),
+                _ => Array1::zeros(data.num_points()),
+            };
+            if let (Some(columns), Some(query_points)) = (columns.as_ref(), queries.get_sparse()) {
+                if let Some(query) = query_points.outer_view(i) {
+                    query.iter().for_each(|(j, &y)| {
+                        if let Some(column) = columns.outer_view(j) {
+                            column.iter().for_each(|(id, &x)| dots[id] += x * y);
+                        }
+                    });
+                }
+            }
+            let scores = dots.iter().zip(data_norms.iter()).map(|(&dot, &norm)| {
+                if *metric == Metric::InnerProduct {
+                    return -dot;
+                }
+                let norms = norm * query_norms[i];
+                if norms > 0_f32 {
+                    1_f32 - dot / norms
+                } else {
+                    1_f32
+                }
+            });
+            smallest_k(scores, k)
+        })
+        .collect())
+}
+
+/// Same as `brute_force_with_scores` but for sparse point sets, computing
+/// `Metric::sparse_distance` between every query and every data point, so that every metric is
+/// supported and only coordinates that are non-zero in either point are visited.
+///
+/// Returns an error if the two sets do not have the same number of dimensions.
+fn sparse_brute_force(
+    data: CsMatView<f32>,
+    queries: CsMatView<f32>,
+    k: usize,
+    metric: &Metric,
+) -> Result<Vec<Vec<SearchResult>>> {
+    if data.cols() != queries.cols() {
+        return Err(anyhow!(
+            "Data points have {} dimensions but queries have {} dimensions.",
+            data.cols(),
+            queries.cols()
+        ));
+    }
+    let k = min(k, data.rows());
+    Ok(maybe_par_iter!(0..queries.rows())
+        .map(|i| {
+            let query = queries.outer_view(i).unwrap();
+            smallest_k(
+                data.outer_iterator()
+                    .map(|point| metric.sparse_distance(point, query.view())),
+                k,
+            )
+        })
+        .collect())
+}
+
+/// Returns the ids of the `k` points in `data` with the largest inner product with `query`,
+/// sorted by descending inner product, where products are accumulated in `i64` so that they do
+/// not overflow the range of the quantized values. Ties are broken by the smaller id.
+fn top_k_quantized<DataType: Quantized>(
+    data: ArrayView2<DataType>,
+    query: ArrayView1<DataType>,
+    k: usize,
+) -> Vec<usize> {
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    data.axis_iter(Axis(0)).enumerate().for_each(|(id, point)| {
+        let dot = point
+            .iter()
+            .zip(query.iter())
+            .map(|(&x, &y)| x.into() * y.into())
+            .sum::<i64>();
+        heap.push((-dot, id));
+        if heap.len() > k {
+            heap.pop();
+        }
+    });
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|(_, id)| id)
+        .collect()
+}
+
+/// Returns the maximum likelihood estimate of the local intrinsic dimensionality of a point
+/// given the distances to its nearest neighbors in ascending order, or `None` if the estimate is
+/// undefined because the distances are not positive or are all equal.
+fn lid_mle(distances: &[f32]) -> Option<f32> {
+    let max = *distances.last()?;
+    if distances[0] <= 0_f32 {
+        return None;
+    }
+    let sum = distances
+        .iter()
+        .map(|&d| (d as f64 / max as f64).ln())
+        .sum::<f64>();
+    if sum >= 0_f64 {
+        return None;
+    }
+    Some((-(distances.len() as f64) / sum) as f32)
+}
+
+impl PointSet<f32> {
+    /// Finds the exact `k` nearest neighbors of each point in `queries` among the points in this
+    /// set according to `metric`.
+    ///
+    /// Returns a matrix where the `i`-th row holds the ids of the neighbors of the `i`-th query,
+    /// sorted by ascending distance. The matrix has exactly `min(k, self.num_points())` columns:
+    /// if there are fewer than `k` points in this set, every point is returned for every query.
+    ///
+    /// If both sets only have a sparse component, distances are computed pair by pair with
+    /// `Metric::sparse_distance`, which supports every metric, e.g., `Canberra` and `BrayCurtis`
+    /// over sparse count data.
+    ///
+    /// Returns an error if either set has both a dense and a sparse component, if one set is
+    /// dense and the other sparse, or if the dimensionality of the two sets does not match.
+    pub fn search(
+        &self,
+        queries: &PointSet<f32>,
+        k: usize,
+        metric: &Metric,
+    ) -> Result<Array2<usize>> {
+        self.view().search(queries, k, metric)
+    }
+
+    /// Same as `search` but returns the score of every neighbor along with its id. The `i`-th
+    /// entry holds the neighbors of the `i`-th query in the same order as `search`.
+    pub fn search_with_scores(
+        &self,
+        queries: &PointSet<f32>,
+        k: usize,
+        metric: &Metric,
+    ) -> Result<Vec<Vec<SearchResult>>> {
+        self.view().search_with_scores(queries, k, metric)
+    }
+
+    /// Same as `search` but returns the neighbors of every query as a separate list, in the form
+    /// expected by `GroundTruth::recall` and `GroundTruth::mean_recall`.
+    pub fn search_batch(
+        &self,
+        queries: &PointSet<f32>,
+        k: usize,
+        metric: &Metric,
+    ) -> Result<Vec<Vec<usize>>> {
+        Ok(self
+            .search(queries, k, metric)?
+            .rows()
+            .into_iter()
+            .map(|row| row.to_vec())
+            .collect())
+    }
+
+    /// Finds the exact `k` nearest neighbors of each point in `queries` among the points in this
+    /// set according to `metric`, and returns them as ground truth that records the score of
+    /// every neighbor (see `SearchResult`) as its distance. Neighbors are ordered as in `search`.
+    ///
+    /// Unlike `search`, supports sets with both a dense and a sparse component for `InnerProduct`
+    /// and `Cosine`, where the inner product of two points sums the contributions of their dense
+    /// and sparse components.
+    ///
+    /// Returns an error if either set has both components and `metric` is neither
+    /// `InnerProduct` nor `Cosine`, or if the two sets do not have the same components with the
+    /// same dimensionality.
+    pub fn knn(&self, queries: &PointSet<f32>, k: usize, metric: &Metric) -> Result<GroundTruth> {
+        let has_sparse = self.get_sparse().is_some() || queries.get_sparse().is_some();
+        let results = if has_sparse && matches!(metric, Metric::InnerProduct | Metric::Cosine) {
+            dot_product_brute_force(self, queries, k, metric)?
+        } else {
+            self.search_with_scores(queries, k, metric)?
+        };
+        let k = min(k, self.num_points());
+        let neighbors = Array2::from_shape_fn((results.len(), k), |(i, j)| results[i][j].id);
+        let distances = Array2::from_shape_fn((results.len(), k), |(i, j)| results[i][j].score);
+        GroundTruth::with_distances(neighbors, distances)
+    }
+
+    /// Estimates the local intrinsic dimensionality (LID) of this set, a common measure of the
+    /// difficulty of a dataset for ANN search.
+    ///
+    /// Samples `sample_size` points uniformly at random using the given `seed`, computes the
+    /// maximum likelihood estimate of LID (Levina and Bickel) of each sampled point from the
+    /// distances to its `k` nearest neighbors among the other points according to `metric`, and
+    /// returns the average. Sampled points whose estimate is undefined, for example because they
+    /// have duplicates among their neighbors, are skipped.
+    ///
+    /// Returns an error if this set has a sparse component, if `metric` is `InnerProduct`, which
+    /// is not a distance, or if the estimate is undefined for every sampled point.
+    pub fn estimate_lid(
+        &self,
+        metric: &Metric,
+        k: usize,
+        sample_size: usize,
+        seed: u64,
+    ) -> Result<f32> {
+        let data = match (self.get_dense(), self.get_sparse()) {
+            (Some(data), None) => data,
+            _ => {
+                return Err(anyhow!(
+                    "LID estimation is only supported for dense point sets."
+                ))
+            }
+        };
+        if *metric == Metric::InnerProduct {
+            return Err(anyhow!("LID is undefined for {}.", metric));
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sample_ids = sample(&mut rng, data.nrows(), min(sample_size, data.nrows())).into_vec();
+        let estimates = maybe_par_iter!(&sample_ids)
+            .filter_map(|&id| {
+                let distances = top_k(data.view(), data.row(id), k + 1, &|point, query| {
+                    metric.distance(point, query)
+                })
+                .iter()
+                .filter(|c| c.id != id)
+                .take(k)
+                .map(|c| c.score)
+                .collect::<Vec<_>>();
+                lid_mle(&distances)
+            })
+            .collect::<Vec<_>>();
+
+        if estimates.is_empty() {
+            return Err(anyhow!(
+                "LID is undefined for all {} sampled points.",
+                sample_ids.len()
+            ));
+        }
+        Ok(estimates.iter().sum::<f32>() / estimates.len() as f32)
+    }
+}
+
+impl<DataType: Quantized + Sync> PointSet<DataType> {
+    /// Finds the exact `k` nearest neighbors by inner product of each point in `queries` among
+    /// the points in this quantized set of `i8` or `u8` vectors.
+    ///
+    /// Inner products are accumulated in `i64`, as products of quantized values quickly overflow
+    /// their own type, and sums of many such products overflow `i32`. As with `PointSet::search`, returns a matrix with `min(k, num_points)`
+    /// columns where the `i`-th row holds the ids of the neighbors of the `i`-th query, sorted by
+    /// descending inner product.
+    ///
+    /// Returns an error if either set has a sparse component, or if the dimensionality of the two
+    /// sets does not match.
+    pub fn search_inner_product(
+        &self,
+        queries: &PointSet<DataType>,
+        k: usize,
+    ) -> Result<Array2<usize>> {
+        let (data, query_points) =
+            dense_components(&self.view(), &queries.view(), &Metric::InnerProduct)?;
+        let k = min(k, data.nrows());
+        let mut neighbors = Array2::<usize>::zeros((query_points.nrows(), k));
+        zip_for_each!(
+            Zip::from(neighbors.axis_iter_mut(Axis(0))).and(query_points.axis_iter(Axis(0))),
+            |mut row, query| {
+                row.assign(&ArrayView1::from(&top_k_quantized(data, query, k)));
+            }
+        );
+        Ok(neighbors)
+    }
+}
+
+impl PointSetView<'_, f32> {
+    /// Same as `PointSet::search` but searches only among the points in this view, so that the
+    /// `j`-th point of the view has id `j` in the returned neighbors.
+    pub fn search(
+        &self,
+        queries: &PointSet<f32>,
+        k: usize,
+        metric: &Metric,
+    ) -> Result<Array2<usize>> {
+        if let Some((data, query_points)) = sparse_components(self, queries) {
+            let results = sparse_brute_force(data, query_points, k, metric)?;
+            let k = min(k, self.num_points());
+            return Ok(Array2::from_shape_fn((results.len(), k), |(i, j)| {
+                results[i][j].id
+            }));
+        }
+        let (data, query_points) = dense_components(self, &queries.view(), metric)?;
+        Ok(brute_force(data, query_points, k, |point, query| {
+            metric.distance(point, query)
+        }))
+    }
+
+    /// Same as `PointSet::search_with_scores` but searches only among the points in this view.
+    pub fn search_with_scores(
+        &self,
+        queries: &PointSet<f32>,
+        k: usize,
+        metric: &Metric,
+    ) -> Result<Vec<Vec<SearchResult>>> {
+        if let Some((data, query_points)) = sparse_components(self, queries) {
+            return sparse_brute_force(data, query_points, k, metric);
+        }
+        let (data, query_points) = dense_components(self, &queries.view(), metric)?;
+        Ok(brute_force_with_scores(
+            data,
+            query_points,
+            k,
+            |point, query| metric.distance(point, query),
+        ))
+    }
+}
+
+impl QuerySet<f32> {
+    /// Computes the exact `k` nearest neighbors of the query points among `data` for each of the
+    /// given `metrics` and attaches them as ground truth, replacing any existing ground truth
+    /// for those metrics.
+    ///
+    /// `Euclidean`, `Cosine`, and `InnerProduct` are computed together from a single inner
+    /// product between each query and the data points; other metrics, and all metrics if the
+    /// sets are sparse, are computed with `PointSet::search`. As with `search`, every set of
+    /// neighbors has `min(k, n)` columns, where `n` is the number of data points.
+    ///
+    /// Returns an error, leaving the query set unchanged, if the sets are not supported by
+    /// `PointSet::search` or if the dimensionality of the two sets does not match.
+    pub fn compute_ground_truth(
+        &mut self,
+        data: &PointSet<f32>,
+        metrics: &[Metric],
+        k: usize,
+    ) -> Result<()> {
+        let Some(first) = metrics.first() else {
+            return Ok(());
+        };
+        if data.get_sparse().is_some() || self.get_points().get_sparse().is_some() {
+            let ground_truths = metrics
+                .iter()
+                .map(|metric| Ok((metric.clone(), data.search(self.get_points(), k, metric)?)))
+                .collect::<Result<Vec<_>>>()?;
+            return self.add_ground_truth_batch(ground_truths);
+        }
+        let (data_points, query_points) =
+            dense_components(&data.view(), &self.get_points().view(), first)?;
+        let k = min(k, data_points.nrows());
+
+        let dot_metrics = metrics
+            .iter()
+            .filter(|metric| {
+                matches!(
+                    metric,
+                    Metric::Euclidean | Metric::Cosine | Metric::InnerProduct
+                )
+            })
+            .collect::<Vec<_>>();
+        let squared_norms = data_points.map_axis(Axis(1), |point| point.dot(&point));
+
+        // The `i`-th entry holds the neighbors of query `i` for every metric in `dot_metrics`.
+        let neighbors = maybe_par_iter!(query_points.axis_iter(Axis(0)))
+            .map(|query| {
+                let dots = data_points.dot(&query);
+                let query_norm = query.dot(&query).sqrt();
+                dot_metrics
+                    .iter()
+                    .map(|metric| {
+                        let pairs = dots.iter().zip(squared_norms.iter());
+                        let candidates = match metric {
+                            // The squared norm of the query does not affect the ranking.
+                            Metric::Euclidean => {
+                                smallest_k(pairs.map(|(&dot, &norm)| norm - 2_f32 * dot), k)
+                            }
+                            Metric::Cosine => smallest_k(
+                                pairs.map(|(&dot, &norm)| {
+                                    let norms = norm.sqrt() * query_norm;
+                                    if norms > 0_f32 {
+                                        1_f32 - dot / norms
+                                    } else {
+                                        1_f32
+                                    }
+                                }),
+                                k,
+                            ),
+                            _ => smallest_k(dots.iter().map(|&dot| -dot), k),
+                        };
+                        candidates.iter().map(|c| c.id).collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut ground_truths = Vec::with_capacity(metrics.len());
+        for (index, &metric) in dot_metrics.iter().enumerate() {
+            let ids = neighbors
+                .iter()
+                .flat_map(|per_metric| per_metric[index].iter().copied())
+                .collect::<Vec<_>>();
+            ground_truths.push((
+                metric.clone(),
+                Array2::from_shape_vec((neighbors.len(), k), ids)?,
+            ));
+        }
+        for metric in metrics
+            .iter()
+            .filter(|metric| !dot_metrics.contains(metric))
+        {
+            ground_truths.push((metric.clone(), data.search(self.get_points(), k, metric)?));
+        }
+
+        // Stores all sets at once, so that none is stored if any fails validation.
+        self.add_ground_truth_batch(ground_truths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GroundTruth, Metric, PointSet, QuerySet, SearchResult};
+    use approx_eq::assert_approx_eq;
+    use ndarray::{arr2, s, Array2, Axis};
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+    use sprs::TriMat;
+
+    #[test]
+    fn test_search_chebyshev() {
+        let data = arr2(&[
+            [0.0_f32, 0.0],
+            [3.0, 0.5],
+            [1.0, 1.0],
+            [-2.0, 2.5],
+            [0.5, -4.0],
+        ]);
+        let queries = arr2(&[[0.0_f32, 0.0], [2.0, 1.0]]);
+
+        let data = PointSet::new(Some(data), None).unwrap();
+        let queries = PointSet::new(Some(queries), None).unwrap();
+
+        // Distances from the first query: [0, 3, 1, 2.5, 4].
+        // Distances from the second query: [2, 1, 1, 4, 5].
+        let neighbors = data.search(&queries, 3, &Metric::Chebyshev).unwrap();
+        assert_eq!(neighbors, arr2(&[[0_usize, 2, 3], [1, 2, 0]]));
+
+        // Ids are relative to the start of the view.
+        let view = data.slice_rows(1, 4).unwrap();
+        let neighbors = view.search(&queries, 2, &Metric::Chebyshev).unwrap();
+        assert_eq!(neighbors, arr2(&[[1_usize, 2], [0, 1]]));
+    }
+
+    #[test]
+    fn test_search_with_scores() {
+        let data = PointSet::random_dense(50, 8, 1);
+        let queries = PointSet::random_dense(5, 8, 2);
+
+        for metric in [Metric::Euclidean, Metric::InnerProduct, Metric::Chebyshev] {
+            let neighbors = data.search(&queries, 7, &metric).unwrap();
+            let results = data.search_with_scores(&queries, 7, &metric).unwrap();
+            assert_eq!(results.len(), 5);
+            for (i, row) in results.iter().enumerate() {
+                let ids = row.iter().map(|result| result.id).collect::<Vec<_>>();
+                assert_eq!(ids, neighbors.row(i).to_vec());
+                assert!(row.windows(2).all(|w| w[0] <= w[1]));
+                row.iter().for_each(|result| {
+                    let expected = metric.distance(
+                        data.get_dense().unwrap().row(result.id),
+                        queries.get_dense().unwrap().row(i),
+                    );
+                    assert_approx_eq!(result.score as f64, expected as f64);
+                });
+            }
+        }
+
+        let a = SearchResult { id: 3, score: 0.5 };
+        let b = SearchResult { id: 1, score: 0.5 };
+        let c = SearchResult { id: 0, score: 0.7 };
+        assert!(b < a && a < c);
+    }
+
+    #[test]
+    fn test_search_inner_product_quantized() {
+        let data = arr2(&[[100_i8, 100], [-100, 50], [127, -127], [10, 10]]);
+        let queries = arr2(&[[100_i8, 100], [-1, 1]]);
+        let data = PointSet::new(Some(data), None).unwrap();
+        let queries = PointSet::new(Some(queries), None).unwrap();
+
+        // Inner products with the first query: [20000, -5000, 0, 2000], which overflow `i16`.
+        // Inner products with the second query: [0, 150, -254, 0].
+        let neighbors = data.search_inner_product(&queries, 3).unwrap();
+        assert_eq!(neighbors, arr2(&[[0_usize, 3, 2], [1, 0, 3]]));
+
+        let data = PointSet::new(Some(arr2(&[[200_u8, 0], [0, 255], [100, 100]])), None).unwrap();
+        let queries = PointSet::new(Some(arr2(&[[255_u8, 10]])), None).unwrap();
+        let neighbors = data.search_inner_product(&queries, 5).unwrap();
+        assert_eq!(neighbors, arr2(&[[0_usize, 2, 1]]));
+
+        // Near the limits of the types, sums over many dimensions overflow `i32`.
+        fn tile<T: Copy>(base: Array2<T>, times: usize) -> Array2<T> {
+            Array2::from_shape_fn((base.nrows(), base.ncols() * times), |(i, j)| {
+                base[[i, j % base.ncols()]]
+            })
+        }
+        let dims = 70_000;
+        let data = tile(arr2(&[[-128_i8, -128], [127, 127], [1, 1]]), dims);
+        let queries = tile(arr2(&[[-128_i8, -128], [127, 127]]), dims);
+        let data = PointSet::new(Some(data), None).unwrap();
+        let queries = PointSet::new(Some(queries), None).unwrap();
+        let neighbors = data.search_inner_product(&queries, 3).unwrap();
+        assert_eq!(neighbors, arr2(&[[0_usize, 2, 1], [1, 2, 0]]));
+
+        let data = tile(arr2(&[[255_u8], [254], [0]]), dims);
+        let queries = tile(arr2(&[[255_u8]]), dims);
+        let data = PointSet::new(Some(data), None).unwrap();
+        let queries = PointSet::new(Some(queries), None).unwrap();
+        let neighbors = data.search_inner_product(&queries, 3).unwrap();
+        assert_eq!(neighbors, arr2(&[[0_usize, 1, 2]]));
+
+        let wide = PointSet::new(Some(Array2::<i8>::zeros((1, 3))), None).unwrap();
+        let data = PointSet::new(Some(Array2::<i8>::zeros((2, 2))), None).unwrap();
+        assert!(data.search_inner_product(&wide, 1).is_err());
+    }
+
+    #[test]
+    fn test_search_k_larger_than_data() {
+        let data =
+            PointSet::new(Some(arr2(&[[0.0_f32, 1.0], [1.0, 0.0], [2.0, 2.0]])), None).unwrap();
+        let queries = PointSet::new(Some(arr2(&[[1.0_f32, 1.0], [0.0, 0.0]])), None).unwrap();
+
+        let neighbors = data.search(&queries, 10, &Metric::Euclidean).unwrap();
+        assert_eq!(neighbors, arr2(&[[0_usize, 1, 2], [0, 1, 2]]));
+
+        let gt = GroundTruth::new(neighbors);
+        let recall = gt.mean_recall(&[vec![0, 1, 2, 5, 6], vec![1, 0, 2, 3, 4]]);
+        assert_approx_eq!(recall.unwrap() as f64, 1.0, 0.001);
+
+        let neighbors = data.search(&queries, 0, &Metric::Euclidean).unwrap();
+        assert_eq!(neighbors.shape(), &[2, 0]);
+    }
+
+    #[test]
+    fn test_search_angular() {
+        let data = arr2(&[[1.0_f32, 0.0], [0.0, 2.0], [-3.0, 0.0], [1.0, 1.0]]);
+        let queries = arr2(&[[2.0_f32, 0.1], [0.0, -1.0]]);
+
+        let data = PointSet::new(Some(data), None).unwrap();
+        let queries = PointSet::new(Some(queries), None).unwrap();
+
+        let neighbors = data.search(&queries, 4, &Metric::Angular).unwrap();
+        assert_eq!(neighbors, arr2(&[[0_usize, 3, 1, 2], [0, 2, 3, 1]]));
+    }
+
+    #[test]
+    fn test_estimate_lid() {
+        // Points drawn uniformly from a 2-dimensional subspace of a 6-dimensional space.
+        let plane = Array2::random((2000, 2), Uniform::new(0_f32, 1_f32));
+        let data = ndarray::concatenate![Axis(1), plane, Array2::<f32>::zeros((2000, 4))];
+        let data = PointSet::new(Some(data), None).unwrap();
+
+        let lid = data.estimate_lid(&Metric::Euclidean, 20, 200, 7).unwrap();
+        assert!((1.5..2.5).contains(&lid), "LID was {}", lid);
+        assert_eq!(
+            lid,
+            data.estimate_lid(&Metric::Euclidean, 20, 200, 7).unwrap()
+        );
+
+        assert!(data
+            .estimate_lid(&Metric::InnerProduct, 20, 200, 7)
+            .is_err());
+
+        let duplicates = PointSet::new(Some(Array2::<f32>::zeros((10, 3))), None).unwrap();
+        assert!(duplicates
+            .estimate_lid(&Metric::Euclidean, 3, 10, 7)
+            .is_err());
+    }
+
+    #[test]
+    fn test_compute_ground_truth() {
+        let small_data = arr2(&[
+            [1.0_f32, 0.0],
+            [0.0, 2.0],
+            [-1.5, 0.5],
+            [3.0, 1.0],
+            [0.5, -2.5],
+            [0.2, 0.1],
+        ]);
+        let small_queries = arr2(&[[1.0_f32, 0.2], [-1.0, 1.5]]);
+        let metrics = [
+            Metric::Euclidean,
+            Metric::Cosine,
+            Metric::InnerProduct,
+            Metric::Chebyshev,
+        ];
+        let mut query_set =
+            QuerySet::new(PointSet::new(Some(small_queries.clone()), None).unwrap());
+        query_set
+            .compute_ground_truth(
+                &PointSet::new(Some(small_data.clone()), None).unwrap(),
+                &metrics,
+                4,
+            )
+            .unwrap();
+
+        // Inner products with the first query: [1, 0.4, -1.4, 3.2, 0, 0.22].
+        // Inner products with the second query: [-1, 3, 2.25, -1.5, -4.25, -0.05].
+        assert_eq!(
+            query_set
+                .get_ground_truth(&Metric::InnerProduct)
+                .unwrap()
+                .get_neighbors(),
+            arr2(&[[3_usize, 0, 1, 5], [1, 2, 5, 0]])
+        );
+        for metric in &metrics {
+            // Sorts all points by their distance to each query; `sort_by` is stable, so ties are
+            // broken by the smaller id as in `search`.
+            let expected = small_queries
+                .rows()
+                .into_iter()
+                .flat_map(|query| {
+                    let mut ids = (0..small_data.nrows()).collect::<Vec<_>>();
+                    ids.sort_by(|&a, &b| {
+                        metric
+                            .distance(small_data.row(a), query)
+                            .total_cmp(&metric.distance(small_data.row(b), query))
+                    });
+                    ids.truncate(4);
+                    ids
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                query_set.get_ground_truth(metric).unwrap().get_neighbors(),
+                Array2::from_shape_vec((2, 4), expected).unwrap()
+            );
+        }
+        assert!(query_set.get_ground_truth(&Metric::Hamming).is_err());
+
+        let data = Array2::random((200, 8), Uniform::new(-1_f32, 1_f32));
+        let queries = Array2::random((10, 8), Uniform::new(-1_f32, 1_f32));
+        let data = PointSet::new(Some(data), None).unwrap();
+        let queries = PointSet::new(Some(queries), None).unwrap();
+
+        let mut query_set = QuerySet::new(queries);
+        query_set
+            .compute_ground_truth(&data, &[Metric::InnerProduct], 500)
+            .unwrap();
+        let gt = query_set.get_ground_truth(&Metric::InnerProduct).unwrap();
+        assert_eq!(gt.get_neighbors().shape(), &[10, 200]);
+
+        let queries = PointSet::new(Some(Array2::<f32>::eye(3)), None).unwrap();
+        let mut query_set = QuerySet::new(queries);
+        assert!(query_set
+            .compute_ground_truth(&data, &[Metric::InnerProduct], 5)
+            .is_err());
+        assert!(query_set.get_ground_truth(&Metric::InnerProduct).is_err());
+
+        // Jaccard ground truth fails validation without sparse query points, after `Euclidean`
+        // and `Hamming` are computed; neither may be stored.
+        let queries = PointSet::new(Some(Array2::<f32>::eye(8)), None).unwrap();
+        let mut query_set = QuerySet::new(queries);
+        query_set
+            .compute_ground_truth(&data, &[Metric::Cosine], 3)
+            .unwrap();
+        let before = query_set.clone();
+        assert!(query_set
+            .compute_ground_truth(
+                &data,
+                &[
+                    Metric::Euclidean,
+                    Metric::Cosine,
+                    Metric::Hamming,
+                    Metric::Jaccard
+                ],
+                5
+            )
+            .is_err());
+        assert_eq!(query_set, before);
+    }
+
+    #[test]
+    fn test_knn() {
+        let data = PointSet::random_dense(40, 6, 3);
+        let queries = PointSet::random_dense(4, 6, 4);
+        let (dense, query_points) = (data.get_dense().unwrap(), queries.get_dense().unwrap());
+
+        for metric in [Metric::Euclidean, Metric::Cosine, Metric::InnerProduct] {
+            let gt = data.knn(&queries, 5, &metric).unwrap();
+            assert_eq!(gt.get_neighbors().shape(), &[4, 5]);
+            for (i, query) in query_points.rows().into_iter().enumerate() {
+                let mut expected = dense
+                    .rows()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, point)| (metric.distance(point, query), id))
+                    .collect::<Vec<_>>();
+                expected.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+                let ids = expected
+                    .iter()
+                    .take(5)
+                    .map(|&(_, id)| id)
+                    .collect::<Vec<_>>();
+                assert_eq!(gt.neighbors_of(i).to_vec(), ids);
+                for (j, &(distance, _)) in expected.iter().take(5).enumerate() {
+                    let recorded = gt.get_distances().unwrap()[[i, j]];
+                    assert!((recorded - distance).abs() < 1e-5);
+                }
+            }
+        }
+
+        let gt = data.knn(&queries, 100, &Metric::Euclidean).unwrap();
+        assert_eq!(gt.get_distances().unwrap().shape(), &[4, 40]);
+        let wide = PointSet::random_dense(2, 7, 0);
+        assert!(data.knn(&wide, 1, &Metric::Euclidean).is_err());
+    }
+
+    #[test]
+    fn test_search_batch() {
+        let data = PointSet::random_dense(60, 5, 5);
+        let queries = data.select(&[3, 17, 42]);
+
+        for metric in [Metric::Euclidean, Metric::Cosine, Metric::Chebyshev] {
+            let retrieved = data.search_batch(&queries, 4, &metric).unwrap();
+            assert_eq!(retrieved.len(), 3);
+            assert!(retrieved.iter().all(|ids| ids.len() == 4));
+            let gt = data.knn(&queries, 4, &metric).unwrap();
+            assert_eq!(gt.mean_recall(&retrieved).unwrap(), 1_f32);
+        }
+        assert_eq!(
+            data.search_batch(&queries, 1, &Metric::Euclidean).unwrap(),
+            vec![vec![3], vec![17], vec![42]]
+        );
+
+        let wide = PointSet::random_dense(2, 6, 0);
+        assert!(data.search_batch(&wide, 1, &Metric::Euclidean).is_err());
+    }
+
+    #[test]
+    fn test_knn_sparse() {
+        let dense = arr2(&[
+            [1.0_f32, 0.0, 2.0, 0.0],
+            [0.0, 3.0, 0.0, 0.0],
+            [0.5, 0.0, 0.0, -1.0],
+            [0.0, 0.0, 0.0, 0.0],
+            [2.0, 1.0, 1.0, 1.0],
+        ]);
+        let queries = arr2(&[[1.0_f32, 0.0, 1.0, 0.0], [0.0, 1.0, 0.0, -2.0]]);
+        let to_sparse = |points: &Array2<f32>| {
+            let mut triplets = TriMat::new((points.nrows(), points.ncols()));
+            points.indexed_iter().for_each(|((i, j), &value)| {
+                if value != 0_f32 {
+                    triplets.add_triplet(i, j, value);
+                }
+            });
+            PointSet::new(None, Some(triplets.to_csr())).unwrap()
+        };
+        let sparse_data = to_sparse(&dense);
+        let sparse_queries = to_sparse(&queries);
+        let dense_data = PointSet::new(Some(dense.clone()), None).unwrap();
+        let dense_queries = PointSet::new(Some(queries.clone()), None).unwrap();
+
+        // Inner products with the first query: [3, 0, 0.5, 0, 3].
+        // Inner products with the second query: [0, 3, 2, 0, -1].
+        let gt = sparse_data
+            .knn(&sparse_queries, 3, &Metric::InnerProduct)
+            .unwrap();
+        assert_eq!(gt.get_neighbors(), arr2(&[[0_usize, 4, 2], [1, 2, 0]]));
+        assert_eq!(
+            gt.get_distances().unwrap(),
+            arr2(&[[-3_f32, -3.0, -0.5], [-3.0, -2.0, 0.0]])
+        );
+
+        for metric in [Metric::InnerProduct, Metric::Cosine] {
+            let expected = dense_data.knn(&dense_queries, 5, &metric).unwrap();
+            let gt = sparse_data.knn(&sparse_queries, 5, &metric).unwrap();
+            assert_eq!(gt.get_neighbors(), expected.get_neighbors());
+            gt.get_distances()
+                .unwrap()
+                .iter()
+                .zip(expected.get_distances().unwrap().iter())
+                .for_each(|(x, y)| assert!((x - y).abs() < 1e-5));
+
+            // Split every point into a dense and a sparse half.
+            let split = |points: &Array2<f32>| {
+                let sparse = to_sparse(&points.slice(s![.., 2..]).to_owned());
+                PointSet::new(
+                    Some(points.slice(s![.., ..2]).to_owned()),
+                    sparse.get_sparse().cloned(),
+                )
+                .unwrap()
+            };
+            let gt = split(&dense).knn(&split(&queries), 5, &metric).unwrap();
+            assert_eq!(gt.get_neighbors(), expected.get_neighbors());
+        }
+
+        let gt = sparse_data
+            .knn(&sparse_queries, 5, &Metric::Euclidean)
+            .unwrap();
+        let expected = dense_data
+            .knn(&dense_queries, 5, &Metric::Euclidean)
+            .unwrap();
+        assert_eq!(gt.get_neighbors(), expected.get_neighbors());
+
+        let split = PointSet::new(
+            Some(dense.slice(s![.., ..2]).to_owned()),
+            to_sparse(&dense.slice(s![.., 2..]).to_owned())
+                .get_sparse()
+                .cloned(),
+        )
+        .unwrap();
+        assert!(split.knn(&split, 3, &Metric::Euclidean).is_err());
+        assert!(sparse_data
+            .knn(&dense_queries, 3, &Metric::InnerProduct)
+            .is_err());
+    }
+
+    #[test]
+    fn test_search_sparse_counts() {
+        let data = arr2(&[
+            [3.0_f32, 0.0, 0.0, 1.0, 0.0],
+            [0.0, 2.0, 0.0, 0.0, 4.0],
+            [1.0, 0.0, 5.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0, 1.0, 1.0],
+        ]);
+        let queries = arr2(&[[2.0_f32, 0.0, 0.0, 1.0, 0.0], [0.0, 1.0, 1.0, 0.0, 3.0]]);
+        let to_sparse = |points: &Array2<f32>| {
+            let mut triplets = TriMat::new((points.nrows(), points.ncols()));
+            points.indexed_iter().for_each(|((i, j), &value)| {
+                if value != 0_f32 {
+                    triplets.add_triplet(i, j, value);
+                }
+            });
+            PointSet::new(None, Some(triplets.to_csr())).unwrap()
+        };
+        let sparse_data = to_sparse(&data);
+        let sparse_queries = to_sparse(&queries);
+
+        // Canberra distances to the first query: [0.2, 4, 2.33, 2, 2].
+        // Canberra distances to the second query: [5, 1.48, 3.67, 3, 3.5].
+        let neighbors = sparse_data
+            .search(&sparse_queries, 3, &Metric::Canberra)
+            .unwrap();
+        assert_eq!(neighbors, arr2(&[[0_usize, 3, 4], [1, 3, 4]]));
+
+        // Bray-Curtis distances to the first query: [0.14, 1, 0.78, 1, 0.25].
+        // Bray-Curtis distances to the second query: [1, 0.27, 0.82, 1, 0.6].
+        let results = sparse_data
+            .search_with_scores(&sparse_queries, 2, &Metric::BrayCurtis)
+            .unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|row| row.iter().map(|c| c.id).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            vec![vec![0, 4], vec![1, 4]]
+        );
+        assert_approx_eq!(results[0][0].score as f64, 1.0 / 7.0, 1e-6);
+
+        // Both agree with the per-pair distance between dense vectors.
+        let mut query_set = QuerySet::new(sparse_queries.clone());
+        let metrics = [Metric::Canberra, Metric::BrayCurtis, Metric::Euclidean];
+        query_set
+            .compute_ground_truth(&sparse_data, &metrics, 5)
+            .unwrap();
+        for metric in &metrics {
+            let expected = queries
+                .rows()
+                .into_iter()
+                .flat_map(|query| {
+                    let mut ids = (0..data.nrows()).collect::<Vec<_>>();
+                    ids.sort_by(|&a, &b| {
+                        metric
+                            .distance(data.row(a), query)
+                            .total_cmp(&metric.distance(data.row(b), query))
+                    });
+                    ids
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                query_set.get_ground_truth(metric).unwrap().get_neighbors(),
+                Array2::from_shape_vec((2, 5), expected).unwrap()
+            );
+        }
+
+        let dense_queries = PointSet::new(Some(queries), None).unwrap();
+        assert!(sparse_data
+            .search(&dense_queries, 3, &Metric::Canberra)
+            .is_err());
+    }
+
+    #[test]
+    fn test_search_errors() {
+        let data = PointSet::new(Some(Array2::<f32>::eye(4)), None).unwrap();
+        let queries = PointSet::new(Some(Array2::<f32>::eye(3)), None).unwrap();
+        assert!(data.search(&queries, 1, &Metric::Chebyshev).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use hdf5::types::{IntSize, TypeDescriptor};
+use hdf5::H5Type;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// A half-precision floating point value that can be stored in HDF5 files, e.g., as the data type
+/// of an `InMemoryAnnDataset<F16>`.
+///
+/// `hdf5` has no half-precision float type, and `H5Type` can not be implemented for `half::f16`
+/// outside of either crate, so this wraps `half::f16` and is stored as its raw bits in an
+/// unsigned 16-bit integer dataset. Other readers, such as `h5py`, see `uint16` values that can be
+/// reinterpreted as `float16` (e.g., `array.view(numpy.float16)`). With serde, a value is likewise
+/// represented by its bits.
+#[derive(PartialEq, PartialOrd, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(from = "u16", into = "u16")]
+#[repr(transparent)]
+pub struct F16(pub half::f16);
+
+impl F16 {
+    /// Rounds `value` to the nearest half-precision value.
+    pub fn from_f32(value: f32) -> F16 {
+        F16(half::f16::from_f32(value))
+    }
+
+    /// Returns the value in single precision. The conversion is exact.
+    pub fn to_f32(self) -> f32 {
+        self.0.to_f32()
+    }
+}
+
+unsafe impl H5Type for F16 {
+    fn type_descriptor() -> TypeDescriptor {
+        TypeDescriptor::Unsigned(IntSize::U2)
+    }
+}
+
+impl From<half::f16> for F16 {
+    fn from(value: half::f16) -> Self {
+        F16(value)
+    }
+}
+
+impl From<F16> for half::f16 {
+    fn from(value: F16) -> Self {
+        value.0
+    }
+}
+
+impl From<F16> for f32 {
+    fn from(value: F16) -> Self {
+        value.to_f32()
+    }
+}
+
+impl From<u16> for F16 {
+    fn from(bits: u16) -> Self {
+        F16(half::f16::from_bits(bits))
+    }
+}
+
+impl From<F16> for u16 {
+    fn from(value: F16) -> Self {
+        value.0.to_bits()
+    }
+}
+
+impl Display for F16 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::f16::F16;
+    use crate::{AnnDataset, Hdf5File, InMemoryAnnDataset, PointSet, QuerySet};
+    use ndarray::arr2;
+    use sprs::CsMat;
+    use tempdir::TempDir;
+
+    fn sample_points() -> PointSet<f32> {
+        let dense = arr2(&[[0.5_f32, -1.25, 3.0], [1e-3, 65504.0, 0.1]]);
+        let sparse = CsMat::new(
+            (2, 4),
+            vec![0, 1, 3],
+            vec![2, 0, 3],
+            vec![0.25_f32, -2.0, 7.5],
+        );
+        PointSet::new(Some(dense), Some(sparse)).unwrap()
+    }
+
+    #[test]
+    fn test_conversion() {
+        assert_eq!(F16::from_f32(0.5).to_f32(), 0.5);
+        assert_eq!(F16::from_f32(0.1).to_f32(), 0.099975586);
+        assert_eq!(F16::from_f32(1e6).to_f32(), f32::INFINITY);
+        assert_eq!(u16::from(F16::from_f32(1.0)), 0x3c00);
+        assert_eq!(F16::from(0x3c00_u16), F16::from_f32(1.0));
+
+        let points = sample_points();
+        let half = points.cast_with(|&x| F16::from_f32(x));
+        assert_eq!(half.num_dense_dimensions(), 3);
+        assert_eq!(half.num_sparse_dimensions(), 4);
+        let rounded = half.cast::<f32>();
+        assert_eq!(
+            rounded.get_dense().unwrap(),
+            arr2(&[[0.5_f32, -1.25, 3.0], [0.0010004044, 65504.0, 0.099975586]])
+        );
+        assert_eq!(rounded.get_sparse(), points.get_sparse());
+    }
+
+    #[test]
+    fn test_serde() {
+        let points = sample_points().cast_with(|&x| F16::from_f32(x));
+        let dataset = InMemoryAnnDataset::create(points);
+        let bytes = bincode::serialize(&dataset).unwrap();
+        let copy: InMemoryAnnDataset<F16> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(copy, dataset);
+    }
+
+    #[test]
+    fn test_hdf5_f16() {
+        let points = sample_points().cast_with(|&x| F16::from_f32(x));
+        let mut dataset = InMemoryAnnDataset::create(points.clone());
+        dataset
+            .add_test_query_set(QuerySet::new(points.clone()))
+            .unwrap();
+
+        let dir = TempDir::new("test_hdf5_f16").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        dataset.write(path).unwrap();
+
+        let copy = InMemoryAnnDataset::<F16>::read(path).unwrap();
+        assert_eq!(copy.get_data_points(), &points);
+        assert_eq!(copy.get_test_query_set().unwrap().get_points(), &points);
+    }
+}
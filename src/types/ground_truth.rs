@@ -1,81 +1,967 @@
 use crate::Hdf5Serialization;
 use anyhow::{anyhow, Result};
 use hdf5::Group;
-use ndarray::{Array2, ArrayView2};
-use roaring::RoaringBitmap;
+#[cfg(feature = "parallel")]
+use ndarray::parallel::prelude::*;
+use ndarray::{s, Array2, ArrayView1, ArrayView2, Axis};
+use roaring::RoaringTreemap;
 use serde::{Deserialize, Serialize};
 use std::cmp::min;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::iter::zip;
+use std::sync::Mutex;
+
+const OFFSETS: &str = "offsets";
+const DISTANCES: &str = "distances";
+
+/// Number of queries processed between two calls to the progress callback of
+/// `recall_with_progress`.
+const PROGRESS_BATCH_SIZE: usize = 1024;
+
+/// Id that pads the rows of ragged ground truth to a common length.
+const PADDING: usize = usize::MAX;
 
 /// Defines the exact nearest neighbors.
-#[derive(Eq, PartialEq, Default, Debug, Clone, Serialize, Deserialize)]
-pub struct GroundTruth(Array2<usize>);
+///
+/// Every query typically has the same number of neighbors, but ground truth may also be ragged,
+/// with a different number of valid neighbors per query, as is the case in filtered search.
+/// Ground truth may also record the distance from every query to each of its neighbors.
+///
+/// Distances are compared bit by bit, so that equality is reflexive even if a distance is NaN.
+///
+/// Note that with serde, ground truth is a struct with the fields `neighbors`, `lengths` and
+/// `distances`, where the latter two may be omitted. Earlier versions of this library serialized
+/// ground truth as the bare neighbor matrix, so ground truth serialized by those versions, e.g.,
+/// in a bincode cache written by `InMemoryAnnDataset::write_bincode`, can not be deserialized
+/// and must be regenerated, for example from the HDF5 file it came from.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct GroundTruth {
+    neighbors: Array2<usize>,
+    /// Number of valid neighbors in each row if not all rows have the same number of neighbors,
+    /// in which case rows are padded with `PADDING` to the length of the longest row.
+    #[serde(default)]
+    lengths: Option<Vec<usize>>,
+    /// Distance from every query to each of its neighbors, with the same shape as `neighbors`.
+    /// Entries that correspond to padding in ragged ground truth are NaN.
+    #[serde(default)]
+    distances: Option<Array2<f32>>,
+}
 
 impl GroundTruth {
     pub fn new(neighbors: Array2<usize>) -> GroundTruth {
-        GroundTruth(neighbors)
+        GroundTruth {
+            neighbors,
+            lengths: None,
+            distances: None,
+        }
+    }
+
+    /// Creates ground truth where `distances[[i, j]]` is the distance from the `i`-th query to
+    /// its neighbor `neighbors[[i, j]]`.
+    ///
+    /// Returns an error if `neighbors` and `distances` do not have the same shape.
+    pub fn with_distances(neighbors: Array2<usize>, distances: Array2<f32>) -> Result<GroundTruth> {
+        if neighbors.shape() != distances.shape() {
+            return Err(anyhow!(
+                "Neighbors have shape {:?}, but distances have shape {:?}.",
+                neighbors.shape(),
+                distances.shape()
+            ));
+        }
+        Ok(GroundTruth {
+            neighbors,
+            lengths: None,
+            distances: Some(distances),
+        })
+    }
+
+    /// Creates ground truth where the `i`-th query has the neighbors in `rows[i]`.
+    ///
+    /// Returns an error if the rows do not all have the same length; use `new_ragged` for ground
+    /// truth with a different number of neighbors per query.
+    pub fn from_rows(rows: Vec<Vec<usize>>) -> Result<GroundTruth> {
+        let width = rows.first().map_or(0, |row| row.len());
+        let mut builder = GroundTruthBuilder::with_capacity(width, rows.len());
+        rows.iter().try_for_each(|row| builder.push_row(row))?;
+        Ok(builder.build())
+    }
+
+    /// Creates ground truth where the `i`-th query has the neighbors in `rows[i]`, which may
+    /// differ in length from one query to another.
+    pub fn new_ragged(rows: Vec<Vec<usize>>) -> GroundTruth {
+        let lengths = rows.iter().map(|row| row.len()).collect();
+        GroundTruth::from_flat(rows.into_iter().flatten().collect(), lengths)
+            .expect("Row lengths always add up to the number of ids.")
+    }
+
+    /// Creates ragged ground truth as `new_ragged` does, where `distances[i][j]` is the distance
+    /// from the `i`-th query to its neighbor `rows[i][j]`.
+    ///
+    /// Returns an error if the rows of `distances` do not have the same lengths as `rows`.
+    pub fn new_ragged_with_distances(
+        rows: Vec<Vec<usize>>,
+        distances: Vec<Vec<f32>>,
+    ) -> Result<GroundTruth> {
+        if rows.len() != distances.len()
+            || zip(&rows, &distances).any(|(row, distances)| row.len() != distances.len())
+        {
+            return Err(anyhow!(
+                "Rows of distances do not have the same lengths as the rows of neighbors."
+            ));
+        }
+        GroundTruth::new_ragged(rows).with_flat_distances(distances.into_iter().flatten().collect())
+    }
+
+    /// Records `distances`, the concatenation of the distances from every query to its valid
+    /// neighbors, in the order of `to_flat`. Padding in ragged ground truth gets NaN distances.
+    fn with_flat_distances(mut self, distances: Vec<f32>) -> Result<GroundTruth> {
+        let total = (0..self.neighbors.nrows())
+            .map(|query| self.num_neighbors(query))
+            .sum::<usize>();
+        if total != distances.len() {
+            return Err(anyhow!(
+                "Ground truth has {} neighbors but {} distances.",
+                total,
+                distances.len()
+            ));
+        }
+        let mut padded = Array2::from_elem(self.neighbors.dim(), f32::NAN);
+        let mut offset = 0;
+        for (query, mut row) in padded.axis_iter_mut(Axis(0)).enumerate() {
+            let length = self.num_neighbors(query);
+            row.slice_mut(s![..length])
+                .assign(&ArrayView1::from(&distances[offset..offset + length]));
+            offset += length;
+        }
+        self.distances = Some(padded);
+        Ok(self)
+    }
+
+    /// Creates ground truth from the concatenation of all rows, `ids`, and the length of each row.
+    ///
+    /// Returns an error if the lengths do not add up to the number of ids.
+    pub(crate) fn from_flat(ids: Vec<usize>, lengths: Vec<usize>) -> Result<GroundTruth> {
+        let total = lengths.iter().sum::<usize>();
+        if total != ids.len() {
+            return Err(anyhow!(
+                "Lengths of ground-truth rows add up to {} but there are {} neighbors.",
+                total,
+                ids.len()
+            ));
+        }
+        let width = lengths.iter().copied().max().unwrap_or(0);
+        if lengths.iter().all(|&length| length == width) {
+            return Ok(GroundTruth::new(Array2::from_shape_vec(
+                (lengths.len(), width),
+                ids,
+            )?));
+        }
+
+        let mut neighbors = Array2::from_elem((lengths.len(), width), PADDING);
+        let mut offset = 0;
+        neighbors
+            .axis_iter_mut(Axis(0))
+            .zip(&lengths)
+            .for_each(|(mut row, &length)| {
+                row.slice_mut(s![..length])
+                    .assign(&ArrayView1::from(&ids[offset..offset + length]));
+                offset += length;
+            });
+        Ok(GroundTruth {
+            neighbors,
+            lengths: Some(lengths),
+            distances: None,
+        })
     }
 
     /// Returns the set of neighbors.
-    pub fn get_neighbors(&self) -> ArrayView2<usize> {
-        self.0.view()
+    ///
+    /// If the ground truth is ragged (see `is_ragged`), shorter rows are padded with
+    /// `usize::MAX` to the length of the longest row. Use `neighbors_of` to get only the valid
+    /// neighbors of a query.
+    pub fn get_neighbors(&self) -> ArrayView2<'_, usize> {
+        self.neighbors.view()
     }
 
-    /// Computes recall given a retrieved set.
+    /// Returns the distance from every query to each of its neighbors, in the order of
+    /// `get_neighbors`, if recorded. If the ground truth is ragged, distances of padding are NaN.
+    pub fn get_distances(&self) -> Option<ArrayView2<'_, f32>> {
+        self.distances.as_ref().map(|distances| distances.view())
+    }
+
+    /// Returns `true` if queries have different numbers of neighbors.
+    pub fn is_ragged(&self) -> bool {
+        self.lengths.is_some()
+    }
+
+    /// Returns the number of valid neighbors of the `query`-th query.
+    pub fn num_neighbors(&self, query: usize) -> usize {
+        match self.lengths.as_ref() {
+            None => self.neighbors.ncols(),
+            Some(lengths) => lengths[query],
+        }
+    }
+
+    /// Returns the valid neighbors of the `query`-th query.
+    pub fn neighbors_of(&self, query: usize) -> ArrayView1<'_, usize> {
+        self.neighbors.slice(s![query, ..self.num_neighbors(query)])
+    }
+
+    /// Keeps only the first `k` neighbors of every query, and their distances if recorded. Queries
+    /// with fewer than `k` valid neighbors are left unchanged.
+    pub fn truncate(&mut self, k: usize) {
+        if k >= self.neighbors.ncols() {
+            return;
+        }
+        self.neighbors = self.neighbors.slice(s![.., ..k]).to_owned();
+        self.distances = self
+            .distances
+            .as_ref()
+            .map(|distances| distances.slice(s![.., ..k]).to_owned());
+        if let Some(lengths) = self.lengths.take() {
+            let lengths = lengths
+                .into_iter()
+                .map(|length| min(length, k))
+                .collect::<Vec<_>>();
+            if lengths.iter().any(|&length| length != k) {
+                self.lengths = Some(lengths);
+            }
+        }
+    }
+
+    /// Returns an error if the number of valid neighbors recorded for a ragged set does not agree
+    /// with the shape of the neighbor matrix, if padding appears among valid neighbors, or if
+    /// distances do not have the same shape as the neighbor matrix.
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Some(distances) = self.distances.as_ref() {
+            if distances.shape() != self.neighbors.shape() {
+                return Err(anyhow!(
+                    "Neighbors have shape {:?}, but distances have shape {:?}.",
+                    self.neighbors.shape(),
+                    distances.shape()
+                ));
+            }
+        }
+        if let Some(lengths) = self.lengths.as_ref() {
+            if lengths.len() != self.neighbors.nrows() {
+                return Err(anyhow!(
+                    "Ground truth records the number of neighbors of {} queries but has {} rows.",
+                    lengths.len(),
+                    self.neighbors.nrows()
+                ));
+            }
+            if let Some(query) = lengths
+                .iter()
+                .position(|&length| length > self.neighbors.ncols())
+            {
+                return Err(anyhow!(
+                    "Query {} has {} neighbors but rows have at most {} entries.",
+                    query,
+                    lengths[query],
+                    self.neighbors.ncols()
+                ));
+            }
+        }
+        if let Some(query) = (0..self.neighbors.nrows())
+            .find(|&query| self.neighbors_of(query).iter().any(|&id| id == PADDING))
+        {
+            return Err(anyhow!("Query {} has an invalid neighbor id.", query));
+        }
+        Ok(())
+    }
+
+    /// Returns the concatenation of the valid neighbors of all queries, along with the number
+    /// of valid neighbors of each query.
+    fn to_flat(&self) -> (Vec<usize>, Vec<usize>) {
+        let lengths = (0..self.neighbors.nrows())
+            .map(|query| self.num_neighbors(query))
+            .collect::<Vec<_>>();
+        let ids = (0..self.neighbors.nrows())
+            .flat_map(|query| self.neighbors_of(query).to_vec())
+            .collect();
+        (ids, lengths)
+    }
+
+    /// Returns the concatenation of the distances from every query to its valid neighbors, in
+    /// the order of `to_flat`, if recorded.
+    fn to_flat_distances(&self) -> Option<Vec<f32>> {
+        self.distances.as_ref().map(|distances| {
+            (0..self.neighbors.nrows())
+                .flat_map(|query| {
+                    distances
+                        .slice(s![query, ..self.num_neighbors(query)])
+                        .to_vec()
+                })
+                .collect()
+        })
+    }
+
+    /// Appends the rows of `other` to the end of this set.
+    ///
+    /// If neither set is ragged, returns an error if the number of neighbors per row does not
+    /// match. Otherwise, the result is ragged. Distances are kept only if both sets record them.
+    pub(crate) fn append(&mut self, other: &GroundTruth) -> Result<()> {
+        if !self.is_ragged() && !other.is_ragged() {
+            self.neighbors.append(Axis(0), other.neighbors.view())?;
+            self.distances = match (self.distances.take(), other.distances.as_ref()) {
+                (Some(mut distances), Some(other)) => {
+                    distances.append(Axis(0), other.view())?;
+                    Some(distances)
+                }
+                _ => None,
+            };
+            return Ok(());
+        }
+        let (mut ids, mut lengths) = self.to_flat();
+        let (other_ids, other_lengths) = other.to_flat();
+        ids.extend(other_ids);
+        lengths.extend(other_lengths);
+        let distances = match (self.to_flat_distances(), other.to_flat_distances()) {
+            (Some(mut distances), Some(other)) => {
+                distances.extend(other);
+                Some(distances)
+            }
+            _ => None,
+        };
+        let gt = GroundTruth::from_flat(ids, lengths)?;
+        *self = match distances {
+            Some(distances) => gt.with_flat_distances(distances)?,
+            None => gt,
+        };
+        Ok(())
+    }
+
+    /// Rewrites the neighbor ids after the data points have been reordered or subsampled, where
+    /// `permutation[i]` is the old id of the point that now has id `i`. This is the convention of
+    /// the permutation returned by `PointSet::shuffle` and of the ids passed to
+    /// `PointSet::select`.
+    ///
+    /// Returns an error if a neighbor id does not appear in `permutation`, or if `permutation`
+    /// lists the same id more than once.
+    pub fn remap_ids(&self, permutation: &[usize]) -> Result<GroundTruth> {
+        let mut new_ids = HashMap::with_capacity(permutation.len());
+        for (new_id, &id) in permutation.iter().enumerate() {
+            if new_ids.insert(id, new_id).is_some() {
+                return Err(anyhow!(
+                    "Id {} appears more than once in the permutation.",
+                    id
+                ));
+            }
+        }
+        let (ids, lengths) = self.to_flat();
+        let ids =
+            ids.iter()
+                .map(|id| {
+                    new_ids.get(id).copied().ok_or_else(|| {
+                        anyhow!("Neighbor {} does not appear in the permutation.", id)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+        let mut gt = GroundTruth::from_flat(ids, lengths)?;
+        gt.distances = self.distances.clone();
+        Ok(gt)
+    }
+
+    /// Computes recall for every query given a retrieved set, where the `i`-th entry of the
+    /// returned vector is the recall of the `i`-th query.
+    ///
+    /// Recall is computed at `k`, the smaller of the size of the first retrieved set and the
+    /// number of neighbors per query. If the ground truth is ragged, a query with fewer than `k`
+    /// valid neighbors has recall equal to the fraction of its valid neighbors that are among
+    /// the first `k` retrieved points, and a query without valid neighbors has recall `1`.
     ///
     /// Returns an error if the number of queries does not match between `retrieved_set`
     /// and the exact neighbor set stored in this object.
-    pub fn mean_recall(&self, retrieved_set: &[Vec<usize>]) -> Result<f32> {
-        if retrieved_set.len() != self.0.nrows() {
+    pub fn recall(&self, retrieved_set: &[Vec<usize>]) -> Result<Vec<f32>> {
+        self.check_num_queries(retrieved_set.len())?;
+
+        if retrieved_set.is_empty() {
+            return Ok(vec![]);
+        }
+        self.recall_at_k(
+            retrieved_set,
+            min(retrieved_set[0].len(), self.neighbors.ncols()),
+        )
+    }
+
+    /// Same as `recall` but computes recall at the given `k` regardless of the size of the
+    /// retrieved sets.
+    ///
+    /// For every query, only the first `k` valid neighbors and the first `k` retrieved points are
+    /// considered, independently of one another. A query whose retrieved set holds fewer than `k`
+    /// points is penalized accordingly, and if `k` exceeds the number of valid neighbors of a
+    /// query, its recall is the fraction of all its valid neighbors that are retrieved.
+    ///
+    /// Returns an error if the number of queries does not match between `retrieved_set`
+    /// and the exact neighbor set stored in this object.
+    pub fn recall_at_k(&self, retrieved_set: &[Vec<usize>], k: usize) -> Result<Vec<f32>> {
+        self.check_num_queries(retrieved_set.len())?;
+        Ok(retrieved_set
+            .iter()
+            .enumerate()
+            .map(|(i, set)| self.query_recall(i, set, k))
+            .collect())
+    }
+
+    /// Computes recall like `recall`, in parallel if the `parallel` feature is enabled, and
+    /// reports progress along the way.
+    ///
+    /// Queries are processed in batches, and `progress` is called once a batch completes with
+    /// the total number of queries processed so far, so the last count is the number of queries.
+    /// `progress` must be `Sync` because it is called from whichever thread completes a batch,
+    /// but calls never overlap and counts are reported in increasing order.
+    pub fn recall_with_progress<F>(
+        &self,
+        retrieved_set: &[Vec<usize>],
+        progress: F,
+    ) -> Result<Vec<f32>>
+    where
+        F: Fn(usize) + Sync,
+    {
+        self.check_num_queries(retrieved_set.len())?;
+        if retrieved_set.is_empty() {
+            return Ok(vec![]);
+        }
+        let k = min(retrieved_set[0].len(), self.neighbors.ncols());
+
+        let completed = Mutex::new(0_usize);
+        let batches = retrieved_set.len().div_ceil(PROGRESS_BATCH_SIZE);
+        let recall = maybe_par_iter!(0..batches)
+            .map(|batch| {
+                let start = batch * PROGRESS_BATCH_SIZE;
+                let end = min(start + PROGRESS_BATCH_SIZE, retrieved_set.len());
+                let recall = (start..end)
+                    .map(|i| self.query_recall(i, &retrieved_set[i], k))
+                    .collect::<Vec<_>>();
+                let mut completed = completed.lock().unwrap();
+                *completed += end - start;
+                progress(*completed);
+                recall
+            })
+            .collect::<Vec<_>>();
+        Ok(recall.into_iter().flatten().collect())
+    }
+
+    /// Computes recall at `k` like `recall_at_k`, but first removes the data point `exclude[i]`
+    /// from both the exact neighbors and the retrieved points of the `i`-th query.
+    ///
+    /// This is meant for leave-one-out evaluation, where queries are drawn from the data points
+    /// themselves and every query trivially finds itself. Note that the exact neighbors then lose
+    /// the excluded point too, so ground truth should hold at least `k + 1` neighbors per query
+    /// for recall to be measured at a full `k`.
+    ///
+    /// Returns an error if `retrieved_set` or `exclude` does not have one entry per query.
+    pub fn recall_excluding(
+        &self,
+        retrieved_set: &[Vec<usize>],
+        exclude: &[usize],
+        k: usize,
+    ) -> Result<Vec<f32>> {
+        self.check_num_queries(retrieved_set.len())?;
+        if exclude.len() != self.neighbors.nrows() {
+            return Err(anyhow!(
+                "There are {} excluded points, but expected one per each of {} queries",
+                exclude.len(),
+                self.neighbors.nrows()
+            ));
+        }
+
+        Ok(zip(retrieved_set, exclude)
+            .enumerate()
+            .map(|(i, (set, &excluded))| self.query_recall_excluding(i, set, k, Some(excluded)))
+            .collect())
+    }
+
+    /// Returns an error if `num_queries`, the number of queries in a retrieved set, does not
+    /// match the number of queries in this object.
+    fn check_num_queries(&self, num_queries: usize) -> Result<()> {
+        if num_queries != self.neighbors.nrows() {
             return Err(anyhow!(
                 "Retrieved set has {} queries, but expected {} queries",
-                retrieved_set.len(),
-                self.0.nrows()
+                num_queries,
+                self.neighbors.nrows()
             ));
         }
+        Ok(())
+    }
 
-        if retrieved_set.is_empty() {
-            return Ok(1_f32);
+    /// Returns the recall at `k` of the `query`-th query given its `retrieved` points. See
+    /// `recall`.
+    fn query_recall(&self, query: usize, retrieved: &[usize], k: usize) -> f32 {
+        self.query_recall_excluding(query, retrieved, k, None)
+    }
+
+    /// Same as `query_recall`, but ignores the point `exclude` among the exact neighbors and the
+    /// retrieved points, if given.
+    fn query_recall_excluding(
+        &self,
+        query: usize,
+        retrieved: &[usize],
+        k: usize,
+        exclude: Option<usize>,
+    ) -> f32 {
+        let kept = |id: &&usize| Some(**id) != exclude;
+        let neighbors = self.neighbors_of(query);
+        let k_i = neighbors.iter().filter(kept).take(k).count();
+        if k_i == 0 {
+            return 1_f32;
+        }
+        let intersection_len =
+            RoaringTreemap::from_iter(neighbors.iter().filter(kept).map(|x| *x as u64).take(k_i))
+                .intersection_len(&RoaringTreemap::from_iter(
+                    retrieved.iter().filter(kept).map(|x| *x as u64).take(k),
+                )) as f64;
+        (intersection_len / k_i as f64) as f32
+    }
+
+    /// Measures how much this ground truth agrees with `other`, for example to validate an
+    /// approximate ground-truth routine against the exact one.
+    ///
+    /// Returns, for every query, the Jaccard overlap of the first `k` valid neighbors in the two
+    /// sets: the size of their intersection divided by the size of their union. Two empty sets
+    /// agree fully. Returns an error if the two sets have a different number of queries.
+    pub fn agreement(&self, other: &GroundTruth, k: usize) -> Result<Vec<f32>> {
+        if self.neighbors.nrows() != other.neighbors.nrows() {
+            return Err(anyhow!(
+                "Ground truth has {} queries, but the other has {} queries",
+                self.neighbors.nrows(),
+                other.neighbors.nrows()
+            ));
+        }
+        Ok((0..self.neighbors.nrows())
+            .map(|query| {
+                let ours = RoaringTreemap::from_iter(
+                    self.neighbors_of(query).iter().map(|x| *x as u64).take(k),
+                );
+                let theirs = RoaringTreemap::from_iter(
+                    other.neighbors_of(query).iter().map(|x| *x as u64).take(k),
+                );
+                let union_len = ours.union_len(&theirs);
+                if union_len == 0 {
+                    return 1_f32;
+                }
+                (ours.intersection_len(&theirs) as f64 / union_len as f64) as f32
+            })
+            .collect())
+    }
+
+    /// Computes recall given a retrieved set.
+    ///
+    /// Returns an error if the number of queries does not match between `retrieved_set`
+    /// and the exact neighbor set stored in this object.
+    pub fn mean_recall(&self, retrieved_set: &[Vec<usize>]) -> Result<f32> {
+        Ok(mean(&self.recall(retrieved_set)?))
+    }
+
+    /// Computes the mean recall at `k` given a retrieved set. See `recall_at_k`.
+    ///
+    /// Returns an error if the number of queries does not match between `retrieved_set`
+    /// and the exact neighbor set stored in this object.
+    pub fn mean_recall_at_k(&self, retrieved_set: &[Vec<usize>], k: usize) -> Result<f32> {
+        Ok(mean(&self.recall_at_k(retrieved_set, k)?))
+    }
+
+    /// Summarizes the per-query recall given a retrieved set, as computed by `recall`.
+    ///
+    /// Returns an error if the number of queries does not match between `retrieved_set`
+    /// and the exact neighbor set stored in this object.
+    pub fn recall_stats(&self, retrieved_set: &[Vec<usize>]) -> Result<RecallStats> {
+        Ok(RecallStats::new(&self.recall(retrieved_set)?))
+    }
+
+    /// Buckets the per-query recall given a retrieved set, as computed by `recall`, into `bins`
+    /// equal-width bins over `[0, 1]` and returns the number of queries in each bin. Bins are
+    /// closed on the left and open on the right, except the last, which also holds recall `1`.
+    ///
+    /// Returns an error if `bins` is `0`, or if the number of queries does not match between
+    /// `retrieved_set` and the exact neighbor set stored in this object.
+    pub fn recall_histogram(
+        &self,
+        retrieved_set: &[Vec<usize>],
+        bins: usize,
+    ) -> Result<Vec<usize>> {
+        if bins == 0 {
+            return Err(anyhow!("A recall histogram needs at least one bin."));
         }
-        let k = min(retrieved_set[0].len(), self.0.ncols());
+        let mut counts = vec![0; bins];
+        self.recall(retrieved_set)?.iter().for_each(|&recall| {
+            let bin = (recall as f64 * bins as f64) as usize;
+            counts[min(bin, bins - 1)] += 1;
+        });
+        Ok(counts)
+    }
+
+    /// Computes precision at `k` for every query given a retrieved set, where the `i`-th entry of
+    /// the returned vector is the precision of the `i`-th query.
+    ///
+    /// Precision is the fraction of the first `k` retrieved points that are among the valid
+    /// neighbors of a query, all of them and not just the first `k`. Every retrieved entry counts,
+    /// so a neighbor retrieved twice is a hit only once and its duplicate a miss. A query whose
+    /// retrieved set holds fewer than `k` points is judged only on the points it retrieved, i.e.,
+    /// hits are divided by `min(k, retrieved.len())`, and a query without retrieved points has
+    /// precision `0`.
+    ///
+    /// Returns an error if the number of queries does not match between `retrieved_set`
+    /// and the exact neighbor set stored in this object.
+    pub fn precision_at_k(&self, retrieved_set: &[Vec<usize>], k: usize) -> Result<Vec<f32>> {
+        self.check_num_queries(retrieved_set.len())?;
+        Ok(retrieved_set
+            .iter()
+            .enumerate()
+            .map(|(query, retrieved)| {
+                let retrieved = &retrieved[..min(k, retrieved.len())];
+                if retrieved.is_empty() {
+                    return 0_f32;
+                }
+                let mut neighbors =
+                    RoaringTreemap::from_iter(self.neighbors_of(query).iter().map(|x| *x as u64));
+                // Removing a neighbor once it is hit makes its duplicates count as misses.
+                let hits = retrieved
+                    .iter()
+                    .filter(|&&id| neighbors.remove(id as u64))
+                    .count();
+                (hits as f64 / retrieved.len() as f64) as f32
+            })
+            .collect())
+    }
+
+    /// Computes the mean precision at `k` given a retrieved set. See `precision_at_k`.
+    ///
+    /// Returns an error if the number of queries does not match between `retrieved_set`
+    /// and the exact neighbor set stored in this object.
+    pub fn mean_precision_at_k(&self, retrieved_set: &[Vec<usize>], k: usize) -> Result<f32> {
+        Ok(mean(&self.precision_at_k(retrieved_set, k)?))
+    }
+
+    /// Computes the mean reciprocal rank of the nearest neighbor given a retrieved set.
+    ///
+    /// For every query, finds the 1-based rank of the first valid neighbor of the query among its
+    /// retrieved points and takes its reciprocal, or `0` if the neighbor is not retrieved. A
+    /// query without valid neighbors has reciprocal rank `1`. Returns the mean over all queries.
+    ///
+    /// Returns an error if the number of queries does not match between `retrieved_set`
+    /// and the exact neighbor set stored in this object.
+    pub fn mean_reciprocal_rank(&self, retrieved_set: &[Vec<usize>]) -> Result<f32> {
+        self.check_num_queries(retrieved_set.len())?;
+        let reciprocal_ranks = retrieved_set
+            .iter()
+            .enumerate()
+            .map(
+                |(query, retrieved)| match self.neighbors_of(query).first() {
+                    None => 1_f32,
+                    Some(nearest) => retrieved
+                        .iter()
+                        .position(|id| id == nearest)
+                        .map_or(0_f32, |rank| 1_f32 / (rank + 1) as f32),
+                },
+            )
+            .collect::<Vec<_>>();
+        Ok(mean(&reciprocal_ranks))
+    }
 
-        let recall = retrieved_set
+    /// Computes the normalized discounted cumulative gain (nDCG) at `k` for every query given a
+    /// retrieved set, where the `i`-th entry of the returned vector is the nDCG of the `i`-th
+    /// query.
+    ///
+    /// Relevance is derived from the order of the exact neighbors: if a query has `n` valid
+    /// neighbors, the neighbor at 0-based position `r` has relevance `n - r`, so the nearest
+    /// neighbor has relevance `n`, and points that are not valid neighbors have relevance `0`.
+    /// The retrieved point at 0-based position `i` contributes `relevance / log2(i + 2)`, and a
+    /// point retrieved more than once counts only once. The sum over the first `k` retrieved
+    /// points is normalized by the same sum over the first `k` exact neighbors, so that
+    /// retrieving them in order yields `1`. A query without valid neighbors has nDCG `1`.
+    ///
+    /// Returns an error if the number of queries does not match between `retrieved_set`
+    /// and the exact neighbor set stored in this object.
+    pub fn ndcg_at_k(&self, retrieved_set: &[Vec<usize>], k: usize) -> Result<Vec<f32>> {
+        self.check_num_queries(retrieved_set.len())?;
+        let discount = |position: usize| ((position + 2) as f64).log2();
+        Ok(retrieved_set
             .iter()
             .enumerate()
-            .map(|(i, set)| {
-                let intersection_len =
-                    RoaringBitmap::from_iter(self.0.row(i).iter().map(|x| *x as u32).take(k))
-                        .intersection_len(&RoaringBitmap::from_iter(
-                            set.iter().map(|x| *x as u32).take(k),
-                        )) as f64;
-                intersection_len / k as f64
+            .map(|(query, retrieved)| {
+                let neighbors = self.neighbors_of(query);
+                let n = neighbors.len();
+                let ideal = (0..min(k, n))
+                    .map(|rank| (n - rank) as f64 / discount(rank))
+                    .sum::<f64>();
+                if ideal == 0_f64 {
+                    return 1_f32;
+                }
+                let mut relevance = neighbors
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, &id)| (id, (n - rank) as f64))
+                    .collect::<HashMap<_, _>>();
+                let dcg = retrieved
+                    .iter()
+                    .take(k)
+                    .enumerate()
+                    .filter_map(|(position, id)| {
+                        relevance.remove(id).map(|gain| gain / discount(position))
+                    })
+                    .sum::<f64>();
+                (dcg / ideal) as f32
             })
-            .sum::<f64>();
-        Ok(recall as f32 / retrieved_set.len() as f32)
+            .collect())
+    }
+}
+
+/// Returns the mean of the given per-query values, or `1` if there are none.
+fn mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 1_f32;
+    }
+    let total = values.iter().map(|&v| v as f64).sum::<f64>();
+    total as f32 / values.len() as f32
+}
+
+/// Summary statistics of per-query recall.
+///
+/// If there are no queries, every statistic is `1` except `stddev`, which is `0`, matching
+/// `GroundTruth::mean_recall` for an empty set.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct RecallStats {
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+    /// Population standard deviation.
+    pub stddev: f32,
+    /// Middle value, or the mean of the two middle values if the number of queries is even.
+    pub median: f32,
+}
+
+impl RecallStats {
+    fn new(recall: &[f32]) -> RecallStats {
+        if recall.is_empty() {
+            return RecallStats {
+                mean: 1_f32,
+                min: 1_f32,
+                max: 1_f32,
+                stddev: 0_f32,
+                median: 1_f32,
+            };
+        }
+        let mut sorted = recall.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let mean = mean(recall);
+        let variance = recall
+            .iter()
+            .map(|&r| (r as f64 - mean as f64).powi(2))
+            .sum::<f64>()
+            / recall.len() as f64;
+        let middle = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) / 2_f32
+        } else {
+            sorted[middle]
+        };
+        RecallStats {
+            mean,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            stddev: variance.sqrt() as f32,
+            median,
+        }
+    }
+}
+
+/// Computes recall one query at a time, for example when results arrive over the network,
+/// without buffering the retrieved sets of all queries.
+#[derive(Debug, Clone)]
+pub struct RecallAccumulator<'a> {
+    ground_truth: &'a GroundTruth,
+    recall: Vec<Option<f32>>,
+}
+
+impl<'a> RecallAccumulator<'a> {
+    /// Creates an accumulator that evaluates retrieved sets against `ground_truth`.
+    pub fn new(ground_truth: &'a GroundTruth) -> RecallAccumulator<'a> {
+        RecallAccumulator {
+            ground_truth,
+            recall: vec![None; ground_truth.get_neighbors().nrows()],
+        }
+    }
+
+    /// Records the recall of the `query_id`-th query given its `retrieved` points.
+    ///
+    /// Recall is computed as in `GroundTruth::recall`, except that `k` is the smaller of the size
+    /// of `retrieved` and the number of neighbors per query, so that it is determined separately
+    /// for every query.
+    ///
+    /// Returns an error if `query_id` is out of range or has already been observed.
+    pub fn observe(&mut self, query_id: usize, retrieved: &[usize]) -> Result<()> {
+        let Some(entry) = self.recall.get_mut(query_id) else {
+            return Err(anyhow!(
+                "Query id {} is out of range; there are {} queries.",
+                query_id,
+                self.ground_truth.get_neighbors().nrows()
+            ));
+        };
+        if entry.is_some() {
+            return Err(anyhow!("Query {} has already been observed.", query_id));
+        }
+        let k = min(retrieved.len(), self.ground_truth.get_neighbors().ncols());
+        *entry = Some(self.ground_truth.query_recall(query_id, retrieved, k));
+        Ok(())
+    }
+
+    /// Returns the number of queries observed so far.
+    pub fn num_observed(&self) -> usize {
+        self.recall.iter().filter(|r| r.is_some()).count()
+    }
+
+    /// Returns the mean recall over the queries observed so far, or `1` if no query has been
+    /// observed, matching `GroundTruth::mean_recall` for an empty set.
+    pub fn mean(&self) -> f32 {
+        let observed = self.recall.iter().flatten().collect::<Vec<_>>();
+        if observed.is_empty() {
+            return 1_f32;
+        }
+        let total = observed.iter().map(|&&r| r as f64).sum::<f64>();
+        total as f32 / observed.len() as f32
+    }
+}
+
+/// Builds `GroundTruth` one query at a time, for example when computing ground truth in a
+/// streaming fashion, without first materializing a full matrix of neighbors.
+#[derive(Debug, Clone)]
+pub struct GroundTruthBuilder {
+    num_neighbors: usize,
+    num_queries: usize,
+    ids: Vec<usize>,
+}
+
+impl GroundTruthBuilder {
+    /// Creates a builder for ground truth with `num_neighbors` neighbors per query.
+    pub fn new(num_neighbors: usize) -> GroundTruthBuilder {
+        GroundTruthBuilder::with_capacity(num_neighbors, 0)
+    }
+
+    /// Same as `new` but reserves space for `num_queries` queries.
+    pub fn with_capacity(num_neighbors: usize, num_queries: usize) -> GroundTruthBuilder {
+        GroundTruthBuilder {
+            num_neighbors,
+            num_queries: 0,
+            ids: Vec::with_capacity(num_neighbors * num_queries),
+        }
+    }
+
+    /// Adds the neighbors of the next query.
+    ///
+    /// Returns an error, leaving the builder unchanged, if `row` does not have exactly as many
+    /// neighbors as given on construction.
+    pub fn push_row(&mut self, row: &[usize]) -> Result<()> {
+        if row.len() != self.num_neighbors {
+            return Err(anyhow!(
+                "Row has {} neighbors, but expected {} neighbors.",
+                row.len(),
+                self.num_neighbors
+            ));
+        }
+        self.ids.extend_from_slice(row);
+        self.num_queries += 1;
+        Ok(())
+    }
+
+    /// Returns the number of queries added so far.
+    pub fn num_queries(&self) -> usize {
+        self.num_queries
+    }
+
+    /// Assembles the rows added so far into `GroundTruth`.
+    pub fn build(self) -> GroundTruth {
+        GroundTruth::new(
+            Array2::from_shape_vec((self.num_queries, self.num_neighbors), self.ids)
+                .expect("Every row has the same number of neighbors."),
+        )
     }
 }
 
 impl Hdf5Serialization for GroundTruth {
     type Object = GroundTruth;
 
+    /// Stores the neighbors as a matrix or, if the ground truth is ragged, as the concatenation
+    /// of the valid neighbors of all queries along with the offset of each query, as in CSR.
+    /// Distances, if recorded, are stored in a separate dataset with the same layout as the
+    /// neighbors.
     fn add_to(&self, group: &mut Group) -> Result<()> {
+        if !self.is_ragged() {
+            let dataset = group
+                .new_dataset::<usize>()
+                .shape(self.neighbors.shape())
+                .create(Self::label().as_str())?;
+            dataset.write(self.neighbors.view())?;
+            if let Some(distances) = self.distances.as_ref() {
+                group
+                    .new_dataset_builder()
+                    .with_data(distances)
+                    .create(DISTANCES)?;
+            }
+            return Ok(());
+        }
+
+        let (ids, lengths) = self.to_flat();
+        let offsets = std::iter::once(0)
+            .chain(lengths.iter().scan(0, |offset, &length| {
+                *offset += length;
+                Some(*offset)
+            }))
+            .collect::<Vec<_>>();
+
         let dataset = group
             .new_dataset::<usize>()
-            .shape(self.0.shape())
+            .shape(ids.len())
             .create(Self::label().as_str())?;
-        dataset.write(self.0.view())?;
+        dataset.write(ids.as_slice())?;
+        let dataset = group
+            .new_dataset::<usize>()
+            .shape(offsets.len())
+            .create(OFFSETS)?;
+        dataset.write(offsets.as_slice())?;
+        if let Some(distances) = self.to_flat_distances() {
+            let dataset = group
+                .new_dataset::<f32>()
+                .shape(distances.len())
+                .create(DISTANCES)?;
+            dataset.write(distances.as_slice())?;
+        }
         Ok(())
     }
 
     fn read_from(group: &Group) -> Result<Self::Object> {
         let dataset = group.dataset(Self::label().as_str())?;
 
+        if dataset.ndim() == 1 {
+            let ids = dataset.read_raw::<usize>()?;
+            let offsets = group.dataset(OFFSETS)?.read_raw::<usize>()?;
+            if offsets.first() != Some(&0)
+                || offsets.last() != Some(&ids.len())
+                || offsets.windows(2).any(|w| w[0] > w[1])
+            {
+                return Err(anyhow!(
+                    "Offsets of ragged ground truth do not match the number of neighbors ({}).",
+                    ids.len()
+                ));
+            }
+            let lengths = offsets.windows(2).map(|w| w[1] - w[0]).collect();
+            let gt = GroundTruth::from_flat(ids, lengths)?;
+            if let Ok(dataset) = group.dataset(DISTANCES) {
+                return gt.with_flat_distances(dataset.read_raw::<f32>()?);
+            }
+            return Ok(gt);
+        }
+
         let vectors = dataset.read_raw::<usize>()?;
         let num_dimensions: usize = dataset.shape()[1];
         let vector_count = vectors.len() / num_dimensions;
         let vectors = Array2::from_shape_vec((vector_count, num_dimensions), vectors)?;
 
-        Ok(GroundTruth(vectors))
+        if let Ok(dataset) = group.dataset(DISTANCES) {
+            return GroundTruth::with_distances(vectors, dataset.read_2d::<f32>()?);
+        }
+        Ok(GroundTruth::new(vectors))
     }
 
     fn label() -> String {
@@ -83,19 +969,44 @@ impl Hdf5Serialization for GroundTruth {
     }
 }
 
+impl PartialEq for GroundTruth {
+    fn eq(&self, other: &Self) -> bool {
+        let distances_eq = match (self.distances.as_ref(), other.distances.as_ref()) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                a.shape() == b.shape() && zip(a, b).all(|(x, y)| x.to_bits() == y.to_bits())
+            }
+            _ => false,
+        };
+        self.neighbors == other.neighbors && self.lengths == other.lengths && distances_eq
+    }
+}
+
+impl Eq for GroundTruth {}
+
 impl Display for GroundTruth {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Shape [{}, {}]", self.0.shape()[0], self.0.shape()[1])
+        write!(
+            f,
+            "Shape [{}, {}]",
+            self.neighbors.shape()[0],
+            self.neighbors.shape()[1]
+        )?;
+        if self.is_ragged() {
+            write!(f, " (ragged)")?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::types::ground_truth::GroundTruth;
+    use crate::types::ground_truth::{GroundTruth, GroundTruthBuilder, RecallAccumulator, OFFSETS};
     use crate::Hdf5Serialization;
     use approx_eq::assert_approx_eq;
     use hdf5::File;
-    use ndarray::Array2;
+    use ndarray::{arr2, s, Array2};
+    use std::sync::Mutex;
     use tempdir::TempDir;
 
     #[test]
@@ -110,6 +1021,488 @@ mod tests {
 
         let recall = gt.mean_recall(&[vec![1_usize, 2], vec![5, 6], vec![1, 8]]);
         assert_approx_eq!(recall.unwrap().into(), 0.666, 0.01);
+
+        assert!(gt.recall(&[vec![1_usize]]).is_err());
+        let recall = gt
+            .recall(&[vec![1_usize, 2], vec![5, 6], vec![1, 8]])
+            .unwrap();
+        assert_eq!(recall, vec![1_f32, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_recall_at_k() {
+        let gt = GroundTruth::new(
+            Array2::from_shape_vec((3, 3), vec![1_usize, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap(),
+        );
+        let retrieved = vec![vec![1, 2, 3, 4], vec![5], vec![9, 0, 0, 7, 8]];
+
+        assert_eq!(
+            gt.recall_at_k(&retrieved, 2).unwrap(),
+            vec![1_f32, 0.5, 0_f32]
+        );
+        assert_eq!(
+            gt.recall_at_k(&retrieved, 5).unwrap(),
+            vec![1_f32, 1_f32 / 3_f32, 1_f32]
+        );
+        assert_approx_eq!(
+            gt.mean_recall_at_k(&retrieved, 5).unwrap() as f64,
+            7_f64 / 9_f64
+        );
+        assert_eq!(gt.recall_at_k(&retrieved, 0).unwrap(), vec![1_f32; 3]);
+
+        let ragged = GroundTruth::new_ragged(vec![vec![1, 2], vec![], vec![3]]);
+        assert_eq!(
+            ragged
+                .recall_at_k(&[vec![2], vec![], vec![3, 1]], 4)
+                .unwrap(),
+            vec![0.5, 1_f32, 1_f32]
+        );
+
+        assert!(gt.recall_at_k(&retrieved[..2], 2).is_err());
+        assert!(gt.mean_recall_at_k(&[], 2).is_err());
+    }
+
+    #[test]
+    fn test_precision_at_k() {
+        let gt =
+            GroundTruth::new(Array2::from_shape_vec((3, 2), vec![1_usize, 2, 3, 4, 5, 6]).unwrap());
+        let retrieved = vec![vec![1, 2, 7, 8], vec![0, 4], vec![]];
+
+        assert_eq!(
+            gt.precision_at_k(&retrieved, 4).unwrap(),
+            vec![0.5, 0.5, 0_f32]
+        );
+        assert_eq!(
+            gt.recall_at_k(&retrieved, 4).unwrap(),
+            vec![1_f32, 0.5, 0_f32]
+        );
+        assert_eq!(
+            gt.precision_at_k(&retrieved, 2).unwrap(),
+            vec![1_f32, 0.5, 0_f32]
+        );
+        assert_approx_eq!(
+            gt.mean_precision_at_k(&retrieved, 4).unwrap() as f64,
+            1_f64 / 3_f64
+        );
+        assert_eq!(gt.precision_at_k(&retrieved, 0).unwrap(), vec![0_f32; 3]);
+
+        // Duplicates are not merged: a neighbor retrieved twice is a hit only once.
+        let retrieved = vec![vec![1, 1], vec![3, 3, 4, 4], vec![5, 9, 5]];
+        assert_eq!(
+            gt.precision_at_k(&retrieved, 2).unwrap(),
+            vec![0.5, 0.5, 0.5]
+        );
+        assert_eq!(
+            gt.precision_at_k(&retrieved, 4).unwrap(),
+            vec![0.5, 0.5, 1_f32 / 3_f32]
+        );
+
+        // Neighbors beyond the first `k` still count.
+        let retrieved = vec![vec![2], vec![4], vec![6]];
+        assert_eq!(gt.precision_at_k(&retrieved, 1).unwrap(), vec![1_f32; 3]);
+        assert_eq!(gt.recall_at_k(&retrieved, 1).unwrap(), vec![0_f32; 3]);
+
+        assert!(gt.precision_at_k(&retrieved[..2], 1).is_err());
+        assert!(gt.mean_precision_at_k(&[], 1).is_err());
+    }
+
+    #[test]
+    fn test_mean_reciprocal_rank() {
+        let gt =
+            GroundTruth::new(Array2::from_shape_vec((3, 2), vec![1_usize, 2, 3, 4, 5, 6]).unwrap());
+        assert_eq!(
+            gt.mean_reciprocal_rank(&[vec![1, 2], vec![3], vec![5, 0]])
+                .unwrap(),
+            1_f32
+        );
+        assert_approx_eq!(
+            gt.mean_reciprocal_rank(&[vec![1, 2], vec![4, 0, 3], vec![6, 7, 8]])
+                .unwrap() as f64,
+            (1_f64 + 1_f64 / 3_f64) / 3_f64
+        );
+        assert_eq!(
+            gt.mean_reciprocal_rank(&[vec![2], vec![4], vec![]])
+                .unwrap(),
+            0_f32
+        );
+
+        let ragged = GroundTruth::new_ragged(vec![vec![1, 2], vec![]]);
+        assert_eq!(
+            ragged.mean_reciprocal_rank(&[vec![0, 1], vec![]]).unwrap(),
+            0.75
+        );
+
+        assert!(gt.mean_reciprocal_rank(&[vec![1]]).is_err());
+    }
+
+    #[test]
+    fn test_ndcg_at_k() {
+        let gt =
+            GroundTruth::new(Array2::from_shape_vec((2, 3), vec![1_usize, 2, 3, 4, 5, 6]).unwrap());
+
+        let ordered = gt.ndcg_at_k(&[vec![1, 2, 3], vec![4, 5, 6]], 3).unwrap();
+        assert_eq!(ordered, vec![1_f32, 1_f32]);
+
+        // Relevance is 3, 2, 1 for the first, second and third neighbor, respectively.
+        let ideal = 3_f64 + 2_f64 / 3_f64.log2() + 1_f64 / 2_f64;
+        let reversed = 1_f64 + 2_f64 / 3_f64.log2() + 3_f64 / 2_f64;
+        let ndcg = gt.ndcg_at_k(&[vec![3, 2, 1], vec![6, 5, 4]], 3).unwrap();
+        assert_approx_eq!(ndcg[0] as f64, reversed / ideal);
+        assert_approx_eq!(ndcg[1] as f64, reversed / ideal);
+        assert!(ndcg[0] < 1_f32);
+
+        let ndcg = gt.ndcg_at_k(&[vec![1, 1, 0], vec![7, 8, 9]], 1).unwrap();
+        assert_eq!(ndcg, vec![1_f32, 0_f32]);
+        let ndcg = gt.ndcg_at_k(&[vec![1, 1, 0], vec![]], 2).unwrap();
+        assert_approx_eq!(ndcg[0] as f64, 3_f64 / (3_f64 + 2_f64 / 3_f64.log2()));
+        assert_eq!(ndcg[1], 0_f32);
+
+        assert_eq!(
+            GroundTruth::new_ragged(vec![vec![], vec![1]])
+                .ndcg_at_k(&[vec![1], vec![1]], 5)
+                .unwrap(),
+            vec![1_f32, 1_f32]
+        );
+        assert!(gt.ndcg_at_k(&[vec![1]], 3).is_err());
+    }
+
+    #[test]
+    fn test_recall_stats() {
+        let gt =
+            GroundTruth::new(Array2::from_shape_vec((3, 2), vec![1_usize, 2, 3, 4, 5, 6]).unwrap());
+        // Recall is 1, 0 and 0.5.
+        let stats = gt
+            .recall_stats(&[vec![1, 2], vec![7, 8], vec![6, 9]])
+            .unwrap();
+        assert_eq!(stats.min, 0_f32);
+        assert_eq!(stats.max, 1_f32);
+        assert_eq!(stats.median, 0.5);
+        assert_approx_eq!(stats.mean as f64, 0.5);
+        assert_approx_eq!(stats.stddev as f64, (1_f64 / 6_f64).sqrt());
+
+        let gt = GroundTruth::new(Array2::from_shape_vec((2, 1), vec![1_usize, 2]).unwrap());
+        let stats = gt.recall_stats(&[vec![1], vec![3]]).unwrap();
+        assert_eq!(stats.median, 0.5);
+        assert_eq!(stats.stddev, 0.5);
+
+        let stats = GroundTruth::default().recall_stats(&[]).unwrap();
+        assert_eq!((stats.mean, stats.stddev), (1_f32, 0_f32));
+        assert!(gt.recall_stats(&[vec![1]]).is_err());
+    }
+
+    #[test]
+    fn test_recall_histogram() {
+        let gt = GroundTruth::new(Array2::from_shape_fn((10, 4), |(i, j)| i * 4 + j));
+        // The `i`-th query retrieves `hits[i]` of its four neighbors.
+        let hits = [0, 0, 1, 1, 1, 2, 3, 4, 4, 4];
+        let retrieved = hits
+            .iter()
+            .enumerate()
+            .map(|(i, &hits)| {
+                (0..4)
+                    .map(|j| if j < hits { i * 4 + j } else { 100 + j })
+                    .collect()
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        assert_eq!(
+            gt.recall_histogram(&retrieved, 4).unwrap(),
+            vec![2, 3, 1, 4]
+        );
+        assert_eq!(gt.recall_histogram(&retrieved, 1).unwrap(), vec![10]);
+        assert_eq!(gt.recall_histogram(&retrieved, 2).unwrap(), vec![5, 5]);
+        assert!(gt.recall_histogram(&retrieved, 0).is_err());
+        assert!(gt.recall_histogram(&retrieved[..3], 4).is_err());
+    }
+
+    #[test]
+    fn test_recall_large_ids() {
+        // `705_032_704` equals `5_000_000_000` truncated to 32 bits.
+        let gt = GroundTruth::new(
+            Array2::from_shape_vec((2, 2), vec![5_000_000_000_usize, 1, 705_032_704, 2]).unwrap(),
+        );
+        let retrieved = vec![vec![705_032_704, 1], vec![5_000_000_000, 705_032_704]];
+        assert_eq!(gt.recall(&retrieved).unwrap(), vec![0.5, 0.5]);
+
+        let other = GroundTruth::new(
+            Array2::from_shape_vec((2, 2), vec![705_032_704_usize, 1, 5_000_000_000, 2]).unwrap(),
+        );
+        assert_eq!(
+            gt.agreement(&other, 2).unwrap(),
+            vec![1_f32 / 3_f32, 1_f32 / 3_f32]
+        );
+    }
+
+    #[test]
+    fn test_recall_excluding() {
+        // Queries are the data points 0, 1 and 2, each of which is its own nearest neighbor.
+        let gt = GroundTruth::new(
+            Array2::from_shape_vec((3, 3), vec![0_usize, 5, 6, 1, 7, 8, 2, 9, 5]).unwrap(),
+        );
+        let retrieved = vec![vec![0_usize, 5, 6], vec![1, 8, 4], vec![9, 2, 3]];
+
+        assert_eq!(
+            gt.recall_at_k(&retrieved, 1).unwrap(),
+            vec![1_f32, 1_f32, 0_f32]
+        );
+        let recall = gt.recall_excluding(&retrieved, &[0, 1, 2], 1).unwrap();
+        assert_eq!(recall, vec![1_f32, 0_f32, 1_f32]);
+        let recall = gt.recall_excluding(&retrieved, &[0, 1, 2], 2).unwrap();
+        assert_eq!(recall, vec![1_f32, 0.5, 0.5]);
+
+        // Plain recall counts every query finding itself.
+        assert_eq!(
+            gt.recall(&retrieved).unwrap(),
+            vec![1_f32, 2_f32 / 3_f32, 2_f32 / 3_f32]
+        );
+        let recall = gt.recall_excluding(&retrieved, &[0, 1, 2], 3).unwrap();
+        assert_eq!(recall, vec![1_f32, 0.5, 0.5]);
+
+        // Excluding a point that is not among the neighbors changes nothing.
+        assert_eq!(
+            gt.recall_excluding(&retrieved, &[4, 4, 4], 3).unwrap(),
+            gt.recall_at_k(&retrieved, 3).unwrap()
+        );
+
+        assert!(gt.recall_excluding(&retrieved, &[0, 1], 1).is_err());
+        assert!(gt.recall_excluding(&retrieved[..2], &[0, 1], 1).is_err());
+    }
+
+    #[test]
+    fn test_recall_with_progress() {
+        let num_queries = 2500;
+        let gt = GroundTruth::new(Array2::from_shape_fn((num_queries, 4), |(i, j)| i + j));
+        let retrieved = (0..num_queries)
+            .map(|i| vec![i, i + 1, i % 7])
+            .collect::<Vec<_>>();
+
+        let reported = Mutex::new(vec![]);
+        let recall = gt
+            .recall_with_progress(&retrieved, |count| reported.lock().unwrap().push(count))
+            .unwrap();
+        assert_eq!(recall, gt.recall(&retrieved).unwrap());
+
+        let reported = reported.into_inner().unwrap();
+        assert_eq!(reported.len(), 3);
+        assert_eq!(reported.last(), Some(&num_queries));
+
+        assert!(gt.recall_with_progress(&retrieved[1..], |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_agreement() {
+        let gt = GroundTruth::new(
+            Array2::from_shape_vec((3, 3), vec![1_usize, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap(),
+        );
+        let other = GroundTruth::new_ragged(vec![vec![1, 2, 3], vec![5, 4, 0], vec![]]);
+
+        assert_eq!(gt.agreement(&other, 3).unwrap(), vec![1_f32, 0.5, 0_f32]);
+        assert_eq!(gt.agreement(&other, 1).unwrap(), vec![1_f32, 0_f32, 0_f32]);
+        assert_eq!(other.agreement(&other, 2).unwrap(), vec![1_f32; 3]);
+
+        let fewer = GroundTruth::new(Array2::zeros((2, 3)));
+        assert!(gt.agreement(&fewer, 3).is_err());
+    }
+
+    #[test]
+    fn test_recall_accumulator() {
+        let gt = GroundTruth::new(
+            Array2::from_shape_vec((3, 3), vec![1_usize, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap(),
+        );
+        let retrieved = [vec![1_usize, 2], vec![5, 6], vec![1, 8]];
+
+        let mut accumulator = RecallAccumulator::new(&gt);
+        assert_eq!(accumulator.mean(), 1_f32);
+        accumulator.observe(2, &retrieved[2]).unwrap();
+        accumulator.observe(0, &retrieved[0]).unwrap();
+        assert_eq!(accumulator.num_observed(), 2);
+        assert_approx_eq!(accumulator.mean().into(), 0.75, 0.01);
+
+        assert!(accumulator.observe(0, &retrieved[0]).is_err());
+        assert!(accumulator.observe(3, &retrieved[0]).is_err());
+        assert_eq!(accumulator.num_observed(), 2);
+
+        accumulator.observe(1, &retrieved[1]).unwrap();
+        assert_eq!(accumulator.mean(), gt.mean_recall(&retrieved).unwrap());
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut builder = GroundTruthBuilder::new(2);
+        builder.push_row(&[1, 2]).unwrap();
+        builder.push_row(&[3, 4]).unwrap();
+        assert!(builder.push_row(&[5]).is_err());
+        builder.push_row(&[5, 6]).unwrap();
+        assert_eq!(builder.num_queries(), 3);
+
+        let gt = builder.build();
+        assert_eq!(
+            gt,
+            GroundTruth::new(Array2::from_shape_vec((3, 2), vec![1, 2, 3, 4, 5, 6]).unwrap())
+        );
+        assert_eq!(
+            gt,
+            GroundTruth::from_rows(vec![vec![1, 2], vec![3, 4], vec![5, 6]]).unwrap()
+        );
+
+        assert!(GroundTruth::from_rows(vec![vec![1, 2], vec![3]]).is_err());
+        assert_eq!(
+            GroundTruth::from_rows(vec![]).unwrap(),
+            GroundTruth::default()
+        );
+        assert_eq!(GroundTruthBuilder::new(0).build(), GroundTruth::default());
+    }
+
+    #[test]
+    fn test_ragged() {
+        let gt = GroundTruth::new_ragged(vec![vec![1, 2, 3], vec![], vec![4]]);
+        assert!(gt.is_ragged());
+        assert_eq!(gt.get_neighbors().shape(), &[3, 3]);
+        assert_eq!(gt.num_neighbors(0), 3);
+        assert_eq!(gt.neighbors_of(1).to_vec(), Vec::<usize>::new());
+        assert_eq!(gt.neighbors_of(2).to_vec(), vec![4]);
+        assert_eq!(gt.to_string(), "Shape [3, 3] (ragged)");
+
+        let recall = gt
+            .recall(&[vec![1, 5, 6], vec![7, 8, 9], vec![0, 4, 2]])
+            .unwrap();
+        assert_eq!(recall, vec![1_f32 / 3_f32, 1_f32, 1_f32]);
+        let recall = gt.recall(&[vec![5], vec![], vec![4]]).unwrap();
+        assert_eq!(recall, vec![0_f32, 1_f32, 1_f32]);
+
+        let uniform = GroundTruth::new_ragged(vec![vec![1, 2], vec![3, 4]]);
+        assert!(!uniform.is_ragged());
+        assert_eq!(
+            uniform,
+            GroundTruth::new(Array2::from_shape_vec((2, 2), vec![1, 2, 3, 4]).unwrap())
+        );
+
+        let mut appended = uniform.clone();
+        appended.append(&gt).unwrap();
+        assert!(appended.is_ragged());
+        assert_eq!(appended.get_neighbors().nrows(), 5);
+        assert_eq!(appended.neighbors_of(1).to_vec(), vec![3, 4]);
+        assert_eq!(appended.neighbors_of(2).to_vec(), vec![1, 2, 3]);
+
+        let remapped = gt.remap_ids(&[4, 3, 2, 1, 0]).unwrap();
+        assert_eq!(
+            remapped,
+            GroundTruth::new_ragged(vec![vec![3, 2, 1], vec![], vec![0]])
+        );
+
+        assert!(GroundTruth::from_flat(vec![1, 2, 3, 4, 5], vec![3]).is_err());
+        assert!(GroundTruth::from_flat(vec![1, 2, 3], vec![2, 2]).is_err());
+    }
+
+    #[test]
+    fn test_remap_ids() {
+        let gt = GroundTruth::new(Array2::from_shape_vec((2, 2), vec![0_usize, 2, 3, 1]).unwrap());
+
+        let remapped = gt.remap_ids(&[2, 0, 3, 1]).unwrap();
+        assert_eq!(
+            remapped.get_neighbors().iter().copied().collect::<Vec<_>>(),
+            vec![1, 0, 2, 3]
+        );
+
+        assert!(gt.remap_ids(&[2, 0, 3]).is_err());
+        assert!(gt.remap_ids(&[2, 0, 3, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_truncate() {
+        let neighbors = Array2::from_shape_fn((4, 5), |(i, j)| i * 5 + j);
+        let distances = Array2::from_shape_fn((4, 5), |(i, j)| (i + j) as f32);
+        let original = GroundTruth::with_distances(neighbors, distances.clone()).unwrap();
+        let retrieved = vec![vec![0, 9, 2], vec![5, 6, 7], vec![13, 12, 0], vec![1, 2, 3]];
+
+        let mut gt = original.clone();
+        gt.truncate(3);
+        assert_eq!(gt.get_neighbors().shape(), &[4, 3]);
+        assert_eq!(gt.get_distances().unwrap(), distances.slice(s![.., ..3]));
+        assert_eq!(
+            gt.recall(&retrieved).unwrap(),
+            original.recall_at_k(&retrieved, 3).unwrap()
+        );
+        gt.truncate(10);
+        assert_eq!(gt.get_neighbors().shape(), &[4, 3]);
+
+        let mut ragged = GroundTruth::new_ragged(vec![vec![1, 2, 3], vec![], vec![4, 5]]);
+        ragged.truncate(2);
+        assert_eq!(ragged.get_neighbors().ncols(), 2);
+        assert!(ragged.is_ragged());
+        assert_eq!(ragged.neighbors_of(0).to_vec(), vec![1, 2]);
+        assert_eq!(ragged.neighbors_of(1).to_vec(), Vec::<usize>::new());
+        assert_eq!(ragged.neighbors_of(2).to_vec(), vec![4, 5]);
+
+        let mut ragged = GroundTruth::new_ragged(vec![vec![1, 2, 3], vec![4, 5]]);
+        ragged.truncate(2);
+        assert_eq!(
+            ragged,
+            GroundTruth::new(Array2::from_shape_vec((2, 2), vec![1, 2, 4, 5]).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_distances() {
+        let neighbors = Array2::from_shape_vec((2, 2), vec![0_usize, 2, 3, 1]).unwrap();
+        let distances = arr2(&[[0.5_f32, 1.5], [0.25, 2.0]]);
+        assert!(GroundTruth::new(neighbors.clone())
+            .get_distances()
+            .is_none());
+        assert!(GroundTruth::with_distances(neighbors.clone(), arr2(&[[0.5_f32, 1.5]])).is_err());
+
+        let gt = GroundTruth::with_distances(neighbors.clone(), distances.clone()).unwrap();
+        assert_eq!(gt.get_distances().unwrap(), distances);
+        assert_ne!(gt, GroundTruth::new(neighbors.clone()));
+        let nan =
+            GroundTruth::with_distances(neighbors.clone(), arr2(&[[f32::NAN; 2]; 2])).unwrap();
+        assert_eq!(nan, nan.clone());
+        assert_ne!(nan, gt);
+
+        let remapped = gt.remap_ids(&[2, 0, 3, 1]).unwrap();
+        assert_eq!(remapped.get_distances().unwrap(), distances);
+
+        let mut appended = gt.clone();
+        appended.append(&gt).unwrap();
+        assert_eq!(appended.get_distances().unwrap().nrows(), 4);
+        appended.append(&GroundTruth::new(neighbors)).unwrap();
+        assert!(appended.get_distances().is_none());
+
+        let ragged = GroundTruth::new_ragged_with_distances(
+            vec![vec![1, 2], vec![]],
+            vec![vec![0.5, 1.5], vec![]],
+        )
+        .unwrap();
+        assert!(ragged.is_ragged());
+        let ragged_distances = ragged.get_distances().unwrap();
+        assert_eq!(ragged_distances.row(0).to_vec(), vec![0.5, 1.5]);
+        assert!(ragged_distances.row(1).iter().all(|x| x.is_nan()));
+        assert!(GroundTruth::new_ragged_with_distances(vec![vec![1, 2]], vec![vec![0.5]]).is_err());
+
+        let mut appended = ragged.clone();
+        appended.append(&gt).unwrap();
+        assert_eq!(
+            appended,
+            GroundTruth::new_ragged_with_distances(
+                vec![vec![1, 2], vec![], vec![0, 2], vec![3, 1]],
+                vec![vec![0.5, 1.5], vec![], vec![0.5, 1.5], vec![0.25, 2.0]],
+            )
+            .unwrap()
+        );
+        let mut truncated = appended.clone();
+        truncated.truncate(1);
+        assert_eq!(
+            truncated.get_distances().unwrap().column(0).to_vec()[2..],
+            [0.5, 0.25]
+        );
+
+        let json = r#"{"neighbors":{"v":1,"dim":[1,1],"data":[7]},"lengths":null}"#;
+        let gt: GroundTruth = serde_json::from_str(json).unwrap();
+        assert!(gt.get_distances().is_none());
+        let json = r#"{"neighbors":{"v":1,"dim":[1,1],"data":[7]}}"#;
+        let gt: GroundTruth = serde_json::from_str(json).unwrap();
+        assert!(!gt.is_ragged());
+        assert!(gt.get_distances().is_none());
     }
 
     #[test]
@@ -136,4 +1529,76 @@ mod tests {
         let gt_copy = GroundTruth::read_from(&group).unwrap();
         assert_eq!(&gt, &gt_copy);
     }
+
+    #[test]
+    fn test_hdf5_ragged() {
+        let gt = GroundTruth::new_ragged(vec![vec![1, 2, 3], vec![], vec![4]]);
+
+        let dir = TempDir::new("gt_test_hdf5_ragged").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        let hdf5 = File::create(path).unwrap();
+
+        let mut group = hdf5.group("/").unwrap();
+        assert!(gt.add_to(&mut group).is_ok());
+
+        let gt_copy = GroundTruth::read_from(&group).unwrap();
+        assert_eq!(&gt, &gt_copy);
+
+        let gt = GroundTruth::new_ragged_with_distances(
+            vec![vec![1, 2], vec![], vec![4]],
+            vec![vec![0.5, 1.5], vec![], vec![2.5]],
+        )
+        .unwrap();
+        let mut distances = hdf5
+            .group("/")
+            .unwrap()
+            .create_group("with_distances")
+            .unwrap();
+        assert!(gt.add_to(&mut distances).is_ok());
+        assert_eq!(GroundTruth::read_from(&distances).unwrap(), gt);
+
+        // Offsets that do not start at `0` would drop or misalign neighbors.
+        let group = group.create_group("/corrupt").unwrap();
+        let ids = group
+            .new_dataset::<usize>()
+            .shape(5)
+            .create(GroundTruth::label().as_str())
+            .unwrap();
+        ids.write(&[1_usize, 2, 3, 4, 5]).unwrap();
+        let offsets = group
+            .new_dataset::<usize>()
+            .shape(2)
+            .create(OFFSETS)
+            .unwrap();
+        offsets.write(&[2_usize, 5]).unwrap();
+        assert!(GroundTruth::read_from(&group).is_err());
+    }
+
+    #[test]
+    fn test_hdf5_distances() {
+        let neighbors = Array2::from_shape_vec((2, 2), vec![0_usize, 2, 3, 1]).unwrap();
+        let gt =
+            GroundTruth::with_distances(neighbors.clone(), arr2(&[[0.5_f32, 1.5], [0.25, 2.0]]))
+                .unwrap();
+
+        let dir = TempDir::new("gt_test_hdf5_distances").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        let hdf5 = File::create(path).unwrap();
+
+        let mut group = hdf5.group("/").unwrap().create_group("with").unwrap();
+        assert!(gt.add_to(&mut group).is_ok());
+        assert!(group.link_exists("distances"));
+        let gt_copy = GroundTruth::read_from(&group).unwrap();
+        assert_eq!(&gt, &gt_copy);
+
+        let gt = GroundTruth::new(neighbors);
+        let mut group = hdf5.group("/").unwrap().create_group("without").unwrap();
+        assert!(gt.add_to(&mut group).is_ok());
+        assert!(!group.link_exists("distances"));
+        let gt_copy = GroundTruth::read_from(&group).unwrap();
+        assert!(gt_copy.get_distances().is_none());
+        assert_eq!(&gt, &gt_copy);
+    }
 }
@@ -1,24 +1,228 @@
+use crate::Hdf5Serialization;
 use anyhow::anyhow;
-use serde::{Deserialize, Serialize};
+use hdf5::Group;
+use ndarray::ArrayView1;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sprs::vec::{NnzEither, SparseIterTools};
+use sprs::CsVecView;
+use std::borrow::Cow;
+use std::f32::consts::PI;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
+#[cfg(feature = "f16")]
+pub mod f16;
 pub mod ground_truth;
 pub mod point_set;
+pub mod point_set_view;
 pub mod query_set;
 
+const MINKOWSKI_CODE: u8 = 9;
+const MINKOWSKI_EXPONENT: &str = "p";
+
 /// Collection of metrics and distance functions that characterize an ANN search.
-#[derive(Eq, PartialEq, Hash, Debug, Clone, Serialize, Deserialize)]
+///
+/// Metrics are ordered by their `code`, and `Minkowski` metrics by their exponent. With serde,
+/// a metric is represented by its `canonical_name`, so that it can key maps in formats such as
+/// JSON and matches the names used in HDF5 files.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Clone)]
 pub enum Metric {
     Hamming,
     Euclidean,
     Cosine,
     InnerProduct,
+    Chebyshev,
+    /// Angular distance, `acos(cosine) / π`, which lies in `[0, 1]`. It induces the same ranking
+    /// as `Cosine`, so the ids of exact nearest neighbors coincide, but distances differ.
+    Angular,
+    /// Canberra distance, `Σ |a_i - b_i| / (|a_i| + |b_i|)`, suited to count data.
+    Canberra,
+    /// Bray-Curtis dissimilarity, `Σ |a_i - b_i| / Σ |a_i + b_i|`, suited to count data.
+    BrayCurtis,
+    /// Manhattan (L1 or taxicab) distance, `Σ |a_i - b_i|`.
+    Manhattan,
+    /// Minkowski distance with exponent `p`, `(Σ |a_i - b_i|^p)^(1/p)`, which generalizes
+    /// `Manhattan` (`p = 1`) and `Euclidean` (`p = 2`). Ground truth for different exponents is
+    /// stored separately.
+    Minkowski {
+        p: u32,
+    },
+    /// Jaccard distance between the sets of non-zero coordinates of two vectors,
+    /// `1 - |A ∩ B| / |A ∪ B|`, meant for sparse or binary vectors. The distance between two
+    /// zero vectors is `0`.
+    Jaccard,
+}
+
+impl Metric {
+    /// Returns the distance between two dense vectors according to this metric, where a smaller
+    /// value indicates a closer pair of vectors.
+    ///
+    /// Similarities are converted to distances as follows: `Cosine` gives `1 - cos(a, b)` and
+    /// `InnerProduct` gives the negated inner product `-<a, b>`. `Hamming` counts the
+    /// coordinates in which the two vectors differ. The cosine similarity of a zero vector with
+    /// any other vector is taken to be `0`. Coordinates where both vectors are `0` contribute
+    /// nothing to `Canberra`, and the `BrayCurtis` distance between two zero vectors is `0`.
+    pub fn distance(&self, a: ArrayView1<f32>, b: ArrayView1<f32>) -> f32 {
+        self.distance_over(a.iter().copied().zip(b.iter().copied()))
+    }
+
+    /// Same as `distance` but for two sparse vectors, where only coordinates that are non-zero
+    /// in at least one of the two vectors are visited.
+    pub fn sparse_distance(&self, a: CsVecView<f32>, b: CsVecView<f32>) -> f32 {
+        self.distance_over(a.iter().nnz_or_zip(b.iter()).map(|entry| match entry {
+            NnzEither::Both((_, &x, &y)) => (x, y),
+            NnzEither::Left((_, &x)) => (x, 0_f32),
+            NnzEither::Right((_, &y)) => (0_f32, y),
+        }))
+    }
+
+    /// Computes the distance from the pairs of coordinates of two vectors. Pairs where both
+    /// coordinates are `0` may be omitted.
+    fn distance_over<I: Iterator<Item = (f32, f32)>>(&self, pairs: I) -> f32 {
+        match self {
+            Metric::Hamming => pairs.filter(|(x, y)| x != y).count() as f32,
+            Metric::Euclidean => pairs
+                .fold(0_f32, |acc, (x, y)| acc + (x - y).powi(2))
+                .sqrt(),
+            Metric::Cosine => 1_f32 - cosine(pairs),
+            Metric::InnerProduct => -pairs.fold(0_f32, |acc, (x, y)| acc + x * y),
+            Metric::Chebyshev => pairs.fold(0_f32, |acc, (x, y)| acc.max((x - y).abs())),
+            Metric::Angular => cosine(pairs).clamp(-1_f32, 1_f32).acos() / PI,
+            Metric::Canberra => pairs
+                .filter(|&(x, y)| x != 0_f32 || y != 0_f32)
+                .fold(0_f32, |acc, (x, y)| {
+                    acc + (x - y).abs() / (x.abs() + y.abs())
+                }),
+            Metric::BrayCurtis => {
+                let (difference, total) = pairs.fold((0_f32, 0_f32), |(d, t), (x, y)| {
+                    (d + (x - y).abs(), t + (x + y).abs())
+                });
+                if total > 0_f32 {
+                    difference / total
+                } else {
+                    0_f32
+                }
+            }
+            Metric::Manhattan => pairs.fold(0_f32, |acc, (x, y)| acc + (x - y).abs()),
+            Metric::Minkowski { p } => pairs
+                .fold(0_f32, |acc, (x, y)| acc + (x - y).abs().powi(*p as i32))
+                .powf(1_f32 / *p as f32),
+            Metric::Jaccard => {
+                let (intersection, union) = pairs
+                    .filter(|&(x, y)| x != 0_f32 || y != 0_f32)
+                    .fold((0_usize, 0_usize), |(i, u), (x, y)| {
+                        (i + (x != 0_f32 && y != 0_f32) as usize, u + 1)
+                    });
+                if union > 0 {
+                    1_f32 - intersection as f32 / union as f32
+                } else {
+                    0_f32
+                }
+            }
+        }
+    }
+
+    /// Returns the stable integer encoding of this metric, used when storing a metric as a value
+    /// rather than as the name of a group. Codes are never reassigned: new metrics get new codes.
+    ///
+    /// The exponent of `Minkowski` is not part of its code and must be stored separately.
+    pub fn code(&self) -> u8 {
+        match self {
+            Metric::Hamming => 0,
+            Metric::Euclidean => 1,
+            Metric::Cosine => 2,
+            Metric::InnerProduct => 3,
+            Metric::Chebyshev => 4,
+            Metric::Angular => 5,
+            Metric::Canberra => 6,
+            Metric::BrayCurtis => 7,
+            Metric::Manhattan => 8,
+            Metric::Minkowski { .. } => MINKOWSKI_CODE,
+            Metric::Jaccard => 10,
+        }
+    }
+
+    /// Returns one instance of every metric, in order of their codes.
+    ///
+    /// As its exponents can not be enumerated, `Minkowski` is represented by `p = 3`, the
+    /// smallest exponent that does not coincide with `Manhattan` (`p = 1`) or `Euclidean`
+    /// (`p = 2`). The other metrics are derived from `from_code`, so the list includes new
+    /// metrics as soon as they are given a code.
+    pub fn all() -> Vec<Metric> {
+        (0..=u8::MAX)
+            .filter_map(|code| match code {
+                MINKOWSKI_CODE => Some(Metric::Minkowski { p: 3 }),
+                _ => Metric::from_code(code).ok(),
+            })
+            .collect()
+    }
+
+    /// Inverse of `code`. Returns an error if `code` does not encode any metric, or if it
+    /// encodes `Minkowski`, whose exponent can not be recovered from the code alone.
+    pub fn from_code(code: u8) -> anyhow::Result<Metric> {
+        match code {
+            0 => Ok(Metric::Hamming),
+            1 => Ok(Metric::Euclidean),
+            2 => Ok(Metric::Cosine),
+            3 => Ok(Metric::InnerProduct),
+            4 => Ok(Metric::Chebyshev),
+            5 => Ok(Metric::Angular),
+            6 => Ok(Metric::Canberra),
+            7 => Ok(Metric::BrayCurtis),
+            8 => Ok(Metric::Manhattan),
+            MINKOWSKI_CODE => Err(anyhow!(
+                "The code of Minkowski does not include its exponent."
+            )),
+            10 => Ok(Metric::Jaccard),
+            _ => Err(anyhow!("{} is not a valid metric code.", code)),
+        }
+    }
+}
+
+/// Returns the cosine similarity between two vectors given the pairs of their coordinates, or
+/// `0` if either is a zero vector.
+fn cosine<I: Iterator<Item = (f32, f32)>>(pairs: I) -> f32 {
+    let (dot, a_norm, b_norm) = pairs.fold((0_f32, 0_f32, 0_f32), |(dot, a, b), (x, y)| {
+        (dot + x * y, a + x * x, b + y * y)
+    });
+    let norms = a_norm.sqrt() * b_norm.sqrt();
+    if norms > 0_f32 {
+        dot / norms
+    } else {
+        0_f32
+    }
+}
+
+impl Metric {
+    /// Returns a stable, lowercase, hyphenated name for the metric (e.g., `inner-product`, or
+    /// `minkowski-3` for `Minkowski` with exponent `3`) that is guaranteed to round-trip through
+    /// `FromStr`. Unlike `Display`, which is meant for human consumption, this name is used to
+    /// label the metric in files. Only `Minkowski` names are allocated.
+    pub fn canonical_name(&self) -> Cow<'static, str> {
+        let name = match self {
+            Metric::Hamming => "hamming",
+            Metric::Euclidean => "euclidean",
+            Metric::Cosine => "cosine",
+            Metric::InnerProduct => "inner-product",
+            Metric::Chebyshev => "chebyshev",
+            Metric::Angular => "angular",
+            Metric::Canberra => "canberra",
+            Metric::BrayCurtis => "bray-curtis",
+            Metric::Manhattan => "manhattan",
+            Metric::Minkowski { p } => return Cow::Owned(format!("minkowski-{}", p)),
+            Metric::Jaccard => "jaccard",
+        };
+        Cow::Borrowed(name)
+    }
 }
 
 impl Display for Metric {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Metric::Minkowski { p } => write!(f, "Minkowski-{}", p),
+            metric => write!(f, "{:?}", metric),
+        }
     }
 }
 
@@ -26,22 +230,183 @@ impl FromStr for Metric {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(p) = s
+            .strip_prefix("Minkowski-")
+            .or_else(|| s.strip_prefix("minkowski-"))
+        {
+            return match p.parse::<u32>() {
+                Ok(p) if p > 0 => Ok(Metric::Minkowski { p }),
+                _ => Err(anyhow!(
+                    "Minkowski exponent must be a positive integer: {}",
+                    s
+                )),
+            };
+        }
         match s {
             "Hamming" | "hamming" => Ok(Metric::Hamming),
             "Euclidean" | "euclidean" => Ok(Metric::Euclidean),
             "Cosine" | "cosine" => Ok(Metric::Cosine),
             "InnerProduct" | "inner-product" | "dot-product" => Ok(Metric::InnerProduct),
+            "Chebyshev" | "chebyshev" | "linf" | "l-inf" | "l-infinity" => Ok(Metric::Chebyshev),
+            "Angular" | "angular" => Ok(Metric::Angular),
+            "Canberra" | "canberra" => Ok(Metric::Canberra),
+            "BrayCurtis" | "bray-curtis" => Ok(Metric::BrayCurtis),
+            "Manhattan" | "manhattan" | "l1" => Ok(Metric::Manhattan),
+            "Jaccard" | "jaccard" => Ok(Metric::Jaccard),
             _ => Err(anyhow!(
-                "Metric must be one of [hamming|euclidean|cosine|inner-product]"
+                "Metric must be one of [hamming|euclidean|cosine|inner-product|chebyshev|angular|\
+                canberra|bray-curtis|manhattan|minkowski-<p>|jaccard]"
             )),
         }
     }
 }
 
+impl Serialize for Metric {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.canonical_name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Metric {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Metric::from_str(&name).map_err(D::Error::custom)
+    }
+}
+
+impl Hdf5Serialization for Metric {
+    type Object = Metric;
+
+    /// Stores the metric as a scalar integer dataset holding its `code`, with the exponent of
+    /// `Minkowski` as an attribute of the dataset.
+    fn add_to(&self, group: &mut Group) -> anyhow::Result<()> {
+        let dataset = group.new_dataset::<u8>().create(Self::label().as_str())?;
+        dataset.write_scalar(&self.code())?;
+        if let Metric::Minkowski { p } = self {
+            dataset
+                .new_attr::<u32>()
+                .create(MINKOWSKI_EXPONENT)?
+                .write_scalar(p)?;
+        }
+        Ok(())
+    }
+
+    fn read_from(group: &Group) -> anyhow::Result<Self::Object> {
+        let dataset = group.dataset(Self::label().as_str())?;
+        match dataset.read_scalar::<u8>()? {
+            MINKOWSKI_CODE => Ok(Metric::Minkowski {
+                p: dataset.attr(MINKOWSKI_EXPONENT)?.read_scalar::<u32>()?,
+            }),
+            code => Metric::from_code(code),
+        }
+    }
+
+    fn label() -> String {
+        "metric".to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Metric;
+    use crate::{Hdf5Serialization, Metric};
+    use approx_eq::assert_approx_eq;
+    use hdf5::File;
+    use ndarray::arr1;
+    use sprs::CsVec;
     use std::str::FromStr;
+    use tempdir::TempDir;
+
+    const ALL_METRICS: [Metric; 11] = [
+        Metric::Hamming,
+        Metric::Euclidean,
+        Metric::Cosine,
+        Metric::InnerProduct,
+        Metric::Chebyshev,
+        Metric::Angular,
+        Metric::Canberra,
+        Metric::BrayCurtis,
+        Metric::Manhattan,
+        Metric::Minkowski { p: 3 },
+        Metric::Jaccard,
+    ];
+
+    #[test]
+    fn test_code() {
+        assert_eq!(Metric::Hamming.code(), 0);
+        assert_eq!(Metric::Euclidean.code(), 1);
+        for metric in ALL_METRICS {
+            if let Metric::Minkowski { .. } = metric {
+                assert!(Metric::from_code(metric.code()).is_err());
+                continue;
+            }
+            assert_eq!(metric, Metric::from_code(metric.code()).unwrap());
+        }
+        assert!(Metric::from_code(ALL_METRICS.len() as u8).is_err());
+    }
+
+    #[test]
+    fn test_all() {
+        let all = Metric::all();
+        assert_eq!(all.len(), ALL_METRICS.len());
+        for metric in ALL_METRICS {
+            assert!(all.contains(&metric));
+        }
+        assert!(all.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_serde() {
+        for metric in ALL_METRICS {
+            let json = serde_json::to_string(&metric).unwrap();
+            assert_eq!(json, format!("\"{}\"", metric.canonical_name()));
+            assert_eq!(serde_json::from_str::<Metric>(&json).unwrap(), metric);
+        }
+        assert_eq!(
+            serde_json::from_str::<Metric>("\"InnerProduct\"").unwrap(),
+            Metric::InnerProduct
+        );
+        assert!(serde_json::from_str::<Metric>("\"foo\"").is_err());
+    }
+
+    #[test]
+    fn test_hdf5() {
+        let dir = TempDir::new("test_metric_hdf5").unwrap();
+        let path = dir.path().join("metric.hdf5");
+        let path = path.to_str().unwrap();
+        let hdf5 = File::create(path).unwrap();
+
+        for metric in ALL_METRICS {
+            let mut group = hdf5.create_group(&metric.to_string()).unwrap();
+            metric.add_to(&mut group).unwrap();
+        }
+
+        for metric in ALL_METRICS {
+            let group = hdf5.group(&metric.to_string()).unwrap();
+            assert_eq!(metric, Metric::read_from(&group).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_canonical_name() {
+        ALL_METRICS.iter().for_each(|metric| {
+            let name = metric.canonical_name();
+            assert_eq!(name, name.to_lowercase());
+            assert_eq!(&Metric::from_str(&name).unwrap(), metric);
+        });
+        assert_eq!(Metric::InnerProduct.canonical_name(), "inner-product");
+        assert!(matches!(
+            Metric::Euclidean.canonical_name(),
+            std::borrow::Cow::Borrowed("euclidean")
+        ));
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for metric in ALL_METRICS {
+            assert_eq!(Metric::from_str(&metric.to_string()).unwrap(), metric);
+        }
+        assert_eq!(Metric::Chebyshev.to_string(), "Chebyshev");
+    }
 
     #[test]
     fn test_from_str() {
@@ -61,6 +426,121 @@ mod tests {
         );
         assert_eq!(Metric::Euclidean, Metric::from_str("euclidean").unwrap());
         assert_eq!(Metric::Euclidean, Metric::from_str("Euclidean").unwrap());
+        assert_eq!(Metric::Chebyshev, Metric::from_str("chebyshev").unwrap());
+        assert_eq!(Metric::Chebyshev, Metric::from_str("Chebyshev").unwrap());
+        assert_eq!(Metric::Chebyshev, Metric::from_str("linf").unwrap());
+        assert_eq!(Metric::Chebyshev, Metric::from_str("l-inf").unwrap());
+        assert_eq!(Metric::Chebyshev, Metric::from_str("l-infinity").unwrap());
+        assert_eq!(Metric::Angular, Metric::from_str("angular").unwrap());
+        assert_eq!(Metric::Angular, Metric::from_str("Angular").unwrap());
+        assert_eq!(Metric::Canberra, Metric::from_str("canberra").unwrap());
+        assert_eq!(Metric::Canberra, Metric::from_str("Canberra").unwrap());
+        assert_eq!(Metric::BrayCurtis, Metric::from_str("bray-curtis").unwrap());
+        assert_eq!(Metric::BrayCurtis, Metric::from_str("BrayCurtis").unwrap());
+        assert_eq!(Metric::Manhattan, Metric::from_str("manhattan").unwrap());
+        assert_eq!(Metric::Manhattan, Metric::from_str("Manhattan").unwrap());
+        assert_eq!(Metric::Manhattan, Metric::from_str("l1").unwrap());
+        assert_eq!(
+            Metric::Minkowski { p: 3 },
+            Metric::from_str("Minkowski-3").unwrap()
+        );
+        assert_eq!(
+            Metric::Minkowski { p: 12 },
+            Metric::from_str("minkowski-12").unwrap()
+        );
+        assert_eq!(Metric::Minkowski { p: 3 }.to_string(), "Minkowski-3");
+        assert!(Metric::from_str("minkowski-0").is_err());
+        assert!(Metric::from_str("minkowski-x").is_err());
+        assert!(Metric::from_str("minkowski").is_err());
+        assert_eq!(Metric::Jaccard, Metric::from_str("jaccard").unwrap());
+        assert_eq!(Metric::Jaccard, Metric::from_str("Jaccard").unwrap());
         assert!(Metric::from_str("foo").is_err());
     }
+
+    #[test]
+    fn test_distance() {
+        let a = arr1(&[1.0_f32, 0.0, 2.0]);
+        let b = arr1(&[1.0_f32, 3.0, -2.0]);
+        let zero = arr1(&[0.0_f32, 0.0, 0.0]);
+
+        let distance = |metric: Metric, x: &ndarray::Array1<f32>, y: &ndarray::Array1<f32>| {
+            metric.distance(x.view(), y.view()) as f64
+        };
+
+        assert_approx_eq!(distance(Metric::Hamming, &a, &b), 2.0, 0.001);
+        assert_approx_eq!(distance(Metric::Hamming, &a, &a), 0.0, 0.001);
+
+        // sqrt(0 + 9 + 16) = 5.
+        assert_approx_eq!(distance(Metric::Euclidean, &a, &b), 5.0, 0.001);
+        assert_approx_eq!(distance(Metric::Euclidean, &a, &zero), 2.236, 0.001);
+
+        // <a, b> = 1 - 4 = -3, |a| = sqrt(5), |b| = sqrt(14).
+        assert_approx_eq!(distance(Metric::InnerProduct, &a, &b), 3.0, 0.001);
+        assert_approx_eq!(distance(Metric::InnerProduct, &a, &a), -5.0, 0.001);
+        assert_approx_eq!(distance(Metric::Cosine, &a, &b), 1.358, 0.001);
+        assert_approx_eq!(distance(Metric::Cosine, &a, &a), 0.0, 0.001);
+        assert_approx_eq!(distance(Metric::Cosine, &a, &zero), 1.0, 0.001);
+
+        assert_approx_eq!(distance(Metric::Chebyshev, &a, &b), 4.0, 0.001);
+        assert_approx_eq!(distance(Metric::Manhattan, &a, &b), 7.0, 0.001);
+        assert_approx_eq!(distance(Metric::Manhattan, &a, &zero), 3.0, 0.001);
+
+        // (0 + 27 + 64)^(1/3).
+        assert_approx_eq!(distance(Metric::Minkowski { p: 3 }, &a, &b), 4.498, 0.001);
+        assert_approx_eq!(distance(Metric::Minkowski { p: 1 }, &a, &b), 7.0, 0.001);
+        assert_approx_eq!(distance(Metric::Minkowski { p: 2 }, &a, &b), 5.0, 0.001);
+
+        // A = {0, 2}, B = {0, 1, 2}.
+        assert_approx_eq!(distance(Metric::Jaccard, &a, &b), 0.3333, 0.001);
+        assert_approx_eq!(distance(Metric::Jaccard, &a, &zero), 1.0, 0.001);
+        assert_eq!(Metric::Jaccard.distance(zero.view(), zero.view()), 0_f32);
+
+        // acos(-3 / sqrt(70)) / pi.
+        assert_approx_eq!(distance(Metric::Angular, &a, &b), 0.617, 0.001);
+        assert_approx_eq!(distance(Metric::Angular, &a, &a), 0.0, 0.001);
+    }
+
+    #[test]
+    fn test_count_distances() {
+        let a = arr1(&[1.0_f32, 0.0, 2.0]);
+        let b = arr1(&[1.0_f32, 3.0, -2.0]);
+        let zero = arr1(&[0.0_f32, 0.0, 0.0]);
+
+        // 0 / 2 + 3 / 3 + 4 / 4.
+        let canberra = Metric::Canberra.distance(a.view(), b.view());
+        assert_approx_eq!(canberra as f64, 2.0, 0.001);
+        assert_eq!(Metric::Canberra.distance(zero.view(), zero.view()), 0_f32);
+
+        // (0 + 3 + 4) / (2 + 3 + 0).
+        let bray_curtis = Metric::BrayCurtis.distance(a.view(), b.view());
+        assert_approx_eq!(bray_curtis as f64, 1.4, 0.001);
+        assert_eq!(Metric::BrayCurtis.distance(zero.view(), zero.view()), 0_f32);
+
+        let u = CsVec::new(5, vec![0, 3], vec![2.0_f32, 1.0]);
+        let v = CsVec::new(5, vec![1, 3], vec![1.0_f32, 3.0]);
+
+        // 2 / 2 + 1 / 1 + 2 / 4.
+        let canberra = Metric::Canberra.sparse_distance(u.view(), v.view());
+        assert_approx_eq!(canberra as f64, 2.5, 0.001);
+
+        // (2 + 1 + 2) / (2 + 1 + 4).
+        let bray_curtis = Metric::BrayCurtis.sparse_distance(u.view(), v.view());
+        assert_approx_eq!(bray_curtis as f64, 0.714, 0.001);
+    }
+
+    #[test]
+    fn test_sparse_distance() {
+        let u = CsVec::new(5, vec![0, 2, 3], vec![2.0_f32, -1.0, 1.0]);
+        let v = CsVec::new(5, vec![1, 3], vec![1.0_f32, 3.0]);
+        let u_dense = arr1(&[2.0_f32, 0.0, -1.0, 1.0, 0.0]);
+        let v_dense = arr1(&[0.0_f32, 1.0, 0.0, 3.0, 0.0]);
+
+        for metric in ALL_METRICS {
+            assert_approx_eq!(
+                metric.sparse_distance(u.view(), v.view()) as f64,
+                metric.distance(u_dense.view(), v_dense.view()) as f64,
+                0.001
+            );
+        }
+    }
 }
@@ -1,12 +1,23 @@
+use crate::io::inspect::PointSetInfo;
+use crate::types::point_set_view::{PointSetView, PointView};
 use crate::Hdf5Serialization;
 use anyhow::{anyhow, Result};
 use hdf5::{Group, H5Type};
-use linfa_linalg::norm::Norm;
-use ndarray::{Array1, Array2, Axis, Zip};
-use serde::{Deserialize, Serialize};
-use sprs::CsMat;
+use ndarray::{s, Array1, Array2, ArrayView1, Axis, Zip};
+use ndarray_rand::rand::rngs::StdRng;
+use ndarray_rand::rand::seq::index::sample;
+use ndarray_rand::rand::seq::SliceRandom;
+use ndarray_rand::rand::{Rng, SeedableRng};
+use ndarray_rand::rand_distr::{Distribution, Normal, Uniform};
+use ndarray_rand::RandomExt;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use sprs::{CompressedStorage, CsMat, CsVec};
+use std::cmp::min;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::iter::zip;
+use std::ops::Sub;
 
 const DENSE: &str = "dense";
 const SPARSE: &str = "sparse";
@@ -15,17 +26,71 @@ const SPARSE_INDICES: &str = "indices";
 const SPARSE_DATA: &str = "data";
 const SPARSE_SHAPE: &str = "shape";
 
+/// Norm at or below which normalization leaves a point untouched rather than dividing by a
+/// (near) zero norm, which would fill the point with NaNs or infinities.
+const NORM_EPSILON: f32 = 1e-12;
+
 /// A set of points (dense, sparse, or both) represented as a matrix,
 /// where each row corresponds to a single vector.
+///
+/// The sparse component is always stored in CSR form with the column indices of every row
+/// sorted, as `sprs` operations and slicing assume. Deserialization enforces this invariant by
+/// sorting the indices of every row if necessary.
+///
+/// Construction, selection and serialization work for any data type, including byte-valued
+/// vectors such as `PointSet<u8>` or `PointSet<i8>`. Norms, normalization, search and other
+/// numeric helpers are only available for `PointSet<f32>`; use `cast` to convert other types.
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct PointSet<DataType: Clone> {
     dense: Option<Array2<DataType>>,
+    #[serde(deserialize_with = "deserialize_sparse")]
     sparse: Option<CsMat<DataType>>,
 }
 
+/// Mirrors the serialized form of a `CsMat`, so that its parts can be deserialized without the
+/// structural checks of `sprs`, which reject unsorted indices.
+#[derive(Deserialize)]
+struct RawSparse<DataType> {
+    storage: CompressedStorage,
+    nrows: usize,
+    ncols: usize,
+    indptr: Vec<usize>,
+    indices: Vec<usize>,
+    data: Vec<DataType>,
+}
+
+/// Deserializes the sparse component of a `PointSet` in CSR form with sorted indices.
+fn deserialize_sparse<'de, D, DataType>(
+    deserializer: D,
+) -> Result<Option<CsMat<DataType>>, D::Error>
+where
+    D: Deserializer<'de>,
+    DataType: Clone + Deserialize<'de>,
+{
+    let Some(raw) = Option::<RawSparse<DataType>>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let shape = (raw.nrows, raw.ncols);
+    let sparse = match raw.storage {
+        CompressedStorage::CSR => {
+            CsMat::new_from_unsorted(shape, raw.indptr, raw.indices, raw.data)
+        }
+        CompressedStorage::CSC => {
+            CsMat::new_from_unsorted_csc(shape, raw.indptr, raw.indices, raw.data)
+                .map(|csc| csc_to_csr(&csc))
+        }
+    };
+    sparse
+        .map(Some)
+        .map_err(|(_, _, _, error)| D::Error::custom(error))
+}
+
 impl<DataType: Clone> PointSet<DataType> {
     /// Creates a point set.
     ///
+    /// A `sparse` matrix in CSC storage is converted to CSR, so that each row corresponds to a
+    /// single vector regardless of the storage order of the input.
+    ///
     /// Returns an error if both `dense` and `sparse` vector sets are empty, or if they are both
     /// provided, the number of rows of the `dense` and `sparse` sets do not match.
     pub fn new(
@@ -35,9 +100,11 @@ impl<DataType: Clone> PointSet<DataType> {
         if dense.is_none() && sparse.is_none() {
             return Err(anyhow!("Both dense and sparse sets are empty."));
         }
-        if dense.is_some() && sparse.is_some() {
-            let dense = dense.as_ref().unwrap();
-            let sparse = sparse.as_ref().unwrap();
+        let sparse = match sparse {
+            Some(sparse) if sparse.is_csc() => Some(csc_to_csr(&sparse)),
+            sparse => sparse,
+        };
+        if let (Some(dense), Some(sparse)) = (dense.as_ref(), sparse.as_ref()) {
             if dense.nrows() != sparse.rows() {
                 return Err(anyhow!(
                     "There are {} dense vectors but {} sparse vectors!",
@@ -81,6 +148,42 @@ impl<DataType: Clone> PointSet<DataType> {
         self.num_sparse_dimensions() + self.num_dense_dimensions()
     }
 
+    /// Summarizes the number of non-zero entries of the sparse component, or returns `None` if
+    /// the set has no sparse component. Only the structure of the sparse matrix is read.
+    pub fn sparsity_stats(&self) -> Option<SparsityStats> {
+        let sparse = self.sparse.as_ref()?;
+        let indptr = sparse.indptr();
+        let indptr = indptr.to_proper();
+        let mut nnz_per_row = indptr.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+        nnz_per_row.sort_unstable();
+
+        let nnz = sparse.nnz();
+        let size = sparse.rows() * sparse.cols();
+        let middle = nnz_per_row.len() / 2;
+        let median_nnz_per_row = match nnz_per_row.len() {
+            0 => 0_f32,
+            len if len.is_multiple_of(2) => {
+                (nnz_per_row[middle - 1] + nnz_per_row[middle]) as f32 / 2_f32
+            }
+            _ => nnz_per_row[middle] as f32,
+        };
+        Some(SparsityStats {
+            nnz,
+            density: if size > 0 {
+                (nnz as f64 / size as f64) as f32
+            } else {
+                0_f32
+            },
+            mean_nnz_per_row: if nnz_per_row.is_empty() {
+                0_f32
+            } else {
+                (nnz as f64 / nnz_per_row.len() as f64) as f32
+            },
+            median_nnz_per_row,
+            max_nnz_per_row: nnz_per_row.last().copied().unwrap_or(0),
+        })
+    }
+
     /// Returns the dense sub-vectors.
     pub fn get_dense(&self) -> Option<&Array2<DataType>> {
         self.dense.as_ref()
@@ -91,6 +194,101 @@ impl<DataType: Clone> PointSet<DataType> {
         self.sparse.as_ref()
     }
 
+    /// Returns a view of the dense and sparse sub-vectors of the `i`-th point, each of which is
+    /// `None` if the set does not have the corresponding component.
+    ///
+    /// Returns an error if `i` is out of bounds.
+    pub fn get_point(&self, i: usize) -> Result<PointView<'_, DataType>> {
+        if i >= self.num_points() {
+            return Err(anyhow!(
+                "Point {} is out of bounds for a set of {} points.",
+                i,
+                self.num_points()
+            ));
+        }
+        Ok(self.point(i))
+    }
+
+    /// Returns an iterator over views of the points in this set, in order.
+    pub fn points(&self) -> impl Iterator<Item = PointView<'_, DataType>> {
+        (0..self.num_points()).map(|i| self.point(i))
+    }
+
+    /// Returns a view of the `i`-th point, which must be in bounds.
+    fn point(&self, i: usize) -> PointView<'_, DataType> {
+        PointView::new(
+            self.dense.as_ref().map(|dense| dense.row(i)),
+            self.sparse.as_ref().and_then(|sparse| sparse.outer_view(i)),
+        )
+    }
+
+    /// Returns a view of all points in this set.
+    pub fn view(&self) -> PointSetView<'_, DataType> {
+        PointSetView::new(
+            self.dense.as_ref().map(|dense| dense.view()),
+            self.sparse.as_ref().map(|sparse| sparse.view()),
+        )
+    }
+
+    /// Returns a view of the points with ids in `[start, end)` that borrows the vectors of this
+    /// set instead of copying them, unlike `select`.
+    ///
+    /// Returns an error if `start > end` or `end > self.num_points()`.
+    pub fn slice_rows(&self, start: usize, end: usize) -> Result<PointSetView<'_, DataType>> {
+        if start > end || end > self.num_points() {
+            return Err(anyhow!(
+                "Range [{}, {}) is out of bounds for {} points.",
+                start,
+                end,
+                self.num_points()
+            ));
+        }
+        Ok(PointSetView::new(
+            self.dense
+                .as_ref()
+                .map(|dense| dense.slice(s![start..end, ..])),
+            self.sparse
+                .as_ref()
+                .map(|sparse| sparse.slice_outer(start..end)),
+        ))
+    }
+
+    /// Returns a copy of this point set that only contains its dense component, or `None` if
+    /// there is no dense component.
+    pub fn dense_only(&self) -> Option<PointSet<DataType>> {
+        self.dense.as_ref().map(|dense| PointSet {
+            dense: Some(dense.clone()),
+            sparse: None,
+        })
+    }
+
+    /// Returns a copy of this point set that only contains its sparse component, or `None` if
+    /// there is no sparse component.
+    pub fn sparse_only(&self) -> Option<PointSet<DataType>> {
+        self.sparse.as_ref().map(|sparse| PointSet {
+            dense: None,
+            sparse: Some(sparse.clone()),
+        })
+    }
+
+    /// Consumes this point set and splits it into a set that only contains its dense component
+    /// and a set that only contains its sparse component, either of which is `None` if the
+    /// component does not exist.
+    ///
+    /// Unlike `dense_only` and `sparse_only`, this moves the components rather than copying them,
+    /// so it does not require additional memory.
+    pub fn split(self) -> (Option<PointSet<DataType>>, Option<PointSet<DataType>>) {
+        let dense = self.dense.map(|dense| PointSet {
+            dense: Some(dense),
+            sparse: None,
+        });
+        let sparse = self.sparse.map(|sparse| PointSet {
+            dense: None,
+            sparse: Some(sparse),
+        });
+        (dense, sparse)
+    }
+
     /// Selects a subset of points with the given ids.
     pub fn select(&self, ids: &[usize]) -> PointSet<DataType> {
         let dense = self.dense.as_ref().map(|dense| dense.select(Axis(0), ids));
@@ -141,54 +339,883 @@ impl<DataType: Clone> PointSet<DataType> {
 
         PointSet { dense, sparse }
     }
+
+    /// Selects the points for which `mask` is `true`, preserving their relative order.
+    ///
+    /// Returns an error if `mask` does not have one entry per point.
+    pub fn select_mask(&self, mask: &[bool]) -> Result<PointSet<DataType>> {
+        if mask.len() != self.num_points() {
+            return Err(anyhow!(
+                "Mask has {} entries but there are {} points.",
+                mask.len(),
+                self.num_points()
+            ));
+        }
+        let ids = mask
+            .iter()
+            .enumerate()
+            .filter_map(|(id, &selected)| selected.then_some(id))
+            .collect::<Vec<_>>();
+        Ok(self.select(&ids))
+    }
+
+    /// Projects every point onto a subset of dimensions, where the `j`-th dense dimension of the
+    /// result is dense dimension `dense_cols[j]` of this set, and likewise for `sparse_cols`.
+    /// Dimensions may be repeated. A component for which no dimensions are selected is dropped.
+    ///
+    /// Returns an error if a dimension is out of range for its component, or if no dimensions are
+    /// selected at all.
+    pub fn select_dimensions(
+        &self,
+        dense_cols: &[usize],
+        sparse_cols: &[usize],
+    ) -> Result<PointSet<DataType>> {
+        let check = |cols: &[usize], num_dimensions: usize, component: &str| match cols
+            .iter()
+            .find(|&&col| col >= num_dimensions)
+        {
+            Some(col) => Err(anyhow!(
+                "Dimension {} is out of range for {} {} dimensions.",
+                col,
+                num_dimensions,
+                component
+            )),
+            None => Ok(()),
+        };
+        check(dense_cols, self.num_dense_dimensions(), "dense")?;
+        check(sparse_cols, self.num_sparse_dimensions(), "sparse")?;
+
+        let dense = match self.dense.as_ref() {
+            Some(dense) if !dense_cols.is_empty() => Some(dense.select(Axis(1), dense_cols)),
+            _ => None,
+        };
+        let sparse = match self.sparse.as_ref() {
+            Some(sparse) if !sparse_cols.is_empty() => {
+                // The new columns of every old column, which may be selected more than once.
+                let mut new_cols = vec![vec![]; sparse.cols()];
+                sparse_cols
+                    .iter()
+                    .enumerate()
+                    .for_each(|(new_col, &col)| new_cols[col].push(new_col));
+
+                let mut indptr = Vec::with_capacity(sparse.rows() + 1);
+                let mut indices = vec![];
+                let mut data = vec![];
+                indptr.push(0);
+                for row in sparse.outer_iterator() {
+                    let mut entries = row
+                        .iter()
+                        .flat_map(|(col, value)| {
+                            new_cols[col]
+                                .iter()
+                                .map(move |&new_col| (new_col, value.clone()))
+                        })
+                        .collect::<Vec<_>>();
+                    entries.sort_by_key(|(new_col, _)| *new_col);
+                    entries.into_iter().for_each(|(new_col, value)| {
+                        indices.push(new_col);
+                        data.push(value);
+                    });
+                    indptr.push(indices.len());
+                }
+                Some(CsMat::new(
+                    (sparse.rows(), sparse_cols.len()),
+                    indptr,
+                    indices,
+                    data,
+                ))
+            }
+            _ => None,
+        };
+        if dense.is_none() && sparse.is_none() {
+            return Err(anyhow!("No dimensions were selected."));
+        }
+        Ok(PointSet { dense, sparse })
+    }
+
+    /// Draws `n` distinct points uniformly at random using the given `seed` and returns them in
+    /// their original relative order.
+    ///
+    /// Returns an error if `n` exceeds the number of points.
+    pub fn sample(&self, n: usize, seed: u64) -> Result<PointSet<DataType>> {
+        if n > self.num_points() {
+            return Err(anyhow!(
+                "Can not sample {} points from a set of {} points.",
+                n,
+                self.num_points()
+            ));
+        }
+        let mut ids = sample(&mut StdRng::seed_from_u64(seed), self.num_points(), n).into_vec();
+        ids.sort_unstable();
+        Ok(self.select(&ids))
+    }
+
+    /// Shuffles the points uniformly at random using the given `seed`.
+    ///
+    /// Returns the shuffled set along with the permutation that was applied, where the `i`-th
+    /// point of the shuffled set is the `permutation[i]`-th point of this set. Ground truth
+    /// computed against this set can be made valid for the shuffled set by passing the
+    /// permutation to `GroundTruth::remap_ids`.
+    pub fn shuffle(&self, seed: u64) -> (PointSet<DataType>, Vec<usize>) {
+        let mut permutation = (0..self.num_points()).collect::<Vec<_>>();
+        permutation.shuffle(&mut StdRng::seed_from_u64(seed));
+        (self.select(&permutation), permutation)
+    }
+
+    /// Returns an error if `other` can not be appended to this point set, which is the case if
+    /// the two sets do not have the same components (dense, sparse, or both) or if the
+    /// dimensionality of a component does not match.
+    pub fn is_appendable(&self, other: &PointSet<DataType>) -> Result<()> {
+        if self.dense.is_some() != other.dense.is_some()
+            || self.sparse.is_some() != other.sparse.is_some()
+        {
+            return Err(anyhow!(
+                "Point sets must both have the same dense and sparse components."
+            ));
+        }
+        if self.num_dense_dimensions() != other.num_dense_dimensions() {
+            return Err(anyhow!(
+                "There are {} dense dimensions but {} dense dimensions in the other set!",
+                self.num_dense_dimensions(),
+                other.num_dense_dimensions()
+            ));
+        }
+        if self.num_sparse_dimensions() != other.num_sparse_dimensions() {
+            return Err(anyhow!(
+                "There are {} sparse dimensions but {} sparse dimensions in the other set!",
+                self.num_sparse_dimensions(),
+                other.num_sparse_dimensions()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Appends the points in `other` to the end of this point set.
+    ///
+    /// Returns an error if `other` is not appendable, as determined by `is_appendable`, in which
+    /// case this point set is left unchanged.
+    pub fn append(&mut self, other: &PointSet<DataType>) -> Result<()> {
+        self.is_appendable(other)?;
+
+        if let (Some(dense), Some(other)) = (self.dense.as_mut(), other.dense.as_ref()) {
+            dense.append(Axis(0), other.view())?;
+        }
+
+        if let (Some(sparse), Some(other)) = (self.sparse.take(), other.sparse.as_ref()) {
+            let (num_rows, num_cols) = sparse.shape();
+            let (mut indptr, mut indices, mut data) = sparse.into_raw_storage();
+            let offset = indptr[num_rows];
+            let other_indptr = other.indptr();
+            let other_indptr = other_indptr.to_proper();
+            indptr.extend(other_indptr.iter().skip(1).map(|&p| offset + p));
+            indices.extend_from_slice(other.indices());
+            data.extend_from_slice(other.data());
+            self.sparse = Some(CsMat::new(
+                (num_rows + other.rows(), num_cols),
+                indptr,
+                indices,
+                data,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Stacks the given point sets into a new point set, with the points of `sets[0]` first.
+    ///
+    /// Returns an error if `sets` is empty or if a set is not appendable to the first, as
+    /// determined by `is_appendable`.
+    pub fn concat(sets: &[PointSet<DataType>]) -> Result<PointSet<DataType>> {
+        let Some((first, rest)) = sets.split_first() else {
+            return Err(anyhow!("There are no point sets to concatenate."));
+        };
+        rest.iter().try_for_each(|set| first.is_appendable(set))?;
+        let mut point_set = first.clone();
+        rest.iter().try_for_each(|set| point_set.append(set))?;
+        Ok(point_set)
+    }
+
+    /// Returns a copy of this set with every value converted to `Target` with `From`, e.g., from
+    /// `f32` to `f64`. Use `cast_with` for lossy conversions, such as from `f64` to `f32`.
+    pub fn cast<Target: Clone + From<DataType>>(&self) -> PointSet<Target> {
+        self.cast_with(|x| Target::from(x.clone()))
+    }
+
+    /// Returns a copy of this set with `convert` applied to every value. The shapes of both
+    /// components and the structure of the sparse component are preserved, so values that
+    /// convert to `0` remain as explicit entries.
+    pub fn cast_with<Target: Clone>(
+        &self,
+        convert: impl Fn(&DataType) -> Target,
+    ) -> PointSet<Target> {
+        PointSet {
+            dense: self.dense.as_ref().map(|dense| dense.map(&convert)),
+            sparse: self.sparse.as_ref().map(|sparse| sparse.map(&convert)),
+        }
+    }
+}
+
+impl<DataType: Copy + Default + PartialOrd + Sub<Output = DataType>> PointSet<DataType> {
+    /// Converts the dense component to a sparse one, keeping only the entries whose absolute
+    /// value exceeds `threshold`, which should not be negative. `DataType::default()` is taken
+    /// to be zero.
+    ///
+    /// If the set also has a sparse component, the converted dense dimensions come first and the
+    /// existing sparse dimensions follow, so that every point keeps all of its dimensions. A set
+    /// without a dense component is returned as is.
+    pub fn to_sparse(&self, threshold: DataType) -> PointSet<DataType> {
+        let Some(dense) = self.dense.as_ref() else {
+            return self.clone();
+        };
+        let zero = DataType::default();
+        // Tests `x < -threshold` without negating `threshold`, which unsigned types can not.
+        let keep = |x: DataType| x > threshold || (x < zero && x < zero - threshold);
+
+        let offset = dense.ncols();
+        let mut indptr = Vec::with_capacity(dense.nrows() + 1);
+        let mut indices = vec![];
+        let mut data = vec![];
+        indptr.push(0);
+        for (i, row) in dense.axis_iter(Axis(0)).enumerate() {
+            row.iter()
+                .enumerate()
+                .filter(|(_, &x)| keep(x))
+                .for_each(|(j, &x)| {
+                    indices.push(j);
+                    data.push(x);
+                });
+            if let Some(row) = self.sparse.as_ref().and_then(|sparse| sparse.outer_view(i)) {
+                row.iter().for_each(|(j, &x)| {
+                    indices.push(offset + j);
+                    data.push(x);
+                });
+            }
+            indptr.push(indices.len());
+        }
+        let shape = (dense.nrows(), offset + self.num_sparse_dimensions());
+        PointSet {
+            dense: None,
+            sparse: Some(CsMat::new(shape, indptr, indices, data)),
+        }
+    }
+
+    /// Materializes the sparse component into a dense one, where entries that are not stored are
+    /// `DataType::default()`, taken to be zero.
+    ///
+    /// If the set also has a dense component, the existing dense dimensions come first and the
+    /// materialized sparse dimensions follow, so that every point keeps all of its dimensions. A
+    /// set without a sparse component is returned as is.
+    pub fn to_dense(&self) -> PointSet<DataType> {
+        let Some(sparse) = self.sparse.as_ref() else {
+            return self.clone();
+        };
+        let offset = self.num_dense_dimensions();
+        let mut dense =
+            Array2::from_elem((sparse.rows(), offset + sparse.cols()), DataType::default());
+        if let Some(existing) = self.dense.as_ref() {
+            dense.slice_mut(s![.., ..offset]).assign(existing);
+        }
+        for (i, row) in sparse.outer_iterator().enumerate() {
+            row.iter().for_each(|(j, &x)| dense[[i, offset + j]] = x);
+        }
+        PointSet {
+            dense: Some(dense),
+            sparse: None,
+        }
+    }
 }
 
 impl PointSet<f32> {
+    /// Creates a point set like `new`, but additionally returns an error naming the first point
+    /// that has a NaN or infinite value in its dense or sparse sub-vector. See `validate_finite`.
+    ///
+    /// Non-finite values silently corrupt norms, scores and therefore ground truth, so this
+    /// should be preferred over `new` when vectors come from an untrusted source, at the cost of
+    /// a pass over all values.
+    pub fn new_checked(
+        dense: Option<Array2<f32>>,
+        sparse: Option<CsMat<f32>>,
+    ) -> Result<PointSet<f32>> {
+        let points = PointSet::new(dense, sparse)?;
+        points.validate_finite()?;
+        Ok(points)
+    }
+
+    /// Returns an error naming the point and dimension of the first NaN or infinite value, if
+    /// any, for example to check a dataset right after reading it. Points are scanned in order,
+    /// and the dense sub-vector of a point before its sparse sub-vector.
+    pub fn validate_finite(&self) -> Result<()> {
+        match self.first_non_finite() {
+            None => Ok(()),
+            Some((row, component, dimension)) => Err(anyhow!(
+                "Point {} has a NaN or infinite value in {} dimension {}.",
+                row,
+                component,
+                dimension
+            )),
+        }
+    }
+
+    /// Returns `true` if any value in the dense or sparse sub-vectors is NaN or infinite.
+    pub fn has_non_finite(&self) -> bool {
+        self.first_non_finite().is_some()
+    }
+
+    /// Returns the point, the component (`dense` or `sparse`) and the dimension of the first
+    /// NaN or infinite value, if any.
+    fn first_non_finite(&self) -> Option<(usize, &'static str, usize)> {
+        let dense = self.dense.as_ref().and_then(|dense| {
+            dense
+                .indexed_iter()
+                .find(|(_, x)| !x.is_finite())
+                .map(|((row, dimension), _)| (row, DENSE, dimension))
+        });
+        let sparse = self.sparse.as_ref().and_then(|sparse| {
+            sparse
+                .iter()
+                .find(|(x, _)| !x.is_finite())
+                .map(|(_, (row, dimension))| (row, SPARSE, dimension))
+        });
+        // `DENSE` sorts before `SPARSE`.
+        dense.into_iter().chain(sparse).min()
+    }
+
+    /// Returns a copy of the dense sub-vectors in transposed form, with shape
+    /// `[num_dense_dimensions, num_points]`, so that each row holds the values of a single
+    /// dimension across all points; or `None` if the point set has no dense component.
+    ///
+    /// Note that this allocates a new array as large as the dense component itself.
+    pub fn dense_transposed(&self) -> Option<Array2<f32>> {
+        self.dense.as_ref().map(|dense| dense.t().to_owned())
+    }
+
+    /// Returns `true` if `other` has the same components and shape as this set and every value
+    /// differs from the corresponding value in this set by at most `tol`.
+    ///
+    /// Sparse components must have exactly the same structure, i.e., the same non-zero
+    /// coordinates in every point. This is useful to compare sets after lossy transformations,
+    /// where `PartialEq` is too strict.
+    pub fn approx_eq(&self, other: &Self, tol: f32) -> bool {
+        let dense_eq = match (self.dense.as_ref(), other.dense.as_ref()) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                a.shape() == b.shape() && Zip::from(a).and(b).all(|x, y| (x - y).abs() <= tol)
+            }
+            _ => false,
+        };
+        let sparse_eq = match (self.sparse.as_ref(), other.sparse.as_ref()) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                a.shape() == b.shape()
+                    && a.indptr() == b.indptr()
+                    && a.indices() == b.indices()
+                    && zip(a.data(), b.data()).all(|(x, y)| (x - y).abs() <= tol)
+            }
+            _ => false,
+        };
+        dense_eq && sparse_eq
+    }
+
+    /// Returns a copy of this set in double precision. The conversion is exact, so it is useful to
+    /// compute scores, e.g., for ground truth, with extra precision to break near ties.
+    pub fn to_f64(&self) -> PointSet<f64> {
+        self.cast()
+    }
+
+    /// Returns a single-precision copy of the given set, rounding every value to the nearest
+    /// `f32`. This is the inverse of `to_f64` but, in general, loses precision.
+    ///
+    /// The structure of the sparse component is preserved: values that round to `0` remain as
+    /// explicit entries.
+    pub fn from_f64(points: &PointSet<f64>) -> PointSet<f32> {
+        points.cast_with(|&x| x as f32)
+    }
+
     /// Returns the L2 norm of the points.
     pub fn l2_norm(&self) -> Array1<f32> {
-        let dense_l2_squared = if let Some(dense) = self.dense.as_ref() {
-            Array1::from(
-                dense
-                    .axis_iter(Axis(0))
-                    .map(|point| point.norm_l2().powi(2))
-                    .collect::<Vec<_>>(),
-            )
-        } else {
-            Array1::<f32>::zeros(self.num_points())
-        };
+        self.view().l2_norm()
+    }
 
-        let sparse_l2_squared = if let Some(sparse) = self.sparse.as_ref() {
-            Array1::from(
-                sparse
-                    .outer_iterator()
-                    .map(|point| point.l2_norm().powi(2))
-                    .collect::<Vec<_>>(),
-            )
-        } else {
-            Array1::<f32>::zeros(self.num_points())
-        };
+    /// Returns the L1 norm of the points.
+    pub fn l1_norm(&self) -> Array1<f32> {
+        self.view().l1_norm()
+    }
 
-        let mut l2_norm = dense_l2_squared + sparse_l2_squared;
-        l2_norm.mapv_inplace(|v| v.sqrt());
-        l2_norm
+    /// Normalizes all points by their L1 norm, so that the absolute values of the coordinates of
+    /// every point sum to `1`, and modifies the `PointSet` in place. Points whose norm is at most
+    /// `NORM_EPSILON` (`1e-12`) are left untouched.
+    pub fn l1_normalize_inplace(&mut self) {
+        let norms = self.l1_norm();
+        if let Some(dense) = self.dense.as_mut() {
+            zip_for_each!(
+                Zip::from(norms.view()).and(dense.axis_iter_mut(Axis(0))),
+                |&norm, mut point| {
+                    if norm > NORM_EPSILON {
+                        point.mapv_inplace(|x| x / norm);
+                    }
+                }
+            );
+        }
+        if let Some(sparse) = self.sparse.as_mut() {
+            zip(norms.iter(), sparse.outer_iterator_mut())
+                .filter(|(&norm, _)| norm > NORM_EPSILON)
+                .for_each(|(&norm, mut point)| {
+                    point.map_inplace(|&x| x / norm);
+                });
+        }
     }
 
-    /// Normalizes all points by their L2 norm and modifies the `PointSet` in place.
+    /// Normalizes all points by their L2 norm and modifies the `PointSet` in place. Points whose
+    /// norm is at most `NORM_EPSILON` (`1e-12`) are left untouched.
     pub fn l2_normalize_inplace(&mut self) {
+        self.l2_normalize_returning_norms();
+    }
+
+    /// Normalizes all points by their L2 norm, modifies the `PointSet` in place, and returns the
+    /// L2 norm of the points prior to normalization.
+    ///
+    /// The norms allow the original points to be recovered or, for example, to augment points
+    /// with an extra dimension that encodes their norm. As in `l2_normalize_inplace`, points
+    /// whose norm is at most `NORM_EPSILON` are left untouched.
+    pub fn l2_normalize_returning_norms(&mut self) -> Array1<f32> {
         let norms = self.l2_norm();
         if let Some(dense) = self.dense.as_mut() {
-            Zip::from(norms.view())
-                .and(dense.axis_iter_mut(Axis(0)))
-                .par_for_each(|&norm, mut point| {
-                    point.mapv_inplace(|x| x / norm);
+            zip_for_each!(
+                Zip::from(norms.view()).and(dense.axis_iter_mut(Axis(0))),
+                |&norm, mut point| {
+                    if norm > NORM_EPSILON {
+                        point.mapv_inplace(|x| x / norm);
+                    }
+                }
+            );
+        }
+        if let Some(sparse) = self.sparse.as_mut() {
+            zip(norms.iter(), sparse.outer_iterator_mut())
+                .filter(|(&norm, _)| norm > NORM_EPSILON)
+                .for_each(|(&norm, mut point)| {
+                    point.map_inplace(|&x| x / norm);
                 });
         }
+        norms
+    }
+
+    /// Scales down every point whose L2 norm exceeds `max_norm` so that its norm is exactly
+    /// `max_norm`, and modifies the `PointSet` in place. Points with a smaller norm are left
+    /// untouched.
+    ///
+    /// The norm of a point spans both its dense and sparse sub-vectors, so both are scaled by the
+    /// same factor.
+    pub fn clip_norms_inplace(&mut self, max_norm: f32) {
+        let scales = self.l2_norm().mapv(|norm| {
+            if norm > max_norm {
+                max_norm / norm
+            } else {
+                1_f32
+            }
+        });
+        if let Some(dense) = self.dense.as_mut() {
+            zip_for_each!(
+                Zip::from(scales.view()).and(dense.axis_iter_mut(Axis(0))),
+                |&scale, mut point| {
+                    if scale < 1_f32 {
+                        point.mapv_inplace(|x| x * scale);
+                    }
+                }
+            );
+        }
         if let Some(sparse) = self.sparse.as_mut() {
-            zip(norms.iter(), sparse.outer_iterator_mut()).for_each(|(&norm, mut point)| {
-                point.map_inplace(|&x| x / norm);
+            zip(scales.iter(), sparse.outer_iterator_mut())
+                .filter(|(&scale, _)| scale < 1_f32)
+                .for_each(|(&scale, mut point)| {
+                    point.map_inplace(|&x| x * scale);
+                });
+        }
+    }
+}
+
+impl PointSet<f32> {
+    /// Generates a dense point set of `n` points in `dims` dimensions, with values drawn
+    /// uniformly at random from `[-1, 1)` using the given `seed`.
+    ///
+    /// This is mainly intended for tests and benchmarks.
+    pub fn random_dense(n: usize, dims: usize, seed: u64) -> PointSet<f32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let dense = Array2::random_using((n, dims), Uniform::new(-1_f32, 1_f32), &mut rng);
+        PointSet {
+            dense: Some(dense),
+            sparse: None,
+        }
+    }
+
+    /// Generates a sparse point set of `n` points in `dims` dimensions using the given `seed`.
+    ///
+    /// Every point has exactly `nnz_per_row` nonzero entries (or `dims`, if smaller) in distinct
+    /// dimensions chosen uniformly at random, with values drawn uniformly at random from
+    /// `[-1, 1)`. This is mainly intended for tests and benchmarks.
+    pub fn random_sparse(n: usize, dims: usize, nnz_per_row: usize, seed: u64) -> PointSet<f32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let nnz_per_row = min(nnz_per_row, dims);
+        let values = Uniform::new(-1_f32, 1_f32);
+
+        let mut indices = Vec::with_capacity(n * nnz_per_row);
+        let mut data = Vec::with_capacity(n * nnz_per_row);
+        for _ in 0..n {
+            let mut row = sample(&mut rng, dims, nnz_per_row).into_vec();
+            row.sort_unstable();
+            indices.extend(row);
+            data.extend((0..nnz_per_row).map(|_| values.sample(&mut rng)));
+        }
+        let indptr = (0..=n).map(|i| i * nnz_per_row).collect();
+        PointSet {
+            dense: None,
+            sparse: Some(CsMat::new((n, dims), indptr, indices, data)),
+        }
+    }
+
+    /// Returns the mean of the dense sub-vectors, with one entry per dense dimension.
+    ///
+    /// The sparse component, if any, is ignored. The centroid of a set without points is the
+    /// zero vector.
+    pub fn centroid(&self) -> Array1<f32> {
+        match self.dense.as_ref() {
+            None => Array1::zeros(0),
+            Some(dense) => dense
+                .mean_axis(Axis(0))
+                .unwrap_or_else(|| Array1::zeros(dense.ncols())),
+        }
+    }
+
+    /// Returns the mean of the dense sub-vectors and the mean of the sparse sub-vectors, each of
+    /// which is `None` if the set does not have the corresponding component. The dense mean is
+    /// the `centroid`, and the sparse mean holds only the dimensions with a non-zero mean.
+    ///
+    /// The mean of a set without points is the zero vector.
+    pub fn mean_vector(&self) -> (Option<Array1<f32>>, Option<CsVec<f32>>) {
+        let dense = self.dense.as_ref().map(|_| self.centroid());
+        let sparse = self.sparse.as_ref().map(|sparse| {
+            let mut sum = vec![0_f64; sparse.cols()];
+            sparse.iter().for_each(|(&x, (_, j))| sum[j] += x as f64);
+            let num_points = sparse.rows().max(1) as f64;
+            let (indices, data): (Vec<_>, Vec<_>) = sum
+                .iter()
+                .enumerate()
+                .filter(|(_, &total)| total != 0_f64)
+                .map(|(j, &total)| (j, (total / num_points) as f32))
+                .unzip();
+            CsVec::new(sparse.cols(), indices, data)
+        });
+        (dense, sparse)
+    }
+
+    /// Subtracts the centroid from every dense sub-vector, so that every dense dimension has
+    /// zero mean, and returns the centroid, which must be subtracted from queries too for them to
+    /// be transformed consistently. Unlike standardization, dimensions are not scaled.
+    ///
+    /// The sparse component, if any, is left untouched, as centering would make it dense.
+    pub fn center_inplace(&mut self) -> Array1<f32> {
+        let mean = self.centroid();
+        if let Some(dense) = self.dense.as_mut() {
+            *dense -= &mean;
+        }
+        mean
+    }
+
+    /// Standardizes every dense dimension to zero mean and unit variance by replacing every value
+    /// `x` with `(x - mean) / std`, where `std` is the population standard deviation of the
+    /// dimension, and returns the `(mean, std)` pair of every dimension, which must be applied to
+    /// queries too for them to be transformed consistently.
+    ///
+    /// Dimensions with zero variance are centered but not scaled, and their `std` is reported as
+    /// `1` so that the returned pairs can be applied as is.
+    ///
+    /// Returns an error, leaving the set untouched, if the set has a sparse component, which
+    /// centering would make dense, or has no dense component.
+    pub fn standardize_inplace(&mut self) -> Result<Array1<(f32, f32)>> {
+        if self.sparse.is_some() {
+            return Err(anyhow!(
+                "Standardization is not supported for point sets with a sparse component."
+            ));
+        }
+        let Some(dense) = self.dense.as_mut() else {
+            return Err(anyhow!("Standardization requires a dense component."));
+        };
+        let mean = dense
+            .mean_axis(Axis(0))
+            .unwrap_or_else(|| Array1::zeros(dense.ncols()));
+        let std = dense
+            .std_axis(Axis(0), 0_f32)
+            .mapv(|std| if std > 0_f32 { std } else { 1_f32 });
+        zip_for_each!(
+            Zip::from(dense.axis_iter_mut(Axis(1)))
+                .and(mean.view())
+                .and(std.view()),
+            |mut column, &mean, &std| {
+                column.mapv_inplace(|x| (x - mean) / std);
+            }
+        );
+        Ok(zip(mean, std).collect())
+    }
+
+    /// Selects `k` points as initial centroids for k-means clustering using k-means++ seeding
+    /// (Arthur and Vassilvitskii) with the given `seed`, and returns them as the rows of a
+    /// matrix.
+    ///
+    /// The first centroid is chosen uniformly at random. Every subsequent centroid is chosen
+    /// with probability proportional to the squared Euclidean distance of a point to its closest
+    /// centroid chosen so far. Only the dense component is considered; the sparse component, if
+    /// any, is ignored. If the set has fewer than `k` points, only `num_points` centroids are
+    /// returned.
+    pub fn kmeans_seed(&self, k: usize, seed: u64) -> Array2<f32> {
+        let Some(dense) = self.dense.as_ref() else {
+            return Array2::zeros((0, 0));
+        };
+        let k = k.min(dense.nrows());
+        let mut centroids = Array2::<f32>::zeros((k, dense.ncols()));
+        if k == 0 {
+            return centroids;
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut id = rng.gen_range(0..dense.nrows());
+        let mut min_distances = Array1::<f64>::from_elem(dense.nrows(), f64::INFINITY);
+        for i in 0..k {
+            let centroid = dense.row(id);
+            centroids.row_mut(i).assign(&centroid);
+            zip_for_each!(
+                Zip::from(&mut min_distances).and(dense.axis_iter(Axis(0))),
+                |min_distance, point| {
+                    let distance = point
+                        .iter()
+                        .zip(centroid.iter())
+                        .map(|(x, c)| (*x as f64 - *c as f64).powi(2))
+                        .sum::<f64>();
+                    *min_distance = min_distance.min(distance);
+                }
+            );
+
+            // Once every point coincides with a centroid, fall back to uniform sampling.
+            let total = min_distances.sum();
+            id = if total > 0_f64 {
+                let mut target = rng.gen_range(0_f64..total);
+                min_distances
+                    .iter()
+                    .position(|&distance| {
+                        target -= distance;
+                        target < 0_f64
+                    })
+                    .unwrap_or(dense.nrows() - 1)
+            } else {
+                rng.gen_range(0..dense.nrows())
+            };
+        }
+        centroids
+    }
+}
+
+impl PointSet<f32> {
+    /// Builds an index from the hash of every dense sub-vector to the ids of the points that
+    /// have a sub-vector with that hash, in increasing order. Returns an empty map if this set has
+    /// no dense component.
+    ///
+    /// Hashes are computed from the bit patterns of the values with a hash function that is
+    /// stable across runs and platforms, so the index may be stored. Two sub-vectors hash equally
+    /// if they are bitwise equal, meaning `0.0` and `-0.0` differ while identical NaNs match.
+    /// Distinct sub-vectors may still collide, which is why every hash maps to a list of ids:
+    /// candidates must be compared to the query vector to confirm a match, as `find_row` does.
+    ///
+    /// Sparse sub-vectors are ignored.
+    pub fn build_lookup(&self) -> HashMap<u64, Vec<usize>> {
+        let mut lookup = HashMap::<u64, Vec<usize>>::new();
+        if let Some(dense) = self.dense.as_ref() {
+            dense.outer_iter().enumerate().for_each(|(id, point)| {
+                lookup.entry(row_hash(point)).or_default().push(id);
             });
         }
+        lookup
+    }
+
+    /// Returns the ids, in increasing order, of the points whose dense sub-vector is bitwise equal
+    /// to `row`. See `build_lookup` for the semantics of equality. Sparse sub-vectors are ignored.
+    ///
+    /// This scans the entire set; to look up many vectors, use `build_lookup` instead.
+    pub fn find_row(&self, row: ArrayView1<f32>) -> Vec<usize> {
+        let Some(dense) = self.dense.as_ref() else {
+            return vec![];
+        };
+        if dense.ncols() != row.len() {
+            return vec![];
+        }
+        dense
+            .outer_iter()
+            .enumerate()
+            .filter(|(_, point)| zip(point, row).all(|(x, y)| x.to_bits() == y.to_bits()))
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+/// Hashes the bit patterns of the values in `row` with 64-bit FNV-1a, which, unlike the default
+/// hasher of the standard library, is guaranteed to be stable.
+fn row_hash(row: ArrayView1<f32>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    row.iter()
+        .flat_map(|x| x.to_bits().to_le_bytes())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+impl PointSet<f32> {
+    /// Applies the standard reduction of Maximum Inner Product Search to Euclidean search to the
+    /// data points in this set. Returns the transformed set along with the maximum L2 norm of
+    /// the points in this set, `M`.
+    ///
+    /// Every point `x` is augmented with an extra dense dimension whose value is
+    /// `sqrt(M^2 - ||x||^2)`, so that all transformed points have the same norm `M`. Queries must
+    /// be transformed with `augment_query`. The Euclidean nearest neighbors of a transformed
+    /// query among the transformed points are then the maximum inner product neighbors of the
+    /// original query among the original points.
+    ///
+    /// The extra dimension is appended to the dense component; if this set has no dense
+    /// component, a dense component with a single dimension is created.
+    pub fn mips_to_l2_transform(&self) -> (PointSet<f32>, f32) {
+        let norms = self.l2_norm();
+        let max_norm = norms.fold(0_f32, |acc, &norm| acc.max(norm));
+        let extra = norms.mapv(|norm| (max_norm.powi(2) - norm.powi(2)).max(0_f32).sqrt());
+        (self.with_extra_dense_dimension(extra), max_norm)
+    }
+
+    /// Transforms a set of queries to be used against data points that were transformed with
+    /// `mips_to_l2_transform` by appending an extra dense dimension whose value is `0`.
+    pub fn augment_query(&self) -> PointSet<f32> {
+        self.with_extra_dense_dimension(Array1::zeros(self.num_points()))
+    }
+
+    /// Reduces the dimensionality of the dense sub-vectors to `target_dims` by multiplying them
+    /// with a random Gaussian matrix generated with the given `seed`. Returns the projected set
+    /// along with the projection matrix, of shape `[num_dense_dimensions, target_dims]`, with
+    /// which queries must be projected using `project_dense`.
+    ///
+    /// Entries of the matrix are drawn independently from a normal distribution with mean `0` and
+    /// variance `1 / target_dims`, so that, by the Johnson-Lindenstrauss lemma, distances and
+    /// inner products are approximately preserved with high probability. Unlike PCA, the
+    /// projection does not depend on the data and does not seek the directions of largest
+    /// variance.
+    ///
+    /// The sparse component, if any, is left untouched.
+    pub fn random_projection(&self, target_dims: usize, seed: u64) -> (PointSet<f32>, Array2<f32>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let std_dev = 1_f32 / (target_dims.max(1) as f32).sqrt();
+        let projection = Array2::random_using(
+            (self.num_dense_dimensions(), target_dims),
+            Normal::new(0_f32, std_dev).unwrap(),
+            &mut rng,
+        );
+        let projected = self
+            .project_dense(&projection)
+            .expect("The projection matrix matches the dense dimensions.");
+        (projected, projection)
+    }
+
+    /// Multiplies the dense sub-vectors with the given `projection` matrix, as returned by
+    /// `random_projection`, and returns a copy of this set with the projected dense component.
+    /// The sparse component, if any, is left untouched, as is a set without a dense component.
+    ///
+    /// Returns an error if the number of rows of `projection` does not match the number of dense
+    /// dimensions of this set.
+    pub fn project_dense(&self, projection: &Array2<f32>) -> Result<PointSet<f32>> {
+        let Some(dense) = self.dense.as_ref() else {
+            return Ok(self.clone());
+        };
+        if dense.ncols() != projection.nrows() {
+            return Err(anyhow!(
+                "Points have {} dense dimensions but the projection expects {}.",
+                dense.ncols(),
+                projection.nrows()
+            ));
+        }
+        Ok(PointSet {
+            dense: Some(dense.dot(projection)),
+            sparse: self.sparse.clone(),
+        })
+    }
+
+    /// Returns a copy of this set where the dense component has an extra last dimension with the
+    /// given values.
+    fn with_extra_dense_dimension(&self, values: Array1<f32>) -> PointSet<f32> {
+        let num_dimensions = self.num_dense_dimensions();
+        let mut dense = Array2::<f32>::zeros((self.num_points(), num_dimensions + 1));
+        if let Some(existing) = self.dense.as_ref() {
+            dense.slice_mut(s![.., ..num_dimensions]).assign(existing);
+        }
+        dense.column_mut(num_dimensions).assign(&values);
+        PointSet {
+            dense: Some(dense),
+            sparse: self.sparse.clone(),
+        }
+    }
+}
+
+/// Summarizes the point set stored in `group` from HDF5 metadata alone, without reading the
+/// vectors.
+pub(crate) fn inspect_point_set(group: &Group) -> Result<PointSetInfo> {
+    let label = PointSet::<f32>::label();
+    let mut info = PointSetInfo::default();
+
+    if let Ok(dataset) = group.dataset(format!("{}-{}", label, DENSE).as_str()) {
+        let shape = dataset.shape();
+        if shape.len() != 2 {
+            return Err(anyhow!(
+                "Corrupt shape for dense dataset '{}'",
+                group.name()
+            ));
+        }
+        info.num_points = shape[0];
+        info.dense_dimensions = Some(shape[1]);
+        info.dtype = dataset.dtype()?.to_descriptor()?.to_string();
+    }
+
+    if let Ok(sparse_group) = group.group(format!("{}-{}", label, SPARSE).as_str()) {
+        let shape = sparse_group.attr(SPARSE_SHAPE)?.read_raw::<usize>()?;
+        if shape.len() != 2 {
+            return Err(anyhow!(
+                "Corrupt shape for sparse dataset '{}'",
+                group.name()
+            ));
+        }
+        info.num_points = shape[0];
+        info.sparse_dimensions = Some(shape[1]);
+        info.dtype = sparse_group
+            .dataset(SPARSE_DATA)?
+            .dtype()?
+            .to_descriptor()?
+            .to_string();
+    }
+
+    if info.dense_dimensions.is_none() && info.sparse_dimensions.is_none() {
+        return Err(anyhow!("No point set found in '{}'", group.name()));
+    }
+    Ok(info)
+}
+
+/// Adds external links to `group` that point to the components of the point set stored at the
+/// root of the HDF5 file `file_name`, as summarized by `info`, so that reading the point set from
+/// `group` reads it from that file.
+pub(crate) fn link_external_point_set(
+    group: &Group,
+    file_name: &str,
+    info: &PointSetInfo,
+) -> Result<()> {
+    let label = PointSet::<f32>::label();
+    let components = [
+        (info.dense_dimensions, DENSE),
+        (info.sparse_dimensions, SPARSE),
+    ];
+    for (_, component) in components.iter().filter(|(dims, _)| dims.is_some()) {
+        let name = format!("{}-{}", label, component);
+        group.link_external(file_name, format!("/{}", name).as_str(), name.as_str())?;
     }
+    Ok(())
 }
 
 impl<DataType: Clone + H5Type> Hdf5Serialization for PointSet<DataType> {
@@ -255,71 +1282,575 @@ impl<DataType: Clone + H5Type> Hdf5Serialization for PointSet<DataType> {
                     ));
                 }
 
-                let indptr = sparse_group.dataset(SPARSE_INDPTR)?.read_raw::<usize>()?;
-                let indices = sparse_group.dataset(SPARSE_INDICES)?.read_raw::<usize>()?;
-                let data: Vec<DataType> =
-                    sparse_group.dataset(SPARSE_DATA)?.read_raw::<DataType>()?;
-                Some(CsMat::new((shape[0], shape[1]), indptr, indices, data))
-            }
-            Err(_) => None,
-        };
+                let indptr = sparse_group.dataset(SPARSE_INDPTR)?.read_raw::<usize>()?;
+                let indices = sparse_group.dataset(SPARSE_INDICES)?.read_raw::<usize>()?;
+                let data: Vec<DataType> =
+                    sparse_group.dataset(SPARSE_DATA)?.read_raw::<DataType>()?;
+                let sparse = CsMat::new_from_unsorted((shape[0], shape[1]), indptr, indices, data)
+                    .map_err(|(_, _, _, error)| {
+                        anyhow!("Corrupt sparse dataset '{}': {}", group.name(), error)
+                    })?;
+                Some(sparse)
+            }
+            Err(_) => None,
+        };
+
+        Ok(PointSet { dense, sparse })
+    }
+
+    fn label() -> String {
+        "point-set".to_string()
+    }
+}
+
+/// Converts a sparse matrix in CSC storage to CSR storage, keeping the column indices of each
+/// row sorted.
+fn csc_to_csr<DataType: Clone>(csc: &CsMat<DataType>) -> CsMat<DataType> {
+    let mut entries = csc
+        .iter()
+        .map(|(value, (row, col))| (row, col, value.clone()))
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|&(row, col, _)| (row, col));
+
+    let mut indptr = vec![0_usize; csc.rows() + 1];
+    entries.iter().for_each(|&(row, _, _)| indptr[row + 1] += 1);
+    (0..csc.rows()).for_each(|row| indptr[row + 1] += indptr[row]);
+
+    let (indices, data): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .map(|(_, col, value)| (col, value))
+        .unzip();
+    CsMat::new(csc.shape(), indptr, indices, data)
+}
+
+/// Summary of the number of non-zero entries in the sparse component of a point set. Statistics
+/// of a set without points are `0`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SparsityStats {
+    /// Number of stored entries.
+    pub nnz: usize,
+    /// Fraction of the entries of the matrix that are stored, `nnz / (rows * cols)`.
+    pub density: f32,
+    pub mean_nnz_per_row: f32,
+    /// Middle value, or the mean of the two middle values if the number of rows is even.
+    pub median_nnz_per_row: f32,
+    pub max_nnz_per_row: usize,
+}
+
+impl<DataType: Clone> Display for PointSet<DataType> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let dense = match self.dense.as_ref() {
+            None => "is empty".to_string(),
+            Some(dense) => {
+                format!("has shape [{}, {}]", dense.shape()[0], dense.shape()[1])
+            }
+        };
+
+        let sparse = match self.sparse.as_ref() {
+            None => "is empty".to_string(),
+            Some(sparse) => {
+                format!("has shape [{}, {}]", sparse.rows(), sparse.cols())
+            }
+        };
+
+        write!(f, "Dense set {}; Sparse set {}", dense, sparse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::point_set::{row_hash, PointSet};
+    use crate::{Hdf5Serialization, Metric};
+    use approx_eq::assert_approx_eq;
+    use hdf5::File;
+    use ndarray::{arr2, Array1, Array2, Axis};
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+    use serde::Serialize;
+    use sprs::{CompressedStorage, CsMat, TriMat};
+    use std::iter::zip;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_new() {
+        let dense = Array2::<f32>::eye(5);
+
+        let mut sparse = TriMat::new((4, 4));
+        sparse.add_triplet(0, 0, 3.0_f32);
+        sparse.add_triplet(1, 2, 2.0);
+        sparse.add_triplet(3, 0, -2.0);
+        let sparse: CsMat<_> = sparse.to_csr();
+
+        assert!(PointSet::<f32>::new(None, None).is_err());
+        assert!(PointSet::new(Some(dense.clone()), None).is_ok());
+        assert!(PointSet::new(None, Some(sparse.clone())).is_ok());
+        assert!(PointSet::new(Some(dense.clone()), Some(sparse.clone())).is_err());
+
+        let dense = Array2::<f32>::eye(4);
+        assert!(PointSet::new(Some(dense.clone()), Some(sparse.clone())).is_ok());
+    }
+
+    #[test]
+    fn test_random() {
+        let point_set = PointSet::random_dense(20, 8, 3);
+        assert_eq!(point_set.num_points(), 20);
+        assert_eq!(point_set.num_dimensions(), 8);
+        assert!(point_set.get_sparse().is_none());
+        assert_eq!(point_set, PointSet::random_dense(20, 8, 3));
+        assert_ne!(point_set, PointSet::random_dense(20, 8, 4));
+
+        let point_set = PointSet::random_sparse(20, 100, 5, 3);
+        let sparse = point_set.get_sparse().unwrap();
+        assert!(point_set.get_dense().is_none());
+        assert_eq!(sparse.shape(), (20, 100));
+        assert_eq!(sparse.nnz(), 100);
+        assert!(sparse.outer_iterator().all(|row| row.nnz() == 5));
+        assert_eq!(point_set, PointSet::random_sparse(20, 100, 5, 3));
+
+        let point_set = PointSet::random_sparse(4, 3, 5, 3);
+        assert_eq!(point_set.get_sparse().unwrap().nnz(), 12);
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let point_set = PointSet::random_dense(10, 4, 0);
+        let mut perturbed = point_set.clone();
+        perturbed.dense.as_mut().unwrap()[[3, 2]] += 0.01;
+        assert!(point_set.approx_eq(&perturbed, 0.02));
+        assert!(!point_set.approx_eq(&perturbed, 0.001));
+        assert_ne!(point_set, perturbed);
+
+        let sparse = PointSet::random_sparse(10, 20, 3, 0);
+        let mut perturbed = sparse.clone();
+        perturbed.sparse.as_mut().unwrap().data_mut()[5] -= 0.01;
+        assert!(sparse.approx_eq(&perturbed, 0.02));
+        assert!(!sparse.approx_eq(&perturbed, 0.001));
+        assert!(!sparse.approx_eq(&PointSet::random_sparse(10, 20, 3, 1), 0.02));
+
+        assert!(!point_set.approx_eq(&point_set.select(&[0, 1]), 1_f32));
+        assert!(!point_set.approx_eq(&sparse, 1_f32));
+    }
+
+    #[test]
+    fn test_f64_conversion() {
+        let points = PointSet::random_dense(10, 4, 3);
+        let sparse = PointSet::random_sparse(10, 20, 3, 5);
+        let points =
+            PointSet::new(points.get_dense().cloned(), sparse.get_sparse().cloned()).unwrap();
+
+        let converted = points.to_f64();
+        assert_eq!(converted.num_points(), 10);
+        assert_eq!(converted.num_dense_dimensions(), 4);
+        assert_eq!(converted.num_sparse_dimensions(), 20);
+        assert_eq!(
+            converted.get_sparse().unwrap().indices(),
+            points.get_sparse().unwrap().indices()
+        );
+        assert_eq!(PointSet::from_f64(&converted), points);
+
+        let precise = PointSet::<f64>::new(Some(arr2(&[[0.1_f64, 1e-300]])), None).unwrap();
+        let rounded = PointSet::from_f64(&precise);
+        assert_eq!(rounded.get_dense().unwrap(), arr2(&[[0.1_f32, 0_f32]]));
+    }
+
+    #[test]
+    fn test_cast() {
+        let sparse = CsMat::new(
+            (2, 5),
+            vec![0, 2, 3],
+            vec![1, 4, 0],
+            vec![1.5_f64, 1e-300, -2.25],
+        );
+        let points =
+            PointSet::new(Some(arr2(&[[0.5_f64, 3.0], [-1.0, 2e40]])), Some(sparse)).unwrap();
+
+        let converted = points.cast_with(|&x| x as f32);
+        assert_eq!(converted.num_points(), 2);
+        assert_eq!(converted.num_dense_dimensions(), 2);
+        assert_eq!(converted.num_sparse_dimensions(), 5);
+        assert_eq!(
+            converted.get_dense().unwrap(),
+            arr2(&[[0.5_f32, 3.0], [-1.0, f32::INFINITY]])
+        );
+        let sparse = converted.get_sparse().unwrap();
+        assert_eq!(sparse.indptr().as_slice().unwrap(), &[0, 2, 3]);
+        assert_eq!(sparse.indices(), &[1, 4, 0]);
+        assert_eq!(sparse.data(), &[1.5_f32, 0.0, -2.25]);
+
+        let integers = PointSet::new(
+            Some(arr2(&[[1_u8, 2], [3, 255]])),
+            Some(CsMat::new((2, 3), vec![0, 1, 1], vec![2], vec![7_u8])),
+        )
+        .unwrap();
+        let widened = integers.cast::<i32>();
+        assert_eq!(widened.get_dense().unwrap(), arr2(&[[1_i32, 2], [3, 255]]));
+        assert_eq!(widened.get_sparse().unwrap().data(), &[7_i32]);
+        assert_eq!(widened.num_sparse_dimensions(), 3);
+        assert_eq!(widened.cast_with(|&x| x as u8), integers);
+    }
+
+    #[test]
+    fn test_new_checked() {
+        let mut dense = Array2::<f32>::random((5, 3), Uniform::new(0_f32, 1_f32));
+        let mut sparse = TriMat::new((5, 10));
+        sparse.add_triplet(1, 2, 1_f32);
+        sparse.add_triplet(3, 7, f32::INFINITY);
+        let sparse: CsMat<f32> = sparse.to_csr();
+
+        let points = PointSet::new_checked(Some(dense.clone()), None).unwrap();
+        assert!(!points.has_non_finite());
+        assert!(PointSet::new_checked(Some(dense.clone()), Some(sparse.clone())).is_err());
+
+        dense[[2, 1]] = f32::NAN;
+        let result = PointSet::new_checked(Some(dense.clone()), Some(sparse.clone()));
+        assert!(result.unwrap_err().to_string().contains("Point 2"));
+        let result = PointSet::new_checked(None, Some(sparse.clone()));
+        assert!(result.unwrap_err().to_string().contains("Point 3"));
+
+        let points = PointSet::new(Some(dense), Some(sparse)).unwrap();
+        assert!(points.has_non_finite());
+    }
+
+    #[test]
+    fn test_validate_finite() {
+        let mut dense = Array2::<f32>::random((5, 3), Uniform::new(0_f32, 1_f32));
+        let mut sparse = TriMat::new((5, 10));
+        sparse.add_triplet(1, 2, 1_f32);
+        sparse.add_triplet(3, 7, 2_f32);
+        let mut points = PointSet::new(Some(dense.clone()), Some(sparse.to_csr())).unwrap();
+        assert!(points.validate_finite().is_ok());
+
+        sparse.add_triplet(2, 4, f32::INFINITY);
+        points = PointSet::new(Some(dense.clone()), Some(sparse.to_csr())).unwrap();
+        let error = points.validate_finite().unwrap_err().to_string();
+        assert!(error.contains("Point 2"), "{}", error);
+        assert!(error.contains("sparse dimension 4"), "{}", error);
+
+        dense[[4, 0]] = f32::NAN;
+        dense[[2, 2]] = f32::NEG_INFINITY;
+        points = PointSet::new(Some(dense.clone()), Some(sparse.to_csr())).unwrap();
+        let error = points.validate_finite().unwrap_err().to_string();
+        assert!(error.contains("Point 2"), "{}", error);
+        assert!(error.contains("dense dimension 2"), "{}", error);
+
+        points = PointSet::new(Some(dense), None).unwrap();
+        let error = points.validate_finite().unwrap_err().to_string();
+        assert!(error.contains("Point 2"), "{}", error);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut dense = PointSet::random_dense(10, 4, 11)
+            .get_dense()
+            .unwrap()
+            .clone();
+        let duplicate = dense.row(2).to_owned();
+        dense.row_mut(7).assign(&duplicate);
+        let almost_duplicate = dense.row(5).to_owned();
+        dense.row_mut(6).assign(&almost_duplicate);
+        dense[[5, 0]] = -0_f32;
+        dense[[6, 0]] = 0_f32;
+        let points = PointSet::new(Some(dense.clone()), None).unwrap();
+
+        assert_eq!(points.find_row(duplicate.view()), vec![2, 7]);
+        assert_eq!(points.find_row(dense.row(5)), vec![5]);
+        assert_eq!(points.find_row(dense.row(6)), vec![6]);
+        assert!(points.find_row(Array1::zeros(4).view()).is_empty());
+        assert!(points.find_row(Array1::zeros(3).view()).is_empty());
+
+        let lookup = points.build_lookup();
+        assert_eq!(lookup.values().map(|ids| ids.len()).sum::<usize>(), 10);
+        let ids = &lookup[&row_hash(duplicate.view())];
+        assert!(ids.contains(&2) && ids.contains(&7));
+        for (id, point) in dense.outer_iter().enumerate() {
+            assert!(lookup[&row_hash(point)].contains(&id));
+        }
+
+        let sparse = PointSet::random_sparse(3, 10, 2, 1);
+        assert!(sparse.build_lookup().is_empty());
+        assert!(sparse.find_row(Array1::zeros(10).view()).is_empty());
+    }
+
+    #[test]
+    fn test_select_mask() {
+        let mut point_set = PointSet::random_dense(10, 4, 0);
+        point_set.sparse = PointSet::random_sparse(10, 20, 3, 0).sparse;
+
+        let mask = (0..10).map(|i| i % 2 == 0).collect::<Vec<_>>();
+        let selected = point_set.select_mask(&mask).unwrap();
+        assert_eq!(selected.num_points(), 5);
+        assert_eq!(selected, point_set.select(&[0, 2, 4, 6, 8]));
+
+        assert_eq!(point_set.select_mask(&[false; 10]).unwrap().num_points(), 0);
+        assert!(point_set.select_mask(&mask[..9]).is_err());
+    }
+
+    #[test]
+    fn test_clip_norms() {
+        let mut point_set = PointSet::random_dense(20, 8, 0);
+        point_set.sparse = PointSet::random_sparse(20, 30, 4, 0).sparse;
+        point_set
+            .dense
+            .as_mut()
+            .unwrap()
+            .row_mut(0)
+            .mapv_inplace(|x| x * 0.01);
+        let norms = point_set.l2_norm();
+        let max_norm = 1.5_f32;
+        assert!(norms.iter().any(|&norm| norm > max_norm));
+        assert!(norms.iter().any(|&norm| norm <= max_norm));
+
+        let mut clipped = point_set.clone();
+        clipped.clip_norms_inplace(max_norm);
+        zip(norms.iter(), clipped.l2_norm().iter())
+            .enumerate()
+            .for_each(|(i, (&before, &after))| {
+                assert!(after <= max_norm + 1e-5);
+                if before > max_norm {
+                    assert_approx_eq!(after as f64, max_norm as f64, 1e-4);
+                } else {
+                    assert_eq!(clipped.select(&[i]), point_set.select(&[i]));
+                }
+            });
+    }
+
+    #[test]
+    fn test_centroid() {
+        let point_set = PointSet::<f32>::new(Some(Array2::eye(4)), None).unwrap();
+        assert_eq!(point_set.centroid(), Array1::from_elem(4, 0.25_f32));
+
+        let mut sparse = TriMat::new((4, 3));
+        sparse.add_triplet(0, 0, 3.0_f32);
+        let point_set = PointSet::<f32>::new(None, Some(sparse.to_csr())).unwrap();
+        assert_eq!(point_set.centroid().len(), 0);
+    }
+
+    #[test]
+    fn test_mean_vector() {
+        let n = 8;
+        let eye = Array2::<f32>::eye(n);
+        let mut triplets = TriMat::new((n, n));
+        (0..n).for_each(|i| triplets.add_triplet(i, i, 1_f32));
+        let point_set = PointSet::new(Some(eye.clone()), Some(triplets.to_csr())).unwrap();
+
+        let (dense, sparse) = point_set.mean_vector();
+        let dense = dense.unwrap();
+        assert_eq!(dense.len(), n);
+        assert!(dense.iter().all(|&x| (x - 1_f32 / n as f32).abs() < 1e-6));
+        let sparse = sparse.unwrap();
+        assert_eq!(sparse.dim(), n);
+        assert_eq!(sparse.indices(), (0..n).collect::<Vec<_>>().as_slice());
+        assert!(sparse
+            .data()
+            .iter()
+            .all(|&x| (x - 1_f32 / n as f32).abs() < 1e-6));
+
+        let mut triplets = TriMat::new((4, 5));
+        triplets.add_triplet(0, 3, 2_f32);
+        triplets.add_triplet(2, 3, 2_f32);
+        triplets.add_triplet(3, 1, -1_f32);
+        triplets.add_triplet(1, 1, 1_f32);
+        triplets.add_triplet(1, 0, 4_f32);
+        let sparse_only = PointSet::new(None, Some(triplets.to_csr())).unwrap();
+        let (dense, sparse) = sparse_only.mean_vector();
+        assert!(dense.is_none());
+        let sparse = sparse.unwrap();
+        assert_eq!(sparse.indices(), &[0, 3]);
+        assert_eq!(sparse.data(), &[1_f32, 1_f32]);
+
+        let (dense, sparse) = PointSet::random_dense(0, 3, 0).mean_vector();
+        assert_eq!(dense.unwrap(), Array1::<f32>::zeros(3));
+        assert!(sparse.is_none());
+    }
 
-        Ok(PointSet { dense, sparse })
+    #[test]
+    fn test_center_inplace() {
+        let dense = PointSet::random_dense(100, 5, 17)
+            .get_dense()
+            .unwrap()
+            .mapv(|x| x + 3_f32);
+        let sparse = PointSet::random_sparse(100, 10, 2, 1).get_sparse().cloned();
+        let original = PointSet::new(Some(dense), sparse).unwrap();
+
+        let mut centered = original.clone();
+        let mean = centered.center_inplace();
+        assert_eq!(mean, original.centroid());
+        assert!(mean.iter().all(|&x| x > 2_f32));
+        assert!(centered.centroid().iter().all(|x| x.abs() < 1e-5));
+        assert_eq!(centered.get_sparse(), original.get_sparse());
+
+        // Queries are centered with the mean of the data points.
+        let query = &original.get_dense().unwrap().row(4) - &mean;
+        assert_eq!(query, centered.get_dense().unwrap().row(4));
     }
 
-    fn label() -> String {
-        "point-set".to_string()
+    #[test]
+    fn test_standardize_inplace() {
+        let mut dense = PointSet::random_dense(200, 4, 23)
+            .get_dense()
+            .unwrap()
+            .mapv(|x| 5_f32 * x + 2_f32);
+        dense.column_mut(3).fill(7_f32);
+        let original = PointSet::new(Some(dense), None).unwrap();
+
+        let mut standardized = original.clone();
+        let stats = standardized.standardize_inplace().unwrap();
+        assert_eq!(stats.len(), 4);
+        assert_eq!(stats[3], (7_f32, 1_f32));
+        let dense = standardized.get_dense().unwrap();
+        for column in dense.columns().into_iter().take(3) {
+            assert!(column.mean().unwrap().abs() < 1e-5);
+            assert!((column.std(0_f32) - 1_f32).abs() < 1e-4);
+        }
+        assert!(dense.column(3).iter().all(|&x| x == 0_f32));
+
+        // Queries are standardized with the statistics of the data points.
+        let (mean, std) = stats[1];
+        let expected = (original.get_dense().unwrap()[[5, 1]] - mean) / std;
+        assert_eq!(dense[[5, 1]], expected);
+
+        let mut sparse = PointSet::random_sparse(10, 5, 2, 0);
+        assert!(sparse.standardize_inplace().is_err());
+        let mut both = PointSet::new(
+            Some(Array2::<f32>::ones((10, 2))),
+            sparse.get_sparse().cloned(),
+        )
+        .unwrap();
+        assert!(both.standardize_inplace().is_err());
+        assert_eq!(both.get_dense().unwrap(), &Array2::<f32>::ones((10, 2)));
     }
-}
 
-impl<DataType: Clone> Display for PointSet<DataType> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let dense = match self.dense.as_ref() {
-            None => "is empty".to_string(),
-            Some(dense) => {
-                format!("has shape [{}, {}]", dense.shape()[0], dense.shape()[1])
-            }
-        };
+    #[test]
+    fn test_random_projection() {
+        let points = PointSet::new(
+            PointSet::random_dense(50, 64, 2).get_dense().cloned(),
+            PointSet::random_sparse(50, 100, 3, 4).get_sparse().cloned(),
+        )
+        .unwrap();
 
-        let sparse = match self.sparse.as_ref() {
-            None => "is empty".to_string(),
-            Some(sparse) => {
-                format!("has shape [{}, {}]", sparse.rows(), sparse.cols())
-            }
-        };
+        let (projected, projection) = points.random_projection(16, 42);
+        assert_eq!(projection.shape(), &[64, 16]);
+        assert_eq!(projected.num_points(), 50);
+        assert_eq!(projected.num_dense_dimensions(), 16);
+        assert_eq!(projected.get_sparse(), points.get_sparse());
 
-        write!(f, "Dense set {}; Sparse set {}", dense, sparse)
+        let (again, same_projection) = points.random_projection(16, 42);
+        assert_eq!(again, projected);
+        assert_eq!(same_projection, projection);
+        let (_, other_projection) = points.random_projection(16, 43);
+        assert_ne!(other_projection, projection);
+
+        let queries = points.select(&[3, 7]);
+        assert_eq!(
+            queries.project_dense(&projection).unwrap(),
+            projected.select(&[3, 7])
+        );
+        assert!(PointSet::random_dense(2, 8, 0)
+            .project_dense(&projection)
+            .is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::types::point_set::PointSet;
-    use crate::Hdf5Serialization;
-    use approx_eq::assert_approx_eq;
-    use hdf5::File;
-    use ndarray::{Array2, Axis};
-    use sprs::{CsMat, TriMat};
-    use std::iter::zip;
-    use tempdir::TempDir;
+    #[test]
+    fn test_kmeans_seed() {
+        // Three well-separated clusters of ten points each.
+        let mut dense = Array2::random((30, 2), Uniform::new(0_f32, 0.1));
+        dense
+            .axis_iter_mut(Axis(0))
+            .enumerate()
+            .for_each(|(i, mut point)| point[0] += 100_f32 * (i / 10) as f32);
+        let point_set = PointSet::new(Some(dense.clone()), None).unwrap();
+
+        let centroids = point_set.kmeans_seed(3, 7);
+        assert_eq!(centroids.shape(), &[3, 2]);
+        assert_eq!(centroids, point_set.kmeans_seed(3, 7));
+        let mut clusters = centroids
+            .axis_iter(Axis(0))
+            .map(|centroid| {
+                assert!(dense.axis_iter(Axis(0)).any(|point| point == centroid));
+                (centroid[0] / 100_f32).round() as usize
+            })
+            .collect::<Vec<_>>();
+        clusters.sort();
+        assert_eq!(clusters, vec![0, 1, 2]);
+
+        assert_eq!(point_set.kmeans_seed(50, 7).nrows(), 30);
+    }
 
     #[test]
-    fn test_new() {
-        let dense = Array2::<f32>::eye(5);
+    fn test_sample() {
+        let point_set = PointSet::new(
+            PointSet::random_dense(50, 3, 0).get_dense().cloned(),
+            PointSet::random_sparse(50, 8, 2, 0).get_sparse().cloned(),
+        )
+        .unwrap();
+
+        let subset = point_set.sample(10, 7).unwrap();
+        assert_eq!(subset.num_points(), 10);
+        assert_eq!(subset.num_dense_dimensions(), 3);
+        assert_eq!(subset.num_sparse_dimensions(), 8);
+        assert_eq!(subset, point_set.sample(10, 7).unwrap());
+        assert_ne!(subset, point_set.sample(10, 8).unwrap());
+
+        // Sampled points keep their relative order.
+        let ids = subset
+            .get_dense()
+            .unwrap()
+            .rows()
+            .into_iter()
+            .map(|row| point_set.find_row(row)[0])
+            .collect::<Vec<_>>();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!(point_set.sample(50, 0).unwrap(), point_set);
+        assert_eq!(point_set.sample(0, 0).unwrap().num_points(), 0);
+        assert!(point_set.sample(51, 0).is_err());
+    }
 
-        let mut sparse = TriMat::new((4, 4));
-        sparse.add_triplet(0, 0, 3.0_f32);
+    #[test]
+    fn test_shuffle() {
+        let dense = Array2::random((20, 3), Uniform::new(0.0_f32, 1.0));
+        let mut sparse = TriMat::new((20, 5));
+        (0..20).for_each(|i| sparse.add_triplet(i, i % 5, i as f32));
+        let point_set = PointSet::new(Some(dense), Some(sparse.to_csr())).unwrap();
+
+        let (shuffled, permutation) = point_set.shuffle(42);
+        assert_ne!(shuffled, point_set);
+        assert_eq!(
+            point_set.shuffle(42),
+            (shuffled.clone(), permutation.clone())
+        );
+
+        // The permutation is a rearrangement of the identity that reproduces the shuffle.
+        let mut sorted = permutation.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+        assert_eq!(point_set.select(&permutation), shuffled);
+
+        let mut inverse = vec![0; permutation.len()];
+        permutation
+            .iter()
+            .enumerate()
+            .for_each(|(new_id, &id)| inverse[id] = new_id);
+        assert_eq!(shuffled.select(&inverse), point_set);
+    }
+
+    #[test]
+    fn test_new_csc() {
+        let mut sparse = TriMat::new((4, 3));
+        sparse.add_triplet(0, 2, 3.0_f32);
+        sparse.add_triplet(0, 0, 1.0);
         sparse.add_triplet(1, 2, 2.0);
-        sparse.add_triplet(3, 0, -2.0);
-        let sparse: CsMat<_> = sparse.to_csr();
+        sparse.add_triplet(3, 1, -2.0);
+        let csr: CsMat<_> = sparse.to_csr();
+        let csc: CsMat<_> = sparse.to_csc();
 
-        assert!(PointSet::<f32>::new(None, None).is_err());
-        assert!(PointSet::new(Some(dense.clone()), None).is_ok());
-        assert!(PointSet::new(None, Some(sparse.clone())).is_ok());
-        assert!(PointSet::new(Some(dense.clone()), Some(sparse.clone())).is_err());
+        let point_set = PointSet::new(None, Some(csc)).unwrap();
+        assert!(point_set.get_sparse().unwrap().is_csr());
+        assert_eq!(point_set.get_sparse().unwrap(), &csr);
 
-        let dense = Array2::<f32>::eye(4);
-        assert!(PointSet::new(Some(dense.clone()), Some(sparse.clone())).is_ok());
+        let subset = point_set.select(&[3, 0]);
+        let expected = PointSet::new(None, Some(csr)).unwrap().select(&[3, 0]);
+        assert_eq!(subset, expected);
     }
 
     #[test]
@@ -359,6 +1890,261 @@ mod tests {
         assert_eq!(subset.get_sparse().unwrap(), &sparse_subset);
     }
 
+    #[test]
+    fn test_dense_and_sparse_only() {
+        let dense = Array2::<f32>::eye(10);
+
+        let mut sparse = TriMat::new((10, 4));
+        sparse.add_triplet(0, 0, 3.0_f32);
+        sparse.add_triplet(1, 2, 2.0);
+        sparse.add_triplet(3, 0, -2.0);
+        let sparse: CsMat<_> = sparse.to_csr();
+
+        let point_set = PointSet::new(Some(dense.clone()), Some(sparse.clone())).unwrap();
+
+        let dense_only = point_set.dense_only().unwrap();
+        assert_eq!(dense_only.get_dense().unwrap(), &dense);
+        assert!(dense_only.get_sparse().is_none());
+        assert_eq!(dense_only.num_points(), 10);
+
+        let sparse_only = point_set.sparse_only().unwrap();
+        assert!(sparse_only.get_dense().is_none());
+        assert_eq!(sparse_only.get_sparse().unwrap(), &sparse);
+        assert_eq!(sparse_only.num_points(), 10);
+
+        assert!(dense_only.sparse_only().is_none());
+        assert!(sparse_only.dense_only().is_none());
+    }
+
+    #[test]
+    fn test_get_point() {
+        let dense = PointSet::random_dense(5, 3, 0);
+        let point = dense.get_point(2).unwrap();
+        assert_eq!(
+            point.get_dense().unwrap(),
+            dense.get_dense().unwrap().row(2)
+        );
+        assert!(point.get_sparse().is_none());
+        assert!(dense.get_point(5).is_err());
+
+        let mut triplets = TriMat::new((3, 4));
+        triplets.add_triplet(0, 3, 1.0_f32);
+        triplets.add_triplet(2, 0, -2.0);
+        triplets.add_triplet(2, 2, 0.5);
+        let sparse = PointSet::new(None, Some(triplets.to_csr())).unwrap();
+        let point = sparse.get_point(2).unwrap();
+        assert!(point.get_dense().is_none());
+        let sparse_row = point.get_sparse().unwrap();
+        assert_eq!(sparse_row.dim(), 4);
+        assert_eq!(sparse_row.indices(), &[0, 2]);
+        assert_eq!(sparse_row.data(), &[-2.0, 0.5]);
+        assert_eq!(sparse.get_point(1).unwrap().get_sparse().unwrap().nnz(), 0);
+        assert!(sparse.get_point(3).is_err());
+
+        let both = PointSet::new(
+            Some(Array2::from_shape_fn((3, 2), |(i, j)| (i * 2 + j) as f32)),
+            sparse.get_sparse().cloned(),
+        )
+        .unwrap();
+        let point = both.get_point(0).unwrap();
+        assert_eq!(point.get_dense().unwrap().to_vec(), vec![0_f32, 1_f32]);
+        assert_eq!(point.get_sparse().unwrap().indices(), &[3]);
+        assert!(both.get_point(3).is_err());
+    }
+
+    #[test]
+    fn test_points() {
+        let point_set = PointSet::new(
+            PointSet::random_dense(6, 3, 0).get_dense().cloned(),
+            PointSet::random_sparse(6, 8, 2, 0).get_sparse().cloned(),
+        )
+        .unwrap();
+        assert_eq!(point_set.points().count(), point_set.num_points());
+        for (i, point) in point_set.points().enumerate() {
+            assert_eq!(point.num_dense_dimensions(), 3);
+            assert_eq!(point.num_sparse_dimensions(), 8);
+            assert_eq!(
+                point.get_dense().unwrap(),
+                point_set.get_dense().unwrap().row(i)
+            );
+            assert_eq!(point.get_sparse().unwrap().nnz(), 2);
+        }
+
+        let sparse = point_set.sparse_only().unwrap();
+        assert!(sparse.points().all(|point| point.get_dense().is_none()
+            && point.num_dense_dimensions() == 0
+            && point.num_sparse_dimensions() == 8));
+        assert_eq!(PointSet::random_dense(0, 3, 0).points().count(), 0);
+    }
+
+    #[test]
+    fn test_select_dimensions() {
+        let dense = Array2::from_shape_fn((3, 4), |(i, j)| (i * 4 + j) as f32);
+        let mut triplets = TriMat::new((3, 5));
+        triplets.add_triplet(0, 0, 1.0_f32);
+        triplets.add_triplet(0, 3, 2.0);
+        triplets.add_triplet(1, 4, 3.0);
+        triplets.add_triplet(2, 1, 4.0);
+        triplets.add_triplet(2, 3, 5.0);
+        let point_set = PointSet::new(Some(dense.clone()), Some(triplets.to_csr())).unwrap();
+
+        let projected = point_set.select_dimensions(&[3, 1], &[3, 0, 3]).unwrap();
+        assert_eq!(
+            projected.get_dense().unwrap(),
+            arr2(&[[3_f32, 1.0], [7.0, 5.0], [11.0, 9.0]])
+        );
+        let sparse = projected.get_sparse().unwrap();
+        assert_eq!(sparse.shape(), (3, 3));
+        assert_eq!(sparse.indptr().raw_storage(), &[0, 3, 3, 5]);
+        assert_eq!(sparse.indices(), &[0, 1, 2, 0, 2]);
+        assert_eq!(sparse.data(), &[2.0, 1.0, 2.0, 5.0, 5.0]);
+
+        let dense_only = point_set.select_dimensions(&[0, 1, 2, 3], &[]).unwrap();
+        assert_eq!(dense_only, point_set.dense_only().unwrap());
+        let sparse_only = point_set.select_dimensions(&[], &[0, 1, 2, 3, 4]).unwrap();
+        assert_eq!(sparse_only, point_set.sparse_only().unwrap());
+
+        assert!(point_set.select_dimensions(&[4], &[0]).is_err());
+        assert!(point_set.select_dimensions(&[0], &[5]).is_err());
+        assert!(point_set.select_dimensions(&[], &[]).is_err());
+        assert!(dense_only.select_dimensions(&[0], &[0]).is_err());
+    }
+
+    #[test]
+    fn test_to_sparse_and_to_dense() {
+        let dense = arr2(&[[0.0_f32, 0.5, 0.0], [-2.0, 0.0, 0.05], [0.0, 0.0, 0.0]]);
+        let point_set = PointSet::new(Some(dense.clone()), None).unwrap();
+
+        let sparse = point_set.to_sparse(0.0);
+        assert!(sparse.get_dense().is_none());
+        let matrix = sparse.get_sparse().unwrap();
+        assert_eq!(matrix.shape(), (3, 3));
+        assert_eq!(matrix.indptr().raw_storage(), &[0, 1, 3, 3]);
+        assert_eq!(matrix.indices(), &[1, 0, 2]);
+        assert_eq!(matrix.data(), &[0.5, -2.0, 0.05]);
+        assert_eq!(sparse.to_dense(), point_set);
+        assert_eq!(sparse.to_sparse(0.0), sparse);
+        assert_eq!(point_set.to_dense(), point_set);
+
+        let pruned = point_set.to_sparse(0.1);
+        assert_eq!(pruned.get_sparse().unwrap().data(), &[0.5, -2.0]);
+        let pruned = pruned.to_dense();
+        assert_eq!(pruned.get_dense().unwrap()[[1, 2]], 0.0);
+
+        // Combined sets keep the dense dimensions first.
+        let combined = PointSet::new(Some(dense.clone()), sparse.get_sparse().cloned()).unwrap();
+        let all_dense = combined.to_dense();
+        assert!(all_dense.get_sparse().is_none());
+        assert_eq!(
+            all_dense.get_dense().unwrap(),
+            ndarray::concatenate![Axis(1), dense, dense]
+        );
+        let all_sparse = combined.to_sparse(0.0);
+        assert_eq!(all_sparse.num_sparse_dimensions(), 6);
+        assert_eq!(all_sparse.to_dense(), all_dense);
+
+        let quantized = PointSet::new(Some(arr2(&[[0_u8, 3], [200, 1]])), None).unwrap();
+        let sparse = quantized.to_sparse(1);
+        assert_eq!(sparse.get_sparse().unwrap().data(), &[3, 200]);
+        assert_eq!(
+            sparse.to_dense().get_dense().unwrap(),
+            arr2(&[[0_u8, 3], [200, 0]])
+        );
+    }
+
+    #[test]
+    fn test_sparsity_stats() {
+        // Rows have 2, 0, 3 and 1 non-zero entries.
+        let sparse = CsMat::new(
+            (4, 10),
+            vec![0, 2, 2, 5, 6],
+            vec![1, 7, 0, 4, 9, 3],
+            vec![1.0_f32, 2.0, 3.0, 4.0, 5.0, 6.0],
+        );
+        let point_set = PointSet::new(Some(Array2::<f32>::eye(4)), Some(sparse)).unwrap();
+        let stats = point_set.sparsity_stats().unwrap();
+        assert_eq!(stats.nnz, 6);
+        assert_eq!(stats.density, 0.15);
+        assert_eq!(stats.mean_nnz_per_row, 1.5);
+        assert_eq!(stats.median_nnz_per_row, 1.5);
+        assert_eq!(stats.max_nnz_per_row, 3);
+
+        let stats = point_set.select(&[0, 2, 3]).sparsity_stats().unwrap();
+        assert_eq!(stats.median_nnz_per_row, 2_f32);
+        assert_eq!(stats.density, 0.2);
+
+        let stats = point_set.select(&[]).sparsity_stats().unwrap();
+        assert_eq!((stats.nnz, stats.max_nnz_per_row), (0, 0));
+        assert_eq!(stats.median_nnz_per_row, 0_f32);
+        assert!(point_set.dense_only().unwrap().sparsity_stats().is_none());
+    }
+
+    #[test]
+    fn test_append() {
+        let dense = PointSet::random_dense(6, 3, 0);
+        let mut appended = dense.select(&[0, 1]);
+        appended.append(&dense.select(&[2, 3, 4, 5])).unwrap();
+        assert_eq!(appended, dense);
+        assert!(appended.append(&PointSet::random_dense(2, 4, 0)).is_err());
+        assert_eq!(appended, dense);
+
+        let sparse = PointSet::random_sparse(7, 10, 3, 0);
+        let mut appended = sparse.select(&[0, 1, 2]);
+        appended.append(&sparse.select(&[3])).unwrap();
+        appended.append(&sparse.select(&[])).unwrap();
+        appended.append(&sparse.select(&[4, 5, 6])).unwrap();
+        assert_eq!(appended, sparse);
+        assert!(appended
+            .append(&PointSet::random_sparse(2, 11, 3, 0))
+            .is_err());
+        assert!(appended.append(&dense).is_err());
+
+        let both = PointSet::new(
+            PointSet::random_dense(7, 3, 1).get_dense().cloned(),
+            sparse.get_sparse().cloned(),
+        )
+        .unwrap();
+        let shards = [
+            both.select(&[0, 1]),
+            both.select(&[2, 3, 4]),
+            both.select(&[5, 6]),
+        ];
+        assert_eq!(PointSet::concat(&shards).unwrap(), both);
+        assert!(PointSet::concat(&[both.clone(), sparse.clone()]).is_err());
+        assert!(PointSet::<f32>::concat(&[]).is_err());
+
+        let mut appended = both.select(&[0]);
+        assert!(appended.append(&both.dense_only().unwrap()).is_err());
+        assert_eq!(appended, both.select(&[0]));
+    }
+
+    #[test]
+    fn test_split() {
+        let point_set = PointSet::new(
+            PointSet::random_dense(10, 4, 0).get_dense().cloned(),
+            PointSet::random_sparse(10, 20, 3, 1).get_sparse().cloned(),
+        )
+        .unwrap();
+        let original = point_set.clone();
+        let dense_ptr = point_set.get_dense().unwrap().as_ptr();
+        let sparse_ptr = point_set.get_sparse().unwrap().data().as_ptr();
+
+        let (dense, sparse) = point_set.split();
+        let (dense, sparse) = (dense.unwrap(), sparse.unwrap());
+        assert_eq!(Some(dense.clone()), original.dense_only());
+        assert_eq!(Some(sparse.clone()), original.sparse_only());
+        assert_eq!(dense.get_dense().unwrap().as_ptr(), dense_ptr);
+        assert_eq!(sparse.get_sparse().unwrap().data().as_ptr(), sparse_ptr);
+
+        let merged = PointSet::new(dense.get_dense().cloned(), sparse.get_sparse().cloned());
+        assert_eq!(merged.unwrap(), original);
+
+        let (dense_half, sparse_half) = dense.split();
+        assert!(dense_half.is_some() && sparse_half.is_none());
+        let (dense_half, sparse_half) = sparse.split();
+        assert!(dense_half.is_none() && sparse_half.is_some());
+    }
+
     #[test]
     fn test_num_dimensions() {
         let dense = Array2::<f32>::eye(10);
@@ -375,6 +2161,22 @@ mod tests {
         assert_eq!(4, point_set.num_sparse_dimensions());
     }
 
+    #[test]
+    fn test_dense_transposed() {
+        let dense = Array2::from_shape_vec((2, 3), vec![1.0_f32, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        let point_set = PointSet::new(Some(dense), None).unwrap();
+        let transposed = point_set.dense_transposed().unwrap();
+        assert_eq!(
+            transposed,
+            Array2::from_shape_vec((3, 2), vec![1.0_f32, 4.0, 2.0, 5.0, 3.0, 6.0]).unwrap()
+        );
+
+        let mut sparse = TriMat::new((2, 4));
+        sparse.add_triplet(0, 0, 3.0_f32);
+        let point_set = PointSet::new(None, Some(sparse.to_csr())).unwrap();
+        assert!(point_set.dense_transposed().is_none());
+    }
+
     #[test]
     fn test_hdf5() {
         let dense = Array2::<f32>::eye(10);
@@ -450,6 +2252,161 @@ mod tests {
         assert_eq!(&point_set, &point_set_copy);
     }
 
+    #[test]
+    fn test_hdf5_integer() {
+        let mut sparse = TriMat::new((3, 6));
+        sparse.add_triplet(0, 5, 255_u8);
+        sparse.add_triplet(2, 1, 7);
+        sparse.add_triplet(2, 0, 1);
+        let point_set = PointSet::new(
+            Some(arr2(&[[0_u8, 128, 255], [1, 2, 3], [9, 8, 7]])),
+            Some(sparse.to_csr()),
+        )
+        .unwrap();
+        let signed = point_set.cast_with(|&x| x as i8);
+
+        let dir = TempDir::new("pointset_test_hdf5").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        let hdf5 = File::create(path).unwrap();
+
+        let mut group = hdf5.group("/").unwrap();
+        assert!(point_set.add_to(&mut group).is_ok());
+        let point_set_copy = PointSet::<u8>::read_from(&group).unwrap();
+        assert_eq!(&point_set, &point_set_copy);
+
+        let mut group = group.create_group("/nested").unwrap();
+        assert!(signed.add_to(&mut group).is_ok());
+        let signed_copy = PointSet::<i8>::read_from(&group).unwrap();
+        assert_eq!(&signed, &signed_copy);
+    }
+
+    #[test]
+    fn test_integer() {
+        let mut sparse = TriMat::new((3, 6));
+        sparse.add_triplet(0, 5, 255_u8);
+        sparse.add_triplet(2, 1, 7);
+        let point_set = PointSet::new(
+            Some(arr2(&[[0_u8, 128, 255], [1, 2, 3], [9, 8, 7]])),
+            Some(sparse.to_csr()),
+        )
+        .unwrap();
+
+        let subset = point_set.select(&[2, 0]);
+        assert_eq!(
+            subset.get_dense().unwrap(),
+            arr2(&[[9_u8, 8, 7], [0, 128, 255]])
+        );
+        assert_eq!(subset.get_sparse().unwrap().data(), &[7_u8, 255]);
+
+        let bytes = bincode::serialize(&point_set).unwrap();
+        assert_eq!(
+            bincode::deserialize::<PointSet<u8>>(&bytes).unwrap(),
+            point_set
+        );
+
+        let norms = point_set.cast::<f32>().l2_norm();
+        assert_eq!(norms[1], 14_f32.sqrt());
+    }
+
+    #[test]
+    fn test_hdf5_unsorted_sparse() {
+        let dir = TempDir::new("test_hdf5_unsorted_sparse").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let hdf5 = File::create(path.to_str().unwrap()).unwrap();
+        let group = hdf5.create_group("point-set-sparse").unwrap();
+        group
+            .new_attr::<usize>()
+            .shape(2)
+            .create("shape")
+            .unwrap()
+            .write(&[2, 5])
+            .unwrap();
+        group
+            .new_dataset_builder()
+            .with_data(&[0_usize, 3, 5])
+            .create("indptr")
+            .unwrap();
+        group
+            .new_dataset_builder()
+            .with_data(&[4_usize, 0, 2, 3, 1])
+            .create("indices")
+            .unwrap();
+        group
+            .new_dataset_builder()
+            .with_data(&[1_f32, 2_f32, 3_f32, 4_f32, 5_f32])
+            .create("data")
+            .unwrap();
+
+        let point_set = PointSet::<f32>::read_from(&hdf5.group("/").unwrap()).unwrap();
+        let sparse = point_set.get_sparse().unwrap();
+        assert_eq!(sparse.indices(), &[0, 2, 4, 1, 3]);
+        assert_eq!(sparse.data(), &[2_f32, 3_f32, 1_f32, 5_f32, 4_f32]);
+        let selected = point_set.select(&[1]);
+        assert_eq!(selected.get_sparse().unwrap().indices(), &[1, 3]);
+        assert_eq!(selected.get_sparse().unwrap().data(), &[5_f32, 4_f32]);
+    }
+
+    #[test]
+    fn test_deserialize_unsorted_sparse() {
+        #[derive(Serialize)]
+        struct RawPointSet {
+            dense: Option<Array2<f32>>,
+            sparse: Option<RawSparse>,
+        }
+        #[derive(Serialize)]
+        struct RawSparse {
+            storage: CompressedStorage,
+            nrows: usize,
+            ncols: usize,
+            indptr: Vec<usize>,
+            indices: Vec<usize>,
+            data: Vec<f32>,
+        }
+
+        let raw = RawPointSet {
+            dense: None,
+            sparse: Some(RawSparse {
+                storage: CompressedStorage::CSR,
+                nrows: 2,
+                ncols: 5,
+                indptr: vec![0, 3, 5],
+                indices: vec![4, 0, 2, 3, 1],
+                data: vec![1_f32, 2_f32, 3_f32, 4_f32, 5_f32],
+            }),
+        };
+        let bytes = bincode::serialize(&raw).unwrap();
+        let point_set: PointSet<f32> = bincode::deserialize(&bytes).unwrap();
+        let sparse = point_set.get_sparse().unwrap();
+        assert!(sparse.is_csr());
+        assert_eq!(sparse.indices(), &[0, 2, 4, 1, 3]);
+        assert_eq!(sparse.data(), &[2_f32, 3_f32, 1_f32, 5_f32, 4_f32]);
+        let selected = point_set.select(&[1]);
+        assert_eq!(selected.get_sparse().unwrap().indices(), &[1, 3]);
+        assert_eq!(selected.get_sparse().unwrap().data(), &[5_f32, 4_f32]);
+
+        let sorted = PointSet::random_sparse(4, 10, 3, 0);
+        let bytes = bincode::serialize(&sorted).unwrap();
+        assert_eq!(
+            bincode::deserialize::<PointSet<f32>>(&bytes).unwrap(),
+            sorted
+        );
+
+        let duplicate = RawPointSet {
+            dense: None,
+            sparse: Some(RawSparse {
+                storage: CompressedStorage::CSR,
+                nrows: 1,
+                ncols: 5,
+                indptr: vec![0, 2],
+                indices: vec![1, 1],
+                data: vec![1_f32, 2_f32],
+            }),
+        };
+        let bytes = bincode::serialize(&duplicate).unwrap();
+        assert!(bincode::deserialize::<PointSet<f32>>(&bytes).is_err());
+    }
+
     #[test]
     fn test_l2_norm() {
         let dense = Array2::<f32>::eye(10);
@@ -516,4 +2473,184 @@ mod tests {
             assert_approx_eq!(e.0, e.1 as f64, 0.01);
         });
     }
+
+    #[test]
+    fn test_l1_norm() {
+        let dense = Array2::<f32>::eye(10) * -2_f32;
+
+        let mut sparse = TriMat::new((10, 4));
+        sparse.add_triplet(0, 0, 3.0_f32);
+        sparse.add_triplet(1, 2, 2.0);
+        sparse.add_triplet(1, 3, -0.5);
+        sparse.add_triplet(3, 0, -2.0);
+        let sparse: CsMat<_> = sparse.to_csr();
+
+        let point_set = PointSet::new(Some(dense.clone()), None).unwrap();
+        assert_eq!(point_set.l1_norm().to_vec(), vec![2.0; 10]);
+
+        let point_set = PointSet::new(Some(dense.clone()), Some(sparse.clone())).unwrap();
+        assert_eq!(
+            point_set.l1_norm().to_vec(),
+            vec![5.0, 4.5, 2.0, 4.0, 2.0, 2.0, 2.0, 2.0, 2.0, 2.0]
+        );
+
+        let point_set = PointSet::new(None, Some(sparse.clone())).unwrap();
+        assert_eq!(
+            point_set.l1_norm().to_vec(),
+            vec![3.0, 2.5, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_l1_normalize_inplace() {
+        let mut dense = Array2::<f32>::eye(10);
+        dense[[0, 1]] = -1.0;
+        dense.row_mut(9).fill(0.0);
+
+        let mut sparse = TriMat::new((10, 4));
+        sparse.add_triplet(0, 0, 3.0_f32);
+        sparse.add_triplet(1, 2, 2.0);
+        sparse.add_triplet(3, 0, -2.0);
+        let sparse: CsMat<_> = sparse.to_csr();
+
+        let mut point_set = PointSet::new(Some(dense.clone()), None).unwrap();
+        point_set.l1_normalize_inplace();
+        zip(
+            vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0],
+            point_set.l1_norm().to_vec(),
+        )
+        .for_each(|e| {
+            assert_approx_eq!(e.0, e.1 as f64, 0.01);
+        });
+        assert_eq!(point_set.get_dense().unwrap()[[0, 1]], -0.5);
+        assert!(!point_set.has_non_finite());
+
+        let mut point_set = PointSet::new(Some(dense.clone()), Some(sparse.clone())).unwrap();
+        point_set.l1_normalize_inplace();
+        zip(
+            vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0],
+            point_set.l1_norm().to_vec(),
+        )
+        .for_each(|e| {
+            assert_approx_eq!(e.0, e.1 as f64, 0.01);
+        });
+        assert_eq!(point_set.get_sparse().unwrap().get(0, 0), Some(&0.6));
+        assert!(!point_set.has_non_finite());
+
+        let mut point_set = PointSet::new(None, Some(sparse.clone())).unwrap();
+        point_set.l1_normalize_inplace();
+        zip(
+            vec![1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            point_set.l1_norm().to_vec(),
+        )
+        .for_each(|e| {
+            assert_approx_eq!(e.0, e.1 as f64, 0.01);
+        });
+    }
+
+    #[test]
+    fn test_l2_normalize_zero_norm() {
+        let mut dense = Array2::<f32>::eye(4);
+        dense.row_mut(2).fill(0.0);
+        dense[[3, 3]] = 1e-20;
+
+        let mut sparse = TriMat::new((4, 3));
+        sparse.add_triplet(0, 0, 3.0_f32);
+        sparse.add_triplet(3, 1, 1e-20);
+        let sparse: CsMat<_> = sparse.to_csr();
+
+        for (dense, sparse) in [
+            (Some(dense.clone()), None),
+            (Some(dense.clone()), Some(sparse.clone())),
+            (None, Some(sparse.clone())),
+        ] {
+            let original = PointSet::new(dense, sparse).unwrap();
+            let mut point_set = original.clone();
+            point_set.l2_normalize_inplace();
+            assert!(!point_set.has_non_finite());
+            assert_eq!(point_set.select(&[2, 3]), original.select(&[2, 3]));
+        }
+    }
+
+    #[test]
+    fn test_l2_normalize_returning_norms() {
+        let dense =
+            Array2::from_shape_vec((3, 2), vec![3.0_f32, 4.0, 1.0, 0.0, -2.0, 2.0]).unwrap();
+
+        let mut sparse = TriMat::new((3, 4));
+        sparse.add_triplet(0, 0, 3.0_f32);
+        sparse.add_triplet(1, 2, 2.0);
+        sparse.add_triplet(2, 1, -1.0);
+        let sparse: CsMat<_> = sparse.to_csr();
+
+        let original = PointSet::new(Some(dense), Some(sparse)).unwrap();
+        let mut point_set = original.clone();
+        let norms = point_set.l2_normalize_returning_norms();
+        zip(original.l2_norm().to_vec(), norms.to_vec()).for_each(|e| {
+            assert_approx_eq!(e.0 as f64, e.1 as f64, 0.01);
+        });
+
+        let dense = point_set.get_dense().unwrap();
+        let original_dense = original.get_dense().unwrap();
+        for (i, row) in dense.outer_iter().enumerate() {
+            zip(row.iter(), original_dense.row(i).iter()).for_each(|(&x, &y)| {
+                assert_approx_eq!((x * norms[i]) as f64, y as f64, 0.01);
+            });
+        }
+
+        let sparse = point_set.get_sparse().unwrap();
+        let original_sparse = original.get_sparse().unwrap();
+        for (i, row) in sparse.outer_iterator().enumerate() {
+            zip(
+                row.data().iter(),
+                original_sparse.outer_view(i).unwrap().data().iter(),
+            )
+            .for_each(|(&x, &y)| {
+                assert_approx_eq!((x * norms[i]) as f64, y as f64, 0.01);
+            });
+        }
+    }
+
+    #[test]
+    fn test_mips_to_l2_transform() {
+        let data = PointSet::random_dense(50, 8, 11);
+        let queries = PointSet::random_dense(5, 8, 12);
+
+        let (transformed, max_norm) = data.mips_to_l2_transform();
+        assert_eq!(transformed.num_dense_dimensions(), 9);
+        transformed.l2_norm().iter().for_each(|&norm| {
+            assert_approx_eq!(norm as f64, max_norm as f64, 0.001);
+        });
+
+        let augmented = queries.augment_query();
+        assert_eq!(augmented.num_dense_dimensions(), 9);
+        assert!(augmented
+            .get_dense()
+            .unwrap()
+            .column(8)
+            .iter()
+            .all(|&x| x == 0.0));
+
+        // Rounding may swap neighbors whose inner products are nearly tied, so compare the inner
+        // products of the neighbors at every rank rather than their ids.
+        let l2_neighbors = transformed
+            .search(&augmented, 10, &Metric::Euclidean)
+            .unwrap();
+        let ip_neighbors = data
+            .search_with_scores(&queries, 10, &Metric::InnerProduct)
+            .unwrap();
+        let dense = data.get_dense().unwrap();
+        let query_points = queries.get_dense().unwrap();
+        l2_neighbors
+            .rows()
+            .into_iter()
+            .zip(&ip_neighbors)
+            .enumerate()
+            .for_each(|(i, (l2_row, ip_row))| {
+                l2_row.iter().zip(ip_row).for_each(|(&id, expected)| {
+                    let product = dense.row(id).dot(&query_points.row(i));
+                    assert_approx_eq!(product as f64, -expected.score as f64, 1e-4);
+                });
+            });
+    }
 }
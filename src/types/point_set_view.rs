@@ -0,0 +1,198 @@
+use crate::PointSet;
+use linfa_linalg::norm::Norm;
+use ndarray::{Array1, ArrayView1, ArrayView2, Axis};
+use sprs::{CsMat, CsMatView, CsVecView};
+
+/// A read-only view of a range of points in a `PointSet` that borrows the vectors of the set
+/// instead of copying them.
+#[derive(Debug, Clone)]
+pub struct PointSetView<'a, DataType: Clone> {
+    dense: Option<ArrayView2<'a, DataType>>,
+    sparse: Option<CsMatView<'a, DataType>>,
+}
+
+impl<'a, DataType: Clone> PointSetView<'a, DataType> {
+    pub(crate) fn new(
+        dense: Option<ArrayView2<'a, DataType>>,
+        sparse: Option<CsMatView<'a, DataType>>,
+    ) -> PointSetView<'a, DataType> {
+        PointSetView { dense, sparse }
+    }
+
+    /// Returns the number of points in the view.
+    pub fn num_points(&self) -> usize {
+        if let Some(dense) = self.dense.as_ref() {
+            return dense.nrows();
+        }
+        if let Some(sparse) = self.sparse.as_ref() {
+            return sparse.rows();
+        }
+        0_usize
+    }
+
+    /// Returns the number of dense dimensions.
+    pub fn num_dense_dimensions(&self) -> usize {
+        self.dense.as_ref().map_or(0, |dense| dense.ncols())
+    }
+
+    /// Returns the number of sparse dimensions.
+    pub fn num_sparse_dimensions(&self) -> usize {
+        self.sparse.as_ref().map_or(0, |sparse| sparse.cols())
+    }
+
+    /// Returns the total number of dimensions.
+    pub fn num_dimensions(&self) -> usize {
+        self.num_sparse_dimensions() + self.num_dense_dimensions()
+    }
+
+    /// Returns the dense sub-vectors.
+    pub fn get_dense(&self) -> Option<ArrayView2<'a, DataType>> {
+        self.dense
+    }
+
+    /// Returns the sparse sub-vectors.
+    pub fn get_sparse(&self) -> Option<&CsMatView<'a, DataType>> {
+        self.sparse.as_ref()
+    }
+
+    /// Copies the points in the view into a new `PointSet`.
+    pub fn to_point_set(&self) -> PointSet<DataType> {
+        PointSet::new(
+            self.dense.as_ref().map(|dense| dense.to_owned()),
+            self.sparse.as_ref().map(|sparse| {
+                // The index pointers of a sliced matrix still carry the offset of the first row.
+                let indptr = sparse.indptr();
+                let indptr = indptr.raw_storage();
+                CsMat::new(
+                    sparse.shape(),
+                    indptr.iter().map(|p| p - indptr[0]).collect(),
+                    sparse.indices().to_vec(),
+                    sparse.data().to_vec(),
+                )
+            }),
+        )
+        .expect("A view has the components of a valid point set.")
+    }
+}
+
+impl PointSetView<'_, f32> {
+    /// Returns the L2 norm of the points.
+    pub fn l2_norm(&self) -> Array1<f32> {
+        let mut l2_norm = Array1::<f32>::zeros(self.num_points());
+        if let Some(dense) = self.dense.as_ref() {
+            l2_norm
+                .iter_mut()
+                .zip(dense.axis_iter(Axis(0)))
+                .for_each(|(norm, point)| *norm += point.norm_l2().powi(2));
+        }
+        if let Some(sparse) = self.sparse.as_ref() {
+            l2_norm
+                .iter_mut()
+                .zip(sparse.outer_iterator())
+                .for_each(|(norm, point)| *norm += point.l2_norm().powi(2));
+        }
+        l2_norm.mapv_inplace(|v| v.sqrt());
+        l2_norm
+    }
+
+    /// Returns the L1 norm of the points, the sum of the absolute values of their coordinates.
+    pub fn l1_norm(&self) -> Array1<f32> {
+        let mut l1_norm = Array1::<f32>::zeros(self.num_points());
+        if let Some(dense) = self.dense.as_ref() {
+            l1_norm
+                .iter_mut()
+                .zip(dense.axis_iter(Axis(0)))
+                .for_each(|(norm, point)| *norm += point.norm_l1());
+        }
+        if let Some(sparse) = self.sparse.as_ref() {
+            l1_norm
+                .iter_mut()
+                .zip(sparse.outer_iterator())
+                .for_each(|(norm, point)| *norm += point.l1_norm());
+        }
+        l1_norm
+    }
+}
+
+/// A read-only view of a single point in a `PointSet`, made up of its dense and sparse
+/// sub-vectors, that borrows the vectors of the set instead of copying them.
+#[derive(Debug, Clone)]
+pub struct PointView<'a, DataType: Clone> {
+    dense: Option<ArrayView1<'a, DataType>>,
+    sparse: Option<CsVecView<'a, DataType>>,
+}
+
+impl<'a, DataType: Clone> PointView<'a, DataType> {
+    pub(crate) fn new(
+        dense: Option<ArrayView1<'a, DataType>>,
+        sparse: Option<CsVecView<'a, DataType>>,
+    ) -> PointView<'a, DataType> {
+        PointView { dense, sparse }
+    }
+
+    /// Returns the number of dense dimensions.
+    pub fn num_dense_dimensions(&self) -> usize {
+        self.dense.as_ref().map_or(0, |dense| dense.len())
+    }
+
+    /// Returns the number of sparse dimensions.
+    pub fn num_sparse_dimensions(&self) -> usize {
+        self.sparse.as_ref().map_or(0, |sparse| sparse.dim())
+    }
+
+    /// Returns the dense sub-vector.
+    pub fn get_dense(&self) -> Option<ArrayView1<'a, DataType>> {
+        self.dense
+    }
+
+    /// Returns the sparse sub-vector.
+    pub fn get_sparse(&self) -> Option<&CsVecView<'a, DataType>> {
+        self.sparse.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PointSet;
+    use ndarray::Array2;
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+    use sprs::TriMat;
+
+    fn sample_point_set() -> PointSet<f32> {
+        let dense = Array2::random((10, 4), Uniform::new(-1_f32, 1_f32));
+        let mut sparse = TriMat::new((10, 6));
+        (0..10).for_each(|i| sparse.add_triplet(i, i % 6, i as f32 + 1_f32));
+        sparse.add_triplet(4, 5, 2_f32);
+        PointSet::new(Some(dense), Some(sparse.to_csr())).unwrap()
+    }
+
+    #[test]
+    fn test_slice_rows() {
+        let point_set = sample_point_set();
+
+        let view = point_set.slice_rows(3, 7).unwrap();
+        assert_eq!(view.num_points(), 4);
+        assert_eq!(view.num_dimensions(), 10);
+        assert_eq!(view.to_point_set(), point_set.select(&[3, 4, 5, 6]));
+
+        let empty = point_set.slice_rows(5, 5).unwrap();
+        assert_eq!(empty.num_points(), 0);
+
+        assert!(point_set.slice_rows(6, 5).is_err());
+        assert!(point_set.slice_rows(5, 11).is_err());
+
+        assert_eq!(point_set.view().to_point_set(), point_set);
+    }
+
+    #[test]
+    fn test_l2_norm() {
+        let point_set = sample_point_set();
+        let view = point_set.slice_rows(2, 9).unwrap();
+        let expected = point_set.select(&[2, 3, 4, 5, 6, 7, 8]).l2_norm();
+        view.l2_norm()
+            .iter()
+            .zip(expected.iter())
+            .for_each(|(a, b)| assert!((a - b).abs() < 1e-5));
+    }
+}
@@ -1,16 +1,21 @@
+use crate::io::inspect::QuerySetInfo;
 use crate::types::ground_truth::GroundTruth;
+use crate::types::point_set::inspect_point_set;
 use crate::types::Metric;
 use crate::{Hdf5Serialization, PointSet};
 use anyhow::{anyhow, Result};
+use hdf5::types::VarLenUnicode;
 use hdf5::{Group, H5Type};
 use ndarray::Array2;
 use serde::{Deserialize, Serialize};
+use sprs::CsMat;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 const QUERIES: &str = "queries";
 const GROUND_TRUTH: &str = "gt";
+const METRIC: &str = "metric";
 
 /// A set of query points (dense, sparse, or both) and their exact nearest neighbors for various
 /// metrics.
@@ -38,20 +43,98 @@ impl<DataType: Clone> QuerySet<DataType> {
     /// metric.
     ///
     /// Returns an error if the number of rows in `neighbors` does not match the number of query
-    /// points.
+    /// points, or if `metric` is `Jaccard` but the query points have no sparse component.
     pub fn add_ground_truth(&mut self, metric: Metric, neighbors: Array2<usize>) -> Result<()> {
-        if neighbors.nrows() != self.points.num_points() {
+        self.insert_ground_truth(metric, GroundTruth::new(neighbors))
+    }
+
+    /// Same as `add_ground_truth` but takes ground truth that may record distances.
+    pub(crate) fn insert_ground_truth(&mut self, metric: Metric, gt: GroundTruth) -> Result<()> {
+        self.check_metric(&metric)?;
+        if gt.get_neighbors().nrows() != self.points.num_points() {
             return Err(anyhow!(
                 "Number of rows in `neighbors` ({}) must match the \
                 number of query points in the set {}.",
+                gt.get_neighbors().nrows(),
+                self.points.num_points()
+            ));
+        }
+        self.neighbors.insert(metric, gt);
+        Ok(())
+    }
+
+    /// Adds several sets of exact nearest neighbors at once, as in `add_ground_truth`.
+    ///
+    /// All sets are validated before any is inserted: if the number of rows in any of them does
+    /// not match the number of query points, returns an error naming the offending metric and
+    /// leaves the query set unchanged. If a metric appears more than once, the last set wins.
+    pub fn add_ground_truth_batch(&mut self, gts: Vec<(Metric, Array2<usize>)>) -> Result<()> {
+        gts.iter()
+            .try_for_each(|(metric, _)| self.check_metric(metric))?;
+        if let Some((metric, neighbors)) = gts
+            .iter()
+            .find(|(_, neighbors)| neighbors.nrows() != self.points.num_points())
+        {
+            return Err(anyhow!(
+                "Number of rows in the neighbors for {:?} ({}) must match the \
+                number of query points in the set {}.",
+                metric,
                 neighbors.nrows(),
                 self.points.num_points()
             ));
         }
-        self.neighbors.insert(metric, GroundTruth::new(neighbors));
+        gts.into_iter().for_each(|(metric, neighbors)| {
+            self.neighbors.insert(metric, GroundTruth::new(neighbors));
+        });
+        Ok(())
+    }
+
+    /// Same as `add_ground_truth` but for ground truth where the number of neighbors may differ
+    /// from one query to another, such that `neighbors[i]` holds the neighbors of the `i`-th
+    /// query. See `GroundTruth::new_ragged`.
+    pub fn add_ragged_ground_truth(
+        &mut self,
+        metric: Metric,
+        neighbors: Vec<Vec<usize>>,
+    ) -> Result<()> {
+        self.check_metric(&metric)?;
+        if neighbors.len() != self.points.num_points() {
+            return Err(anyhow!(
+                "Number of rows in `neighbors` ({}) must match the \
+                number of query points in the set {}.",
+                neighbors.len(),
+                self.points.num_points()
+            ));
+        }
+        self.neighbors
+            .insert(metric, GroundTruth::new_ragged(neighbors));
+        Ok(())
+    }
+
+    /// Returns an error if ground truth for `metric` does not make sense for the query points,
+    /// which is the case for `Jaccard` unless the query points have a sparse component.
+    fn check_metric(&self, metric: &Metric) -> Result<()> {
+        if *metric == Metric::Jaccard && self.points.get_sparse().is_none() {
+            return Err(anyhow!(
+                "Jaccard ground truth requires query points with a sparse component."
+            ));
+        }
         Ok(())
     }
 
+    /// Returns all sets of exact nearest neighbors keyed by metric.
+    pub(crate) fn get_ground_truths(&self) -> &HashMap<Metric, GroundTruth> {
+        &self.neighbors
+    }
+
+    /// Returns all sets of exact nearest neighbors sorted by their metric, so that output is
+    /// deterministic.
+    pub(crate) fn sorted_ground_truths(&self) -> impl Iterator<Item = (&Metric, &GroundTruth)> {
+        let mut entries = self.neighbors.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(metric, _)| *metric);
+        entries.into_iter()
+    }
+
     /// Returns the set of exact nearest neighbors for ANN search with the given metric; or an error
     /// if the query set does not have the solution.
     pub fn get_ground_truth(&self, metric: &Metric) -> Result<&GroundTruth> {
@@ -63,18 +146,362 @@ impl<DataType: Clone> QuerySet<DataType> {
             metric
         ))
     }
+
+    /// Returns an error if the ground truth for some metric does not have exactly one row per
+    /// query point, or if its rows are inconsistent with the number of neighbors recorded for a
+    /// ragged set. The error names the offending metric.
+    pub fn validate(&self) -> Result<()> {
+        self.sorted_ground_truths().try_for_each(|(metric, gt)| {
+            if gt.get_neighbors().nrows() != self.points.num_points() {
+                return Err(anyhow!(
+                    "Ground truth for {:?} has {} rows but there are {} query points.",
+                    metric,
+                    gt.get_neighbors().nrows(),
+                    self.points.num_points()
+                ));
+            }
+            gt.validate()
+                .map_err(|e| anyhow!("Invalid ground truth for {:?}: {}", metric, e))
+        })
+    }
+
+    /// Returns the largest `k` at which recall can be evaluated against the ground truth for
+    /// `metric` without being capped, i.e., the number of neighbors per query. If the ground
+    /// truth is ragged, returns the smallest number of neighbors of any query.
+    ///
+    /// Recall at a larger `k` is silently computed at this smaller width instead; see
+    /// `GroundTruth::recall`. Returns an error if the query set has no solution for `metric`.
+    pub fn max_evaluable_k(&self, metric: &Metric) -> Result<usize> {
+        let gt = self.get_ground_truth(metric)?;
+        Ok((0..gt.get_neighbors().nrows())
+            .map(|query| gt.num_neighbors(query))
+            .min()
+            .unwrap_or(gt.get_neighbors().ncols()))
+    }
+
+    /// Verifies that the ground truth for all given `metrics` lists exactly the same neighbors in
+    /// the same order for every query, as expected of metrics that induce the same ranking, such
+    /// as cosine and angular distance. This helps catch a ground-truth generator that produces
+    /// divergent orderings for metrics that are known to be equivalent.
+    ///
+    /// Returns an error naming the first metric and query whose neighbors differ from those of
+    /// the first metric in `metrics`, or if the query set has no solution for one of `metrics`.
+    pub fn assert_ranking_consistency(&self, metrics: &[Metric]) -> Result<()> {
+        let Some((reference_metric, others)) = metrics.split_first() else {
+            return Ok(());
+        };
+        let reference = self.get_ground_truth(reference_metric)?;
+        for metric in others {
+            let gt = self.get_ground_truth(metric)?;
+            let num_queries = reference.get_neighbors().nrows();
+            if gt.get_neighbors().nrows() != num_queries {
+                return Err(anyhow!(
+                    "Ground truth for {:?} has {} queries but ground truth for {:?} has {}.",
+                    metric,
+                    gt.get_neighbors().nrows(),
+                    reference_metric,
+                    num_queries
+                ));
+            }
+            if let Some(query) = (0..num_queries)
+                .find(|&query| gt.neighbors_of(query) != reference.neighbors_of(query))
+            {
+                return Err(anyhow!(
+                    "Neighbors of query {} for {:?} differ from those for {:?}.",
+                    query,
+                    metric,
+                    reference_metric
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the exact nearest neighbors for all metrics that are not in `keep`. Metrics in
+    /// `keep` for which the query set has no solution are ignored.
+    pub fn retain_metrics(&mut self, keep: &[Metric]) {
+        self.neighbors.retain(|metric, _| keep.contains(metric));
+        self.neighbors.shrink_to_fit();
+    }
+}
+
+impl<DataType: Clone> QuerySet<DataType> {
+    /// Returns an error if `other` can not be appended to this query set.
+    ///
+    /// Two query sets are appendable if their query points have the same components (dense,
+    /// sparse, or both) with the same dimensionality, and if they have ground truth for exactly
+    /// the same metrics with the same number of neighbors per query, unless the ground truth of
+    /// either set is ragged. The error describes the first mismatch found.
+    pub fn is_appendable(&self, other: &QuerySet<DataType>) -> Result<()> {
+        self.points.is_appendable(&other.points)?;
+
+        if let Some(metric) = other
+            .neighbors
+            .keys()
+            .find(|metric| !self.neighbors.contains_key(metric))
+        {
+            return Err(anyhow!(
+                "No solution to ANN with {:?} exists in this set.",
+                metric
+            ));
+        }
+        self.neighbors.iter().try_for_each(|(metric, gt)| {
+            let Some(other_gt) = other.neighbors.get(metric) else {
+                return Err(anyhow!(
+                    "No solution to ANN with {:?} exists in the other set.",
+                    metric
+                ));
+            };
+            if !gt.is_ragged()
+                && !other_gt.is_ragged()
+                && gt.get_neighbors().ncols() != other_gt.get_neighbors().ncols()
+            {
+                return Err(anyhow!(
+                    "Ground-truth for {:?} has {} neighbors per query but {} in the other set.",
+                    metric,
+                    gt.get_neighbors().ncols(),
+                    other_gt.get_neighbors().ncols()
+                ));
+            }
+            Ok(())
+        })
+    }
+
+    /// Appends the query points in `other` along with their ground truth to the end of this
+    /// query set.
+    ///
+    /// The ground truth for each metric is concatenated row-wise, so that the `i`-th query of
+    /// `other` becomes query `n + i` of this set, where `n` is the number of queries before the
+    /// call. Neighbor ids are copied as-is, so both sets must refer to the same data points.
+    ///
+    /// Returns an error, leaving this set unchanged, if `other` is not appendable as determined
+    /// by `is_appendable`.
+    pub fn append(&mut self, other: &QuerySet<DataType>) -> Result<()> {
+        self.is_appendable(other)?;
+        self.points.append(&other.points)?;
+        self.neighbors.iter_mut().try_for_each(|(metric, gt)| {
+            gt.append(&other.neighbors[metric])?;
+            anyhow::Ok(())
+        })
+    }
+}
+
+/// Accumulates query sets into buffers that are preallocated for an expected total number of
+/// query points, and assembles them into a single `QuerySet` once all query sets have arrived.
+pub(crate) struct QuerySetBuffer<DataType: Clone> {
+    expected_points: usize,
+    template: Option<QuerySet<DataType>>,
+    num_points: usize,
+    dense: Vec<DataType>,
+    sparse_indptr: Vec<usize>,
+    sparse_indices: Vec<usize>,
+    sparse_data: Vec<DataType>,
+    /// Neighbor ids of all buffered queries along with the number of neighbors of each query.
+    neighbors: HashMap<Metric, (Vec<usize>, Vec<usize>)>,
+    /// Distances of all buffered queries to their neighbors in row-major order, or `None` if a
+    /// query set without distances was received.
+    distances: HashMap<Metric, Option<Vec<f32>>>,
+}
+
+impl<DataType: Clone> QuerySetBuffer<DataType> {
+    /// Creates an empty buffer that will reserve space for `expected_points` query points once
+    /// the first query set arrives.
+    pub(crate) fn with_capacity(expected_points: usize) -> QuerySetBuffer<DataType> {
+        QuerySetBuffer {
+            expected_points,
+            template: None,
+            num_points: 0,
+            dense: vec![],
+            sparse_indptr: vec![0],
+            sparse_indices: vec![],
+            sparse_data: vec![],
+            neighbors: HashMap::new(),
+            distances: HashMap::new(),
+        }
+    }
+
+    /// Reserves space in all buffers using the shape of `query_set` as a guide.
+    fn reserve(&mut self, query_set: &QuerySet<DataType>) {
+        let points = &query_set.points;
+        self.dense
+            .reserve(self.expected_points * points.num_dense_dimensions());
+        if let Some(sparse) = points.get_sparse() {
+            self.sparse_indptr.reserve(self.expected_points);
+            let nnz_per_point = sparse.nnz().div_ceil(sparse.rows().max(1));
+            self.sparse_indices
+                .reserve(self.expected_points * nnz_per_point);
+            self.sparse_data
+                .reserve(self.expected_points * nnz_per_point);
+        }
+        query_set.neighbors.iter().for_each(|(metric, gt)| {
+            self.neighbors.insert(
+                metric.clone(),
+                (
+                    Vec::with_capacity(self.expected_points * gt.get_neighbors().ncols()),
+                    Vec::with_capacity(self.expected_points),
+                ),
+            );
+            self.distances.insert(
+                metric.clone(),
+                gt.get_distances()
+                    .map(|distances| Vec::with_capacity(self.expected_points * distances.ncols())),
+            );
+        });
+    }
+
+    /// Copies `query_set` into the buffers.
+    ///
+    /// Returns an error if `query_set` is not appendable to the query sets received so far.
+    pub(crate) fn push(&mut self, query_set: &QuerySet<DataType>) -> Result<()> {
+        match self.template.as_ref() {
+            None => {
+                self.reserve(query_set);
+                self.template = Some(QuerySet::new(query_set.points.select(&[])));
+                let template = self.template.as_mut().unwrap();
+                query_set.neighbors.iter().for_each(|(metric, gt)| {
+                    template.neighbors.insert(
+                        metric.clone(),
+                        GroundTruth::new(Array2::zeros((0, gt.get_neighbors().ncols()))),
+                    );
+                });
+            }
+            Some(template) => template.is_appendable(query_set)?,
+        }
+
+        let points = &query_set.points;
+        if let Some(dense) = points.get_dense() {
+            self.dense.extend(dense.iter().cloned());
+        }
+        if let Some(sparse) = points.get_sparse() {
+            let offset = self.sparse_data.len();
+            let indptr = sparse.indptr();
+            self.sparse_indptr
+                .extend(indptr.to_proper().iter().skip(1).map(|&p| offset + p));
+            self.sparse_indices.extend_from_slice(sparse.indices());
+            self.sparse_data.extend_from_slice(sparse.data());
+        }
+        query_set.neighbors.iter().for_each(|(metric, gt)| {
+            if let Some((ids, lengths)) = self.neighbors.get_mut(metric) {
+                (0..gt.get_neighbors().nrows()).for_each(|query| {
+                    ids.extend(gt.neighbors_of(query).iter());
+                    lengths.push(gt.num_neighbors(query));
+                });
+            }
+            if let Some(buffer) = self.distances.get_mut(metric) {
+                match (buffer.as_mut(), gt.get_distances()) {
+                    (Some(buffer), Some(distances)) => buffer.extend(distances.iter()),
+                    _ => *buffer = None,
+                }
+            }
+        });
+        self.num_points += points.num_points();
+        Ok(())
+    }
+
+    /// Assembles the buffered query sets into a single `QuerySet`, or returns `None` if no query
+    /// set was received.
+    pub(crate) fn finish(self) -> Result<Option<QuerySet<DataType>>> {
+        let Some(template) = self.template else {
+            return Ok(None);
+        };
+
+        let num_points = self.num_points;
+        let dense = match template.points.get_dense() {
+            None => None,
+            Some(dense) => Some(Array2::from_shape_vec(
+                (num_points, dense.ncols()),
+                self.dense,
+            )?),
+        };
+        let sparse = template.points.get_sparse().map(|sparse| {
+            CsMat::new(
+                (num_points, sparse.cols()),
+                self.sparse_indptr,
+                self.sparse_indices,
+                self.sparse_data,
+            )
+        });
+
+        let mut neighbors = HashMap::new();
+        let mut distances = self.distances;
+        for (metric, (ids, lengths)) in self.neighbors {
+            let mut gt = GroundTruth::from_flat(ids, lengths)?;
+            if let Some(Some(distances)) = distances.remove(&metric) {
+                if !gt.is_ragged() {
+                    let shape = gt.get_neighbors().dim();
+                    gt = GroundTruth::with_distances(
+                        gt.get_neighbors().to_owned(),
+                        Array2::from_shape_vec(shape, distances)?,
+                    )?;
+                }
+            }
+            neighbors.insert(metric, gt);
+        }
+
+        Ok(Some(QuerySet {
+            points: PointSet::new(dense, sparse)?,
+            neighbors,
+        }))
+    }
+}
+
+/// Returns the metric of the ground truth stored in `group`.
+fn read_metric(group: &Group) -> Result<Metric> {
+    // The metric attribute takes precedence over the name of the group, which older files rely
+    // on exclusively.
+    match group.attr(METRIC) {
+        Ok(attr) => Metric::from_str(attr.read_scalar::<VarLenUnicode>()?.as_str()),
+        Err(_) => {
+            let name = group.name();
+            Metric::from_str(name.split('/').next_back().unwrap())
+        }
+    }
+}
+
+/// Summarizes the query set stored in `group` from HDF5 metadata alone, without reading the
+/// query points or ground truth.
+pub(crate) fn inspect_query_set(group: &Group, label: &str) -> Result<QuerySetInfo> {
+    let points = inspect_point_set(&group.group(QUERIES)?)?;
+    let mut metrics = match group.group(GROUND_TRUTH) {
+        Ok(gt_group) => gt_group
+            .groups()?
+            .iter()
+            .map(read_metric)
+            .collect::<Result<Vec<_>>>()?,
+        Err(_) => vec![],
+    };
+    metrics.sort();
+    Ok(QuerySetInfo {
+        label: label.to_string(),
+        points,
+        metrics,
+    })
+}
+
+impl<DataType: Clone + H5Type> QuerySet<DataType> {
+    /// Adds the query points, but not the ground truth, to the given HDF5 `group`.
+    ///
+    /// This allows publishing query points while keeping the ground truth private. Reading the
+    /// group back yields a query set without ground truth.
+    pub fn add_to_without_gt(&self, group: &mut Group) -> Result<()> {
+        let mut query_group = group.create_group(QUERIES)?;
+        self.points.add_to(&mut query_group)?;
+        Ok(())
+    }
 }
 
 impl<DataType: Clone + H5Type> Hdf5Serialization for QuerySet<DataType> {
     type Object = QuerySet<DataType>;
 
     fn add_to(&self, group: &mut Group) -> Result<()> {
-        let mut query_group = group.create_group(QUERIES)?;
-        self.points.add_to(&mut query_group)?;
+        self.add_to_without_gt(group)?;
 
         let gt_group = group.create_group(GROUND_TRUTH)?;
-        self.neighbors.iter().try_for_each(|entry| {
-            let mut grp = gt_group.create_group(entry.0.to_string().as_str())?;
+        self.sorted_ground_truths().try_for_each(|entry| {
+            let mut grp = gt_group.create_group(&entry.0.canonical_name())?;
+            grp.new_attr::<VarLenUnicode>()
+                .create(METRIC)?
+                .write_scalar(&VarLenUnicode::from_str(&entry.0.canonical_name())?)?;
             entry.1.add_to(&mut grp)?;
             anyhow::Ok(())
         })?;
@@ -86,18 +513,20 @@ impl<DataType: Clone + H5Type> Hdf5Serialization for QuerySet<DataType> {
         let query_group = group.group(QUERIES)?;
         let points = PointSet::<DataType>::read_from(&query_group)?;
 
+        // A missing ground-truth group is treated as a query set without ground truth.
         let mut neighbors: HashMap<Metric, GroundTruth> = HashMap::new();
-        let gt_group = group.group(GROUND_TRUTH)?;
-        gt_group.groups()?.iter().try_for_each(|grp| {
-            let name = grp.name();
-            let name = name.split('/').last().unwrap();
-            let metric = Metric::from_str(name)?;
-            let gt = GroundTruth::read_from(grp)?;
-            neighbors.insert(metric, gt);
-            anyhow::Ok(())
-        })?;
+        if let Ok(gt_group) = group.group(GROUND_TRUTH) {
+            gt_group.groups()?.iter().try_for_each(|grp| {
+                let metric = read_metric(grp)?;
+                let gt = GroundTruth::read_from(grp)?;
+                neighbors.insert(metric, gt);
+                anyhow::Ok(())
+            })?;
+        }
 
-        Ok(QuerySet { points, neighbors })
+        let query_set = QuerySet { points, neighbors };
+        query_set.validate()?;
+        Ok(query_set)
     }
 
     fn label() -> String {
@@ -111,8 +540,7 @@ impl<DataType: Clone> Display for QuerySet<DataType> {
             f,
             "Query points: {}\nGround-truths: {}",
             self.points,
-            self.neighbors
-                .iter()
+            self.sorted_ground_truths()
                 .map(|entry| format!("{}: {}", entry.0, entry.1))
                 .collect::<Vec<_>>()
                 .join("; ")
@@ -122,10 +550,13 @@ impl<DataType: Clone> Display for QuerySet<DataType> {
 
 #[cfg(test)]
 mod tests {
-    use crate::types::Metric::{Cosine, Euclidean, InnerProduct};
-    use crate::{Hdf5Serialization, PointSet, QuerySet};
+    use crate::types::Metric::{
+        Angular, Chebyshev, Cosine, Euclidean, InnerProduct, Jaccard, Manhattan, Minkowski,
+    };
+    use crate::{GroundTruth, Hdf5Serialization, PointSet, QuerySet};
     use hdf5::File;
-    use ndarray::Array2;
+    use ndarray::{arr2, Array2};
+    use sprs::{CsMat, TriMat};
     use tempdir::TempDir;
 
     #[test]
@@ -174,6 +605,12 @@ mod tests {
         assert!(query_set
             .add_ground_truth(Euclidean, Array2::<usize>::ones((5, 1)))
             .is_ok());
+        assert!(query_set
+            .add_ground_truth(Chebyshev, Array2::<usize>::ones((5, 2)))
+            .is_ok());
+        assert!(query_set
+            .add_ground_truth(Manhattan, Array2::<usize>::ones((5, 3)))
+            .is_ok());
 
         let dir = TempDir::new("pointset_test_hdf5").unwrap();
         let path = dir.path().join("ann-dataset.hdf5");
@@ -191,6 +628,118 @@ mod tests {
         assert_eq!(&query_set, &query_set_copy);
     }
 
+    #[test]
+    fn test_hdf5_minkowski() {
+        let dense = Array2::<f64>::eye(3);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense), None).unwrap());
+        for p in 1..=3 {
+            query_set
+                .add_ground_truth(Minkowski { p }, Array2::from_elem((3, 2), p as usize))
+                .unwrap();
+        }
+        assert_eq!(query_set.sorted_ground_truths().count(), 3);
+
+        let dir = TempDir::new("test_hdf5_minkowski").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let hdf5 = File::create(path.to_str().unwrap()).unwrap();
+        let mut group = hdf5.group("/").unwrap();
+        query_set.add_to(&mut group).unwrap();
+
+        let copy = QuerySet::<f64>::read_from(&group).unwrap();
+        for p in 1..=3 {
+            assert_eq!(
+                copy.get_ground_truth(&Minkowski { p })
+                    .unwrap()
+                    .get_neighbors(),
+                Array2::from_elem((3, 2), p as usize)
+            );
+        }
+        assert!(copy.get_ground_truth(&Minkowski { p: 4 }).is_err());
+        assert_eq!(copy, query_set);
+    }
+
+    #[test]
+    fn test_hdf5_jaccard() {
+        let mut sparse = TriMat::new((3, 6));
+        sparse.add_triplet(0, 1, 1_f64);
+        sparse.add_triplet(1, 4, 1_f64);
+        sparse.add_triplet(2, 0, 1_f64);
+        let sparse: CsMat<f64> = sparse.to_csr();
+
+        let dense = QuerySet::new(PointSet::new(Some(Array2::<f64>::eye(3)), None).unwrap());
+        let mut query_set = dense.clone();
+        assert!(query_set
+            .add_ground_truth(Jaccard, Array2::zeros((3, 1)))
+            .is_err());
+        assert!(query_set
+            .add_ragged_ground_truth(Jaccard, vec![vec![]; 3])
+            .is_err());
+        assert_eq!(query_set, dense);
+
+        let mut query_set = QuerySet::new(PointSet::new(None, Some(sparse)).unwrap());
+        query_set
+            .add_ground_truth(Jaccard, arr2(&[[0_usize, 2], [1, 0], [2, 1]]))
+            .unwrap();
+
+        let dir = TempDir::new("test_hdf5_jaccard").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let hdf5 = File::create(path.to_str().unwrap()).unwrap();
+        let mut group = hdf5.group("/").unwrap();
+        query_set.add_to(&mut group).unwrap();
+        assert!(group.link_exists("gt/jaccard"));
+        assert_eq!(QuerySet::<f64>::read_from(&group).unwrap(), query_set);
+    }
+
+    #[test]
+    fn test_json() {
+        let dense = Array2::<f32>::eye(3);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense), None).unwrap());
+        query_set
+            .add_ground_truth(InnerProduct, arr2(&[[0_usize, 1], [1, 2], [2, 0]]))
+            .unwrap();
+        query_set
+            .add_ragged_ground_truth(Minkowski { p: 3 }, vec![vec![0], vec![], vec![2, 1]])
+            .unwrap();
+
+        let json = serde_json::to_string(&query_set).unwrap();
+        assert!(json.contains("\"inner-product\"") && json.contains("\"minkowski-3\""));
+        let copy: QuerySet<f32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy, query_set);
+        assert_eq!(copy.sorted_ground_truths().count(), 2);
+    }
+
+    #[test]
+    fn test_hdf5_metric_attribute() {
+        let dense = Array2::<f64>::eye(5);
+        let queries = PointSet::<f64>::new(Some(dense), None).unwrap();
+        let mut query_set = QuerySet::new(queries);
+        assert!(query_set
+            .add_ground_truth(Euclidean, Array2::<usize>::ones((5, 1)))
+            .is_ok());
+
+        let dir = TempDir::new("queryset_test_hdf5").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        let hdf5 = File::create(path).unwrap();
+
+        let mut group = hdf5.group("/").unwrap();
+        assert!(query_set.add_to(&mut group).is_ok());
+
+        // Renaming the group does not change the metric.
+        let gt_group = group.group("gt").unwrap();
+        assert!(gt_group.relink("euclidean", "renamed").is_ok());
+        let query_set_copy = QuerySet::<f64>::read_from(&group).unwrap();
+        assert_eq!(&query_set, &query_set_copy);
+
+        // Without the attribute, the group name determines the metric.
+        let mut cosine_group = gt_group.create_group("Cosine").unwrap();
+        let gt = GroundTruth::new(Array2::<usize>::zeros((5, 1)));
+        assert!(gt.add_to(&mut cosine_group).is_ok());
+        let query_set_copy = QuerySet::<f64>::read_from(&group).unwrap();
+        assert_eq!(query_set_copy.get_ground_truth(&Cosine).unwrap(), &gt);
+        assert!(query_set_copy.get_ground_truth(&Euclidean).is_ok());
+    }
+
     #[test]
     fn test_hdf5_no_gt() {
         let dense = Array2::<f64>::eye(5);
@@ -212,4 +761,232 @@ mod tests {
         let query_set_copy = QuerySet::<f64>::read_from(&group).unwrap();
         assert_eq!(&query_set, &query_set_copy);
     }
+
+    #[test]
+    fn test_hdf5_without_gt() {
+        let dense = Array2::<f64>::eye(5);
+        let queries = PointSet::<f64>::new(Some(dense), None).unwrap();
+        let mut query_set = QuerySet::new(queries.clone());
+        query_set
+            .add_ground_truth(InnerProduct, Array2::<usize>::zeros((5, 2)))
+            .unwrap();
+
+        let dir = TempDir::new("queryset_test_hdf5_without_gt").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        let hdf5 = File::create(path).unwrap();
+
+        let mut group = hdf5.group("/").unwrap();
+        assert!(query_set.add_to_without_gt(&mut group).is_ok());
+        let query_set_copy = QuerySet::<f64>::read_from(&group).unwrap();
+        assert_eq!(&queries, query_set_copy.get_points());
+        assert!(query_set_copy.get_ground_truth(&InnerProduct).is_err());
+    }
+
+    #[test]
+    fn test_add_ground_truth_batch() {
+        let dense = Array2::<f64>::eye(4);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense), None).unwrap());
+        query_set
+            .add_ground_truth(Cosine, Array2::<usize>::zeros((4, 1)))
+            .unwrap();
+        let original = query_set.clone();
+
+        let result = query_set.add_ground_truth_batch(vec![
+            (InnerProduct, Array2::<usize>::zeros((4, 2))),
+            (Euclidean, Array2::<usize>::zeros((3, 2))),
+            (Cosine, Array2::<usize>::ones((4, 2))),
+        ]);
+        assert!(result.unwrap_err().to_string().contains("Euclidean"));
+        assert_eq!(query_set, original);
+
+        query_set
+            .add_ground_truth_batch(vec![
+                (InnerProduct, Array2::<usize>::zeros((4, 2))),
+                (Cosine, Array2::<usize>::ones((4, 2))),
+            ])
+            .unwrap();
+        assert_eq!(
+            query_set.get_ground_truth(&InnerProduct).unwrap(),
+            &GroundTruth::new(Array2::<usize>::zeros((4, 2)))
+        );
+        assert_eq!(
+            query_set.get_ground_truth(&Cosine).unwrap(),
+            &GroundTruth::new(Array2::<usize>::ones((4, 2)))
+        );
+    }
+
+    #[test]
+    fn test_max_evaluable_k() {
+        let dense = Array2::<f64>::eye(3);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense), None).unwrap());
+        query_set
+            .add_ground_truth(InnerProduct, Array2::<usize>::zeros((3, 10)))
+            .unwrap();
+        query_set
+            .add_ragged_ground_truth(Cosine, vec![vec![0, 1, 2], vec![1], vec![2, 0]])
+            .unwrap();
+
+        assert_eq!(query_set.max_evaluable_k(&InnerProduct).unwrap(), 10);
+        assert_eq!(query_set.max_evaluable_k(&Cosine).unwrap(), 1);
+        assert!(query_set.max_evaluable_k(&Euclidean).is_err());
+    }
+
+    #[test]
+    fn test_assert_ranking_consistency() {
+        let dense = Array2::<f64>::eye(3);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense), None).unwrap());
+        let neighbors = arr2(&[[0_usize, 1], [1, 2], [2, 0]]);
+        query_set
+            .add_ground_truth(Cosine, neighbors.clone())
+            .unwrap();
+        query_set
+            .add_ground_truth(Angular, neighbors.clone())
+            .unwrap();
+        query_set
+            .add_ground_truth(InnerProduct, arr2(&[[0_usize, 1], [2, 1], [2, 0]]))
+            .unwrap();
+
+        assert!(query_set.assert_ranking_consistency(&[]).is_ok());
+        assert!(query_set.assert_ranking_consistency(&[Cosine]).is_ok());
+        assert!(query_set
+            .assert_ranking_consistency(&[Cosine, Angular])
+            .is_ok());
+
+        let error = query_set
+            .assert_ranking_consistency(&[Cosine, Angular, InnerProduct])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("query 1") && error.contains("InnerProduct"));
+        assert!(query_set
+            .assert_ranking_consistency(&[Cosine, Euclidean])
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let dense = Array2::<f64>::eye(4);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense), None).unwrap());
+        query_set
+            .add_ground_truth(InnerProduct, Array2::<usize>::zeros((4, 2)))
+            .unwrap();
+        query_set
+            .add_ragged_ground_truth(Cosine, vec![vec![0], vec![1, 2], vec![], vec![3]])
+            .unwrap();
+        assert!(query_set.validate().is_ok());
+
+        query_set
+            .neighbors
+            .insert(Euclidean, GroundTruth::new(Array2::<usize>::zeros((3, 2))));
+        let error = query_set.validate().unwrap_err().to_string();
+        assert!(error.contains("Euclidean"));
+    }
+
+    #[test]
+    fn test_hdf5_inconsistent() {
+        let dense = Array2::<f64>::eye(4);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense), None).unwrap());
+        query_set
+            .neighbors
+            .insert(Chebyshev, GroundTruth::new(Array2::<usize>::zeros((5, 2))));
+
+        let dir = TempDir::new("queryset_test_hdf5_inconsistent").unwrap();
+        let path = dir.path().join("ann-dataset.hdf5");
+        let path = path.to_str().unwrap();
+        let hdf5 = File::create(path).unwrap();
+
+        let mut group = hdf5.group("/").unwrap();
+        assert!(query_set.add_to(&mut group).is_ok());
+        let error = QuerySet::<f64>::read_from(&group).unwrap_err().to_string();
+        assert!(error.contains("Chebyshev"));
+    }
+
+    #[test]
+    fn test_append() {
+        let dense = Array2::<f64>::eye(3);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense.clone()), None).unwrap());
+        query_set
+            .add_ground_truth(InnerProduct, Array2::<usize>::zeros((3, 2)))
+            .unwrap();
+
+        let mut other = QuerySet::new(PointSet::new(Some(dense.clone()), None).unwrap());
+        assert!(query_set.is_appendable(&other).is_err());
+        other
+            .add_ground_truth(InnerProduct, Array2::<usize>::ones((3, 3)))
+            .unwrap();
+        assert!(query_set.is_appendable(&other).is_err());
+        other
+            .add_ground_truth(InnerProduct, Array2::<usize>::ones((3, 2)))
+            .unwrap();
+        other
+            .add_ground_truth(Euclidean, Array2::<usize>::ones((3, 2)))
+            .unwrap();
+        assert!(query_set.is_appendable(&other).is_err());
+
+        let mut other = QuerySet::new(PointSet::new(Some(Array2::<f64>::eye(2)), None).unwrap());
+        other
+            .add_ground_truth(InnerProduct, Array2::<usize>::ones((2, 2)))
+            .unwrap();
+        assert!(query_set.is_appendable(&other).is_err());
+        assert!(query_set.append(&other).is_err());
+        assert_eq!(query_set.get_points().num_points(), 3);
+
+        let mut other = QuerySet::new(PointSet::new(Some(dense.clone()), None).unwrap());
+        other
+            .add_ground_truth(InnerProduct, Array2::<usize>::ones((3, 2)))
+            .unwrap();
+        assert!(query_set.is_appendable(&other).is_ok());
+        assert!(query_set.append(&other).is_ok());
+        assert_eq!(query_set.get_points().num_points(), 6);
+
+        let gt = query_set.get_ground_truth(&InnerProduct).unwrap();
+        assert_eq!(gt.get_neighbors().shape(), &[6, 2]);
+        assert_eq!(gt.get_neighbors().row(2).to_vec(), vec![0, 0]);
+        assert_eq!(gt.get_neighbors().row(3).to_vec(), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_retain_metrics() {
+        let mut query_set =
+            QuerySet::new(PointSet::new(Some(Array2::<f32>::eye(3)), None).unwrap());
+        for metric in [InnerProduct, Euclidean, Cosine] {
+            query_set
+                .add_ground_truth(metric, Array2::<usize>::zeros((3, 2)))
+                .unwrap();
+        }
+
+        query_set.retain_metrics(&[InnerProduct, Chebyshev]);
+        assert!(query_set.get_ground_truth(&InnerProduct).is_ok());
+        assert!(query_set.get_ground_truth(&Euclidean).is_err());
+        assert!(query_set.get_ground_truth(&Cosine).is_err());
+        assert!(query_set.get_ground_truth(&Chebyshev).is_err());
+
+        query_set.retain_metrics(&[]);
+        assert!(query_set.get_ground_truth(&InnerProduct).is_err());
+    }
+
+    #[test]
+    fn test_append_ragged() {
+        let dense = Array2::<f64>::eye(2);
+        let mut query_set = QuerySet::new(PointSet::new(Some(dense.clone()), None).unwrap());
+        query_set
+            .add_ground_truth(InnerProduct, Array2::<usize>::zeros((2, 2)))
+            .unwrap();
+        assert!(query_set
+            .add_ragged_ground_truth(Euclidean, vec![vec![1]])
+            .is_err());
+
+        let mut other = QuerySet::new(PointSet::new(Some(dense.clone()), None).unwrap());
+        other
+            .add_ragged_ground_truth(InnerProduct, vec![vec![1, 2, 3], vec![]])
+            .unwrap();
+        assert!(query_set.append(&other).is_ok());
+
+        let gt = query_set.get_ground_truth(&InnerProduct).unwrap();
+        assert!(gt.is_ragged());
+        assert_eq!(gt.get_neighbors().shape(), &[4, 3]);
+        assert_eq!(gt.neighbors_of(1).to_vec(), vec![0, 0]);
+        assert_eq!(gt.neighbors_of(2).to_vec(), vec![1, 2, 3]);
+        assert_eq!(gt.num_neighbors(3), 0);
+    }
 }